@@ -6,8 +6,8 @@ use axum::handler::Handler;
 use axum::response::NoContent;
 use axum::routing::{MethodRouter, Router};
 use cs2kz::Context;
-use cs2kz::bans::{BanId, BanReason, BannedBy, CreateBanError};
-use cs2kz::pagination::{Limit, Offset, Paginated};
+use cs2kz::bans::{BanId, BanReason, BannedBy, BansCursor, CreateBanError};
+use cs2kz::pagination::{Cursor, Limit, Offset, Paginated};
 use cs2kz::players::PlayerId;
 use cs2kz::time::Timestamp;
 use cs2kz::users::{Permission, UserId};
@@ -61,6 +61,12 @@ pub struct GetBansQuery {
     #[param(value_type = Option<crate::openapi::shims::BanReason>)]
     reason: Option<BanReason>,
 
+    /// The `next_cursor` of a previous response.
+    ///
+    /// This cannot be combined with `offset`.
+    #[param(value_type = Option<crate::openapi::shims::Cursor>)]
+    cursor: Option<BansCursor>,
+
     #[serde(default)]
     #[param(value_type = crate::openapi::shims::Limit)]
     limit: Limit<1000, 100>,
@@ -194,8 +200,19 @@ async fn create_ban(
 )]
 async fn get_bans(
     State(cx): State<Context>,
-    Query(GetBansQuery { player, banned_by, reason, limit, offset }): Query<GetBansQuery>,
+    Query(GetBansQuery {
+        player,
+        banned_by,
+        reason,
+        cursor,
+        limit,
+        offset,
+    }): Query<GetBansQuery>,
 ) -> Result<Json<Paginated<Vec<Ban>>>, ErrorResponse> {
+    if cursor.is_some() && offset.value() != 0 {
+        return Err(ErrorResponse::cursor_with_offset());
+    }
+
     let player_id = match player {
         None => None,
         Some(PlayerIdentifier::Id(player_id)) => Some(player_id),
@@ -211,12 +228,21 @@ async fn get_bans(
         },
     };
 
-    let params = cs2kz::bans::GetBansParams { player_id, banned_by, reason, limit, offset };
+    let params = cs2kz::bans::GetBansParams {
+        player_id,
+        banned_by,
+        reason,
+        cursor,
+        limit,
+        offset,
+    };
+
     let bans = cs2kz::bans::get(&cx, params)
         .map_ok(Paginated::map_into)
         .and_then(Paginated::collect)
         .map_err(|err| ErrorResponse::internal_server_error(err))
-        .await?;
+        .await?
+        .with_next_cursor(limit.value(), |ban: &Ban| Cursor::new((), ban.id));
 
     Ok(Json(bans))
 }
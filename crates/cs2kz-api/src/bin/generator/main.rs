@@ -20,16 +20,7 @@ use cs2kz::maps::courses::filters::{
     GetCourseFiltersParams,
     Tier,
 };
-use cs2kz::maps::{
-    self,
-    ApproveMapError,
-    MapId,
-    MapState,
-    NewCourse,
-    NewCourseFilter,
-    NewCourseFilters,
-    NewMap,
-};
+use cs2kz::maps::{self, MapId, MapState, NewCourse, NewCourseFilter, NewCourseFilters, NewMap};
 use cs2kz::pagination::Limit;
 use cs2kz::players::{self, CreatePlayerError, GetPlayersParams, NewPlayer, PlayerId};
 use cs2kz::plugin::{self, NewPluginVersion};
@@ -79,6 +70,7 @@ async fn main() -> anyhow::Result<()> {
                 min_connections: 1,
                 max_connections: Some(NonZero::<u32>::MIN),
             },
+            ..Default::default()
         })?;
 
     let cx = Context::new(cfg).await?;
@@ -344,7 +336,6 @@ async fn create_records(
 
         match records::submit(cx, record).await {
             Ok(SubmittedRecord { record_id: id, .. }) => info!(%id, "created record"),
-            Err(SubmitRecordError::CalculatePoints(error)) => return Err(error.into()),
             Err(SubmitRecordError::Database(error)) => return Err(error.into()),
         }
     }
@@ -419,6 +410,7 @@ async fn create_maps(cx: &Context, mappers: Vec<PlayerId>, count: usize) -> anyh
             },
             state: MapState::Approved,
             vpk_checksum: Faker.fake(),
+            changelog: None,
             mappers: mappers.clone(),
             courses: (0..rng.gen_range(1..=10))
                 .map(|idx| NewCourse {
@@ -440,6 +432,7 @@ async fn create_maps(cx: &Context, mappers: Vec<PlayerId>, count: usize) -> anyh
                         vanilla: gen_course_filter(&mut rng),
                         classic: gen_course_filter(&mut rng),
                     },
+                    previous_version: None,
                 })
                 .collect::<Vec<_>>()
                 .into_boxed_slice(),
@@ -447,7 +440,7 @@ async fn create_maps(cx: &Context, mappers: Vec<PlayerId>, count: usize) -> anyh
 
         match maps::approve(cx, map).await {
             Ok(id) => info!(%id, "created map"),
-            Err(ApproveMapError::Database(error)) => return Err(error.into()),
+            Err(error) => return Err(error.into()),
         }
     }
 
@@ -3,7 +3,8 @@
 use std::net::IpAddr;
 use std::path::PathBuf;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
+use cs2kz::maps::CourseFilterId;
 
 pub fn args() -> Args {
     Args::parse()
@@ -11,6 +12,9 @@ pub fn args() -> Args {
 
 #[derive(Debug, Parser)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+
     /// The IP address the HTTP server will listen on.
     ///
     /// This takes precedence over the value in the configuration file.
@@ -35,6 +39,25 @@ pub struct Args {
     pub depot_downloader_path: Option<PathBuf>,
 }
 
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Verify that the tables derived from `Records` agree with it, instead of running the API.
+    ///
+    /// This should only be run while the API is offline. Exits with an error if any violations
+    /// were found and `--repair` was not specified.
+    CheckConsistency {
+        /// Only check this filter.
+        #[arg(long = "filter")]
+        filter_id: Option<CourseFilterId>,
+
+        /// Rebuild the derived tables of every filter with violations.
+        ///
+        /// Their points are recalculated the next time the API starts.
+        #[arg(long)]
+        repair: bool,
+    },
+}
+
 impl Args {
     /// Applies any overrides specified as CLI flags to the given config.
     pub fn apply_to_config(&self, config: &mut cs2kz_api::Config) {
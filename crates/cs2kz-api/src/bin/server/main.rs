@@ -28,7 +28,27 @@ fn main() -> anyhow::Result<()> {
         init_tracing(&config.tracing).context("failed to initialize tracing")?;
     }
 
-    cs2kz_api::run(config).context("failed to run API")
+    match cli_args.command {
+        None => cs2kz_api::run(config).context("failed to run API"),
+        Some(cli::Command::CheckConsistency { filter_id, repair }) => {
+            let violations = cs2kz_api::check_consistency(config, filter_id, repair)
+                .context("failed to check database consistency")?;
+
+            for violation in &violations {
+                println!("{violation}");
+            }
+
+            if violations.is_empty() {
+                println!("no violations found");
+            } else if repair {
+                println!("found and repaired {} violations", violations.len());
+            } else {
+                anyhow::bail!("found {} violations", violations.len());
+            }
+
+            Ok(())
+        },
+    }
 }
 
 fn read_and_parse_config_file(path: &Path) -> anyhow::Result<cs2kz_api::Config> {
@@ -1,26 +1,41 @@
 use std::path::PathBuf;
 
-use cs2kz::steam::WorkshopId;
-
 #[derive(Debug, serde::Deserialize)]
 #[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
 pub struct DepotDownloaderConfig {
+    /// How map files should be acquired.
+    pub backend: MapDownloaderBackend,
+
     #[serde(default = "default_exe_path")]
     pub exe_path: PathBuf,
 
+    /// Where `DepotDownloader` should put downloaded files.
+    ///
+    /// With the [`Local`] backend, this is where pre-downloaded files are read from instead.
+    ///
+    /// [`Local`]: MapDownloaderBackend::Local
     #[serde(default = "default_out_dir")]
     pub out_dir: PathBuf,
 }
 
-impl DepotDownloaderConfig {
-    pub fn vpk_path(&self, workshop_id: WorkshopId) -> PathBuf {
-        self.out_dir.join(format!("{workshop_id}.vpk"))
-    }
+#[derive(Debug, Default, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum MapDownloaderBackend {
+    /// Download maps from the Steam workshop using `DepotDownloader`.
+    #[default]
+    DepotDownloader,
+
+    /// Read pre-downloaded maps from `{out_dir}/{workshop_id}.vpk`.
+    Local,
+
+    /// Don't read any files; checksums are derived from workshop IDs.
+    Fake,
 }
 
 impl Default for DepotDownloaderConfig {
     fn default() -> Self {
         Self {
+            backend: MapDownloaderBackend::default(),
             exe_path: default_exe_path(),
             out_dir: default_out_dir(),
         }
@@ -0,0 +1,21 @@
+use std::num::NonZero;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct ExportsConfig {
+    /// How many exports a single client may download at the same time.
+    #[serde(default = "default_max_concurrent_per_client")]
+    pub max_concurrent_per_client: NonZero<usize>,
+}
+
+impl Default for ExportsConfig {
+    fn default() -> Self {
+        Self {
+            max_concurrent_per_client: default_max_concurrent_per_client(),
+        }
+    }
+}
+
+fn default_max_concurrent_per_client() -> NonZero<usize> {
+    NonZero::new(2).unwrap()
+}
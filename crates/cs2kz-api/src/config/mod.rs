@@ -17,7 +17,16 @@ mod steam_auth;
 pub use steam_auth::SteamAuthConfig;
 
 mod depot_downloader;
-pub use depot_downloader::DepotDownloaderConfig;
+pub use depot_downloader::{DepotDownloaderConfig, MapDownloaderBackend};
+
+mod exports;
+pub use exports::ExportsConfig;
+
+mod workshop_updates;
+pub use workshop_updates::WorkshopUpdatesConfig;
+
+mod workshop_metadata;
+pub use workshop_metadata::WorkshopMetadataConfig;
 
 #[derive(Debug, Default, serde::Deserialize)]
 #[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
@@ -40,6 +49,15 @@ pub struct Config {
     pub steam_auth: SteamAuthConfig,
     pub depot_downloader: DepotDownloaderConfig,
 
+    /// Configuration for detecting map updates on the Steam workshop.
+    pub workshop_updates: WorkshopUpdatesConfig,
+
+    /// Configuration for refreshing the workshop metadata of maps.
+    pub workshop_metadata: WorkshopMetadataConfig,
+
+    /// Configuration for bulk exports.
+    pub exports: ExportsConfig,
+
     #[serde(flatten)]
     pub cs2kz: cs2kz::Config,
 }
@@ -0,0 +1,47 @@
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WorkshopMetadataConfig {
+    /// Whether to periodically refresh the workshop metadata of maps.
+    #[serde(default = "default_enable")]
+    pub enable: bool,
+
+    /// How often to refresh metadata (in seconds).
+    #[serde(default = "default_interval", deserialize_with = "deserialize_interval")]
+    pub interval: Duration,
+
+    /// Where to store mirrored preview images.
+    #[serde(default = "default_preview_dir")]
+    pub preview_dir: PathBuf,
+}
+
+impl Default for WorkshopMetadataConfig {
+    fn default() -> Self {
+        Self {
+            enable: default_enable(),
+            interval: default_interval(),
+            preview_dir: default_preview_dir(),
+        }
+    }
+}
+
+fn default_enable() -> bool {
+    true
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(60 * 60 * 24)
+}
+
+fn default_preview_dir() -> PathBuf {
+    PathBuf::from("/var/lib/cs2kz-api/previews/")
+}
+
+fn deserialize_interval<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    <u64 as serde::Deserialize<'de>>::deserialize(deserializer).map(Duration::from_secs)
+}
@@ -0,0 +1,37 @@
+use std::time::Duration;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct WorkshopUpdatesConfig {
+    /// Whether to periodically check approved maps for updates on the Steam workshop.
+    #[serde(default = "default_enable")]
+    pub enable: bool,
+
+    /// How often to check for updates (in seconds).
+    #[serde(default = "default_interval", deserialize_with = "deserialize_interval")]
+    pub interval: Duration,
+}
+
+impl Default for WorkshopUpdatesConfig {
+    fn default() -> Self {
+        Self {
+            enable: default_enable(),
+            interval: default_interval(),
+        }
+    }
+}
+
+fn default_enable() -> bool {
+    true
+}
+
+fn default_interval() -> Duration {
+    Duration::from_secs(60 * 60 * 6)
+}
+
+fn deserialize_interval<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    <u64 as serde::Deserialize<'de>>::deserialize(deserializer).map(Duration::from_secs)
+}
@@ -0,0 +1,262 @@
+//! Bulk exports.
+//!
+//! Some endpoints allow downloading entire datasets at once. The values are streamed directly
+//! from the database into the response body as either [NDJSON] or [CSV], depending on the
+//! request's `Accept` header.
+//!
+//! [NDJSON]: https://github.com/ndjson/ndjson-spec
+//! [CSV]: https://www.rfc-editor.org/rfc/rfc4180
+
+use std::fmt::{self, Write as _};
+
+use axum::body::Body;
+use axum::extract::FromRequestParts;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use cs2kz::Context;
+use futures_util::stream::{self, BoxStream, Stream, StreamExt};
+use tokio::sync::mpsc;
+
+use crate::response::ErrorResponse;
+
+/// The format an export is encoded in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Newline-delimited JSON (`application/x-ndjson`).
+    Ndjson,
+
+    /// Comma-separated values (`text/csv`).
+    Csv,
+}
+
+impl ExportFormat {
+    const fn content_type(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "application/x-ndjson",
+            Self::Csv => "text/csv; charset=utf-8",
+        }
+    }
+
+    const fn file_extension(&self) -> &'static str {
+        match self {
+            Self::Ndjson => "ndjson",
+            Self::Csv => "csv",
+        }
+    }
+
+    /// Picks the most preferred format out of an `Accept` header value.
+    ///
+    /// Returns [`None`] if none of the supported formats are acceptable.
+    fn negotiate(accept: &str) -> Option<Self> {
+        let mut best = None::<(Self, f32)>;
+
+        for media_range in accept.split(',') {
+            let mut parts = media_range.split(';').map(str::trim);
+            let format = match parts.next() {
+                Some("application/x-ndjson" | "application/*" | "*/*") => Self::Ndjson,
+                Some("text/csv" | "text/*") => Self::Csv,
+                _ => continue,
+            };
+
+            let quality = match parts.find_map(|param| param.strip_prefix("q=")) {
+                None => 1.0,
+                Some(quality) => match quality.parse::<f32>() {
+                    Ok(quality) => quality,
+                    Err(_) => continue,
+                },
+            };
+
+            if quality > 0.0 && best.is_none_or(|(_, best_quality)| quality > best_quality) {
+                best = Some((format, quality));
+            }
+        }
+
+        best.map(|(format, _)| format)
+    }
+}
+
+impl<S> FromRequestParts<S> for ExportFormat
+where
+    S: Send + Sync,
+{
+    type Rejection = ErrorResponse;
+
+    #[tracing::instrument(level = "trace", skip_all, err(Debug, level = "debug"))]
+    async fn from_request_parts(
+        request: &mut http::request::Parts,
+        _state: &S,
+    ) -> Result<Self, Self::Rejection> {
+        let mut accept = request
+            .headers
+            .get_all(http::header::ACCEPT)
+            .iter()
+            .peekable();
+
+        if accept.peek().is_none() {
+            return Ok(Self::Ndjson);
+        }
+
+        accept
+            .filter_map(|value| value.to_str().ok())
+            .find_map(Self::negotiate)
+            .ok_or_else(ErrorResponse::not_acceptable)
+    }
+}
+
+/// A type which can be exported as a CSV row.
+pub trait ToCsv {
+    /// The column names.
+    const HEADER: &'static [&'static str];
+
+    /// Writes one field per column into `row`.
+    fn write_row(&self, row: &mut CsvRow<'_>);
+}
+
+/// A single CSV row that is currently being written.
+#[derive(Debug)]
+pub struct CsvRow<'a> {
+    buf: &'a mut String,
+    is_first: bool,
+}
+
+impl CsvRow<'_> {
+    /// Appends a field to the row, quoting it if necessary.
+    pub fn field(&mut self, value: impl fmt::Display) -> &mut Self {
+        if !self.is_first {
+            self.buf.push(',');
+        }
+
+        self.is_first = false;
+
+        let start = self.buf.len();
+
+        write!(self.buf, "{value}").expect("writing into a `String` never fails");
+
+        if self.buf[start..].contains([',', '"', '\n', '\r']) {
+            let escaped = self.buf[start..].replace('"', "\"\"");
+            self.buf.truncate(start);
+            self.buf.push('"');
+            self.buf.push_str(&escaped);
+            self.buf.push('"');
+        }
+
+        self
+    }
+
+    /// Appends an optional field to the row; [`None`] is written as an empty field.
+    pub fn opt_field(&mut self, value: Option<impl fmt::Display>) -> &mut Self {
+        match value {
+            Some(value) => self.field(value),
+            None => self.field(""),
+        }
+    }
+}
+
+/// A streaming export response.
+#[derive(Debug)]
+pub struct Export<S> {
+    format: ExportFormat,
+    name: &'static str,
+    values: S,
+}
+
+impl<S> Export<S> {
+    /// Creates a new [`Export`].
+    ///
+    /// `name` is used as the filename in the `Content-Disposition` header.
+    pub fn new(format: ExportFormat, name: &'static str, values: S) -> Self {
+        Self { format, name, values }
+    }
+}
+
+impl<S, T, E> IntoResponse for Export<S>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: serde::Serialize + ToCsv,
+    E: std::error::Error + Send + Sync + 'static,
+{
+    fn into_response(self) -> Response {
+        let Self { format, name, values } = self;
+
+        let header = match format {
+            ExportFormat::Ndjson => None,
+            ExportFormat::Csv => {
+                let mut header = T::HEADER.join(",");
+                header.push_str("\r\n");
+                Some(Ok(Bytes::from(header)))
+            },
+        };
+
+        let values = values.map(move |value| -> Result<Bytes, axum::BoxError> {
+            let value = value.inspect_err(|err| {
+                error!(error = err as &dyn std::error::Error, "failed to export value");
+            })?;
+
+            match format {
+                ExportFormat::Ndjson => {
+                    let mut line = serde_json::to_vec(&value)?;
+                    line.push(b'\n');
+                    Ok(Bytes::from(line))
+                },
+                ExportFormat::Csv => {
+                    let mut line = String::new();
+                    value.write_row(&mut CsvRow { buf: &mut line, is_first: true });
+                    line.push_str("\r\n");
+                    Ok(Bytes::from(line))
+                },
+            }
+        });
+
+        let content_disposition =
+            format!("attachment; filename=\"{name}.{}\"", format.file_extension());
+
+        (
+            [
+                (http::header::CONTENT_TYPE, format.content_type().to_owned()),
+                (http::header::CONTENT_DISPOSITION, content_disposition),
+            ],
+            Body::from_stream(stream::iter(header).chain(values)),
+        )
+            .into_response()
+    }
+}
+
+/// Drives the stream returned by `make_stream` on a background task and forwards its items.
+///
+/// Database streams borrow the [`Context`] they were created from, so they cannot be used as
+/// response bodies directly. The channel in between is bounded, so the database is only polled
+/// as fast as the client reads the response.
+pub fn spawn_stream<T, E, F>(
+    cx: &Context,
+    make_stream: F,
+) -> impl Stream<Item = Result<T, E>> + use<T, E, F>
+where
+    T: Send + 'static,
+    E: Send + 'static,
+    F: for<'cx> FnOnce(&'cx Context) -> BoxStream<'cx, Result<T, E>> + Send + 'static,
+{
+    let (tx, mut rx) = mpsc::channel(64);
+    let task_cx = cx.clone();
+
+    cx.spawn("export", move |cancellation_token| async move {
+        let mut values = make_stream(&task_cx);
+
+        loop {
+            select! {
+                () = cancellation_token.cancelled() => break,
+                () = tx.closed() => break,
+                value = values.next() => {
+                    let Some(value) = value else {
+                        break;
+                    };
+
+                    if tx.send(value).await.is_err() {
+                        break;
+                    }
+                },
+            }
+        }
+    });
+
+    stream::poll_fn(move |cx| rx.poll_recv(cx))
+}
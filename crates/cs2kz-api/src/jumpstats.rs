@@ -1,26 +1,34 @@
 use axum::extract::{FromRef, State};
 use axum::routing::{self, Router};
 use cs2kz::Context;
-use cs2kz::jumpstats::{JumpType, JumpstatId};
+use cs2kz::jumpstats::{GetJumpstatsError, JumpType, JumpstatId, JumpstatsCursor};
 use cs2kz::mode::Mode;
-use cs2kz::pagination::{Limit, Offset, Paginated};
+use cs2kz::pagination::{Cursor, Limit, Offset, Paginated};
 use cs2kz::styles::Styles;
 use cs2kz::time::{Seconds, Timestamp};
-use futures_util::TryFutureExt;
+use futures_util::stream::{Stream, StreamExt};
+use futures_util::{TryFutureExt, TryStreamExt};
 
+use crate::export::{self, CsvRow, Export, ExportFormat, ToCsv};
 use crate::extract::{Json, Path, Query};
+use crate::middleware::per_client_limit::{PerClientLimit, per_client_limit};
 use crate::players::PlayerInfo;
 use crate::replays::ReplayFile;
 use crate::response::ErrorResponse;
 use crate::servers::ServerInfo;
 
-pub fn router<S>() -> Router<S>
+pub fn router<S>(export_limit: PerClientLimit) -> Router<S>
 where
     S: Clone + Send + Sync + 'static,
     Context: FromRef<S>,
 {
     Router::new()
         .route("/", routing::get(get_jumpstats))
+        .route(
+            "/export",
+            routing::get(export_jumpstats)
+                .layer(axum::middleware::from_fn_with_state(export_limit, per_client_limit)),
+        )
         .route("/{jumpstat_id}", routing::get(get_jumpstat))
         .route("/{jumpstat_id}/replay", routing::get(get_jumpstat_replay))
 }
@@ -64,6 +72,12 @@ pub struct Jumpstat {
 #[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
 #[into_params(parameter_in = Query)]
 pub struct GetJumpstatsQuery {
+    /// The `next_cursor` of a previous response.
+    ///
+    /// This cannot be combined with `offset`.
+    #[param(value_type = Option<crate::openapi::shims::Cursor>)]
+    cursor: Option<JumpstatsCursor>,
+
     #[serde(default)]
     #[param(value_type = crate::openapi::shims::Limit)]
     limit: Limit<1000, 100>,
@@ -87,18 +101,51 @@ pub struct GetJumpstatsQuery {
 )]
 async fn get_jumpstats(
     State(cx): State<Context>,
-    Query(GetJumpstatsQuery { limit, offset }): Query<GetJumpstatsQuery>,
+    Query(GetJumpstatsQuery { cursor, limit, offset }): Query<GetJumpstatsQuery>,
 ) -> Result<Json<Paginated<Vec<Jumpstat>>>, ErrorResponse> {
-    let params = cs2kz::jumpstats::GetJumpstatsParams { limit, offset };
+    if cursor.is_some() && offset.value() != 0 {
+        return Err(ErrorResponse::cursor_with_offset());
+    }
+
+    let params = cs2kz::jumpstats::GetJumpstatsParams { cursor, limit, offset };
     let jumpstats = cs2kz::jumpstats::get(&cx, params)
         .map_ok(Paginated::map_into)
         .and_then(Paginated::collect)
         .map_err(|err| ErrorResponse::internal_server_error(err))
-        .await?;
+        .await?
+        .with_next_cursor(limit.value(), |jumpstat: &Jumpstat| Cursor::new((), jumpstat.id));
 
     Ok(Json(jumpstats))
 }
 
+/// Exports all jumpstats.
+///
+/// The response is streamed as either NDJSON or CSV, depending on the `Accept` header. Jumpstats
+/// are ordered by their ID.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/jumpstats/export",
+    tag = "Jumpstats",
+    responses(
+        (status = 200, content(
+            (Jumpstat = "application/x-ndjson"),
+            (String = "text/csv"),
+        )),
+        (status = 406, description = "none of the supported formats are acceptable"),
+        (status = 429, description = "too many concurrent exports"),
+    ),
+)]
+async fn export_jumpstats(
+    format: ExportFormat,
+    State(cx): State<Context>,
+) -> Export<impl Stream<Item = Result<Jumpstat, GetJumpstatsError>>> {
+    let jumpstats =
+        export::spawn_stream(&cx, |cx| cs2kz::jumpstats::export(cx).map_ok(Jumpstat::from).boxed());
+
+    Export::new(format, "jumpstats", jumpstats)
+}
+
 /// Returns the jumpstat with the specified ID.
 #[tracing::instrument(skip(cx))]
 #[utoipa::path(
@@ -175,3 +222,61 @@ impl From<cs2kz::jumpstats::Jumpstat> for Jumpstat {
         }
     }
 }
+
+impl ToCsv for Jumpstat {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "player_id",
+        "player_name",
+        "server_id",
+        "server_name",
+        "mode",
+        "styles",
+        "jump_type",
+        "time",
+        "strafes",
+        "distance",
+        "sync",
+        "pre",
+        "max",
+        "overlap",
+        "bad_angles",
+        "dead_air",
+        "height",
+        "airpath",
+        "deviation",
+        "average_width",
+        "submitted_at",
+    ];
+
+    fn write_row(&self, row: &mut CsvRow<'_>) {
+        row.field(self.id)
+            .field(self.player.id)
+            .field(&self.player.name)
+            .field(self.server.id)
+            .field(&self.server.name)
+            .field(self.mode.as_str())
+            .field(
+                self.styles
+                    .iter()
+                    .map(|style| style.as_str())
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            )
+            .field(self.jump_type.as_str())
+            .field(self.time.0.as_secs_f64())
+            .field(self.strafes)
+            .field(self.distance)
+            .field(self.sync)
+            .field(self.pre)
+            .field(self.max)
+            .field(self.overlap)
+            .field(self.bad_angles)
+            .field(self.dead_air)
+            .field(self.height)
+            .field(self.airpath)
+            .field(self.deviation)
+            .field(self.average_width)
+            .field(self.submitted_at);
+    }
+}
@@ -0,0 +1,195 @@
+use axum::extract::{FromRef, State};
+use axum::routing::{self, Router};
+use cs2kz::Context;
+use cs2kz::maps::CourseFilterId;
+use cs2kz::time::{Seconds, Timestamp};
+
+use crate::extract::{Json, Path, Query};
+use crate::response::ErrorResponse;
+
+pub fn router<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Context: FromRef<S>,
+{
+    Router::new()
+        .route("/{filter_id}/projection", routing::get(get_projection))
+        .route("/{filter_id}/distribution", routing::get(get_distribution))
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetProjectionQuery {
+    /// The time to project, in seconds.
+    #[serde(deserialize_with = "crate::serde::deserialize_positive_seconds")]
+    #[param(value_type = f64, minimum = 0)]
+    time: Seconds,
+
+    /// How many teleports were used.
+    #[serde(default)]
+    teleports: u32,
+}
+
+/// The ranks and points a time would achieve on a course filter.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct Projection {
+    /// The projection for the NUB leaderboard.
+    ///
+    /// This is `null` if the course filter is not ranked, or its NUB tier is not humanly possible.
+    nub: Option<ProjectedRecord>,
+
+    /// The projection for the PRO leaderboard.
+    ///
+    /// This is `null` if the course filter is not ranked, the time has teleports, or the course
+    /// filter's PRO tier is not humanly possible.
+    pro: Option<ProjectedRecord>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ProjectedRecord {
+    /// The rank the time would achieve.
+    rank: u32,
+
+    /// How many records are currently on the leaderboard.
+    leaderboard_size: u32,
+
+    /// The points the time would be awarded.
+    points: f64,
+
+    /// Whether `points` were calculated based on the PRO leaderboard, rather than the NUB
+    /// leaderboard.
+    based_on_pro_leaderboard: bool,
+}
+
+impl From<cs2kz::points::Projection> for Projection {
+    fn from(projection: cs2kz::points::Projection) -> Self {
+        Self {
+            nub: projection.nub.map(Into::into),
+            pro: projection.pro.map(Into::into),
+        }
+    }
+}
+
+impl From<cs2kz::points::ProjectedRecord> for ProjectedRecord {
+    fn from(record: cs2kz::points::ProjectedRecord) -> Self {
+        Self {
+            rank: record.rank,
+            leaderboard_size: record.leaderboard_size,
+            points: record.points,
+            based_on_pro_leaderboard: record.based_on_pro_leaderboard,
+        }
+    }
+}
+
+/// The point distributions fitted to a course filter's leaderboards.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct DistributionData {
+    /// The distribution of the NUB leaderboard, if it has been calculated yet.
+    nub: Option<LeaderboardDistribution>,
+
+    /// The distribution of the PRO leaderboard, if it has been calculated yet.
+    pro: Option<LeaderboardDistribution>,
+}
+
+/// [Normal-inverse Gaussian distribution][norminvgauss] parameters.
+///
+/// The share of distribution points awarded for a time `t` is
+/// `sf((t - loc) / scale, a, b) / top_scale`, where `sf` is the distribution's survival
+/// function. Leaderboards with [`SMALL_LEADERBOARD_THRESHOLD`] or fewer records do not use the
+/// distribution.
+///
+/// [norminvgauss]: https://docs.scipy.org/doc/scipy/reference/generated/scipy.stats.norminvgauss.html
+/// [`SMALL_LEADERBOARD_THRESHOLD`]: cs2kz::points::SMALL_LEADERBOARD_THRESHOLD
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct LeaderboardDistribution {
+    a: f64,
+    b: f64,
+    loc: f64,
+    scale: f64,
+
+    /// The value of the survival function for the top time.
+    top_scale: f64,
+
+    /// How many records the distribution was fitted on.
+    leaderboard_size: u32,
+
+    /// When the distribution was last recalculated.
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    updated_at: Timestamp,
+}
+
+impl From<cs2kz::points::DistributionData> for DistributionData {
+    fn from(data: cs2kz::points::DistributionData) -> Self {
+        Self {
+            nub: data.nub.map(Into::into),
+            pro: data.pro.map(Into::into),
+        }
+    }
+}
+
+impl From<cs2kz::points::LeaderboardDistribution> for LeaderboardDistribution {
+    fn from(leaderboard: cs2kz::points::LeaderboardDistribution) -> Self {
+        Self {
+            a: leaderboard.dist.a,
+            b: leaderboard.dist.b,
+            loc: leaderboard.dist.loc,
+            scale: leaderboard.dist.scale,
+            top_scale: leaderboard.dist.top_scale,
+            leaderboard_size: leaderboard.leaderboard_size,
+            updated_at: leaderboard.updated_at,
+        }
+    }
+}
+
+/// Calculates the rank and points a time would achieve on a course filter.
+///
+/// Nothing is submitted; this only shows what a record with this time *would* get.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/leaderboards/{filter_id}/projection",
+    tag = "Records",
+    params(("filter_id" = u16, Path), GetProjectionQuery),
+    responses(
+        (status = 200, body = Projection),
+        (status = 400, description = "invalid path parameters or query string"),
+        (status = 404,),
+    ),
+)]
+async fn get_projection(
+    State(cx): State<Context>,
+    Path(filter_id): Path<CourseFilterId>,
+    Query(GetProjectionQuery { time, teleports }): Query<GetProjectionQuery>,
+) -> Result<Json<Projection>, ErrorResponse> {
+    let projection = cs2kz::points::project(&cx, filter_id, time, teleports)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .ok_or_else(ErrorResponse::not_found)?;
+
+    Ok(Json(projection.into()))
+}
+
+/// Returns the point distributions fitted to a course filter's leaderboards.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/leaderboards/{filter_id}/distribution",
+    tag = "Records",
+    params(("filter_id" = u16, Path)),
+    responses(
+        (status = 200, body = DistributionData),
+        (status = 400, description = "invalid path parameters"),
+        (status = 404,),
+    ),
+)]
+async fn get_distribution(
+    State(cx): State<Context>,
+    Path(filter_id): Path<CourseFilterId>,
+) -> Result<Json<DistributionData>, ErrorResponse> {
+    let data = cs2kz::points::get_distribution_data(&cx, filter_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .ok_or_else(ErrorResponse::not_found)?;
+
+    Ok(Json(data.into()))
+}
@@ -35,6 +35,8 @@ use std::{future, io};
 
 use axum::{Router, ServiceExt, routing};
 use cs2kz::Context;
+use cs2kz::maps::CourseFilterId;
+use cs2kz::points::consistency::{self, Violation};
 use futures_util::FutureExt as _;
 use tokio::sync::oneshot;
 use tokio::task;
@@ -59,8 +61,11 @@ pub mod players;
 pub mod maps;
 pub mod jumpstats;
 pub mod records;
+pub mod leaderboards;
 pub mod bans;
+pub mod points;
 
+mod export;
 mod extract;
 mod problem_details;
 mod replays;
@@ -89,6 +94,44 @@ pub enum Error {
     #[display("failed to run server: {_0}")]
     #[from(ignore)]
     RunServer(io::Error),
+
+    #[display("{_0}")]
+    CheckConsistency(cs2kz::points::consistency::CheckConsistencyError),
+
+    #[display("{_0}")]
+    RepairConsistency(cs2kz::points::consistency::RepairConsistencyError),
+}
+
+/// Check the database for inconsistencies between `Records` and the tables derived from it.
+///
+/// If `repair` is `true`, every filter with violations is rebuilt afterwards. This should only be
+/// run while the API is offline; see [`cs2kz::points::consistency`].
+///
+/// Like [`run()`], this function will initialize its own [`tokio`] runtime and **block** until it
+/// is done.
+pub fn check_consistency(
+    config: Config,
+    filter_id: Option<CourseFilterId>,
+    repair: bool,
+) -> Result<Vec<Violation>, Error> {
+    runtime::build(&config.runtime)
+        .map_err(Error::InitializeRuntime)?
+        .block_on(async {
+            let cx = Context::new(config.cs2kz).await?;
+            let result = async {
+                let violations = consistency::check(&cx, filter_id).await?;
+
+                if repair && !violations.is_empty() {
+                    consistency::repair(&cx, violations.iter().map(Violation::filter_id)).await?;
+                }
+
+                Ok(violations)
+            }
+            .await;
+
+            cx.cleanup().await;
+            result
+        })
 }
 
 /// Run the API.
@@ -104,6 +147,16 @@ pub fn run(config: Config) -> Result<(), Error> {
             let cookie_config = Arc::new(config.cookies);
             let steam_auth_config = Arc::new(config.steam_auth);
             let depot_downloader_config = Arc::new(config.depot_downloader);
+            let workshop_updates_config = config.workshop_updates;
+            let workshop_metadata_config = config.workshop_metadata;
+            let map_approval_jobs = maps::jobs::Jobs::new(cx.clone());
+            let previews = Arc::new(maps::workshop_metadata::PreviewCache::new(
+                workshop_metadata_config.preview_dir.clone(),
+                steam_auth_config.public_url.clone(),
+            ));
+            let export_limit = middleware::per_client_limit::PerClientLimit::new(
+                config.exports.max_concurrent_per_client,
+            );
 
             let router = Router::new()
                 .route("/", routing::get("(͡ ͡° ͜ つ ͡͡°)"))
@@ -132,13 +185,17 @@ pub fn run(config: Config) -> Result<(), Error> {
                     maps::router(
                         cx.clone(),
                         Arc::clone(&cookie_config),
-                        Arc::clone(&steam_auth_config),
-                        depot_downloader_config,
+                        &steam_auth_config,
+                        &depot_downloader_config,
+                        map_approval_jobs.clone(),
+                        Arc::clone(&previews),
                     ),
                 )
-                .nest("/jumpstats", jumpstats::router())
-                .nest("/records", records::router())
-                .nest("/bans", bans::router(cx.clone(), Arc::clone(&cookie_config)));
+                .nest("/jumpstats", jumpstats::router(export_limit.clone()))
+                .nest("/records", records::router(export_limit))
+                .nest("/leaderboards", leaderboards::router())
+                .nest("/bans", bans::router(cx.clone(), Arc::clone(&cookie_config)))
+                .nest("/points", points::router(cx.clone(), Arc::clone(&cookie_config)));
 
             cfg_taskdump! {
                 let router = router.nest("/taskdump", {
@@ -188,6 +245,34 @@ pub fn run(config: Config) -> Result<(), Error> {
                 cs2kz::points::daemon::run(cx.clone(), cancellation_token)
             });
 
+            cx.spawn("rating-snapshots", |cancellation_token| {
+                cs2kz::players::rating_history::run(cx.clone(), cancellation_token)
+            });
+
+            if workshop_updates_config.enable {
+                cx.spawn("workshop-updates", |cancellation_token| {
+                    maps::workshop_updates::run(
+                        cx.clone(),
+                        workshop_updates_config.interval,
+                        depot_downloader_config,
+                        steam_auth_config,
+                        map_approval_jobs,
+                        cancellation_token,
+                    )
+                });
+            }
+
+            if workshop_metadata_config.enable {
+                cx.spawn("workshop-metadata", |cancellation_token| {
+                    maps::workshop_metadata::run(
+                        cx.clone(),
+                        workshop_metadata_config.interval,
+                        previews,
+                        cancellation_token,
+                    )
+                });
+            }
+
             select! {
                 biased;
 
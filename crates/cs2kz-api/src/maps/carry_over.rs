@@ -0,0 +1,190 @@
+use std::sync::Arc;
+
+use axum::extract::{FromRef, State};
+use axum::handler::Handler;
+use axum::routing::{MethodRouter, Router};
+use cs2kz::Context;
+use cs2kz::maps::carry_over::{CarryOverError, CarryOverId};
+use cs2kz::maps::{CourseFilterId, MapId};
+use cs2kz::time::Timestamp;
+use cs2kz::users::Permission;
+
+use crate::config::CookieConfig;
+use crate::extract::{Json, Path};
+use crate::middleware::auth::session_auth;
+use crate::middleware::auth::session_auth::Session;
+use crate::middleware::auth::session_auth::authorization::HasPermissions;
+use crate::response::{Created, ErrorResponse};
+use crate::users::UserInfo;
+
+pub(super) fn router<S>(cx: Context, cookie_config: Arc<CookieConfig>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Context: FromRef<S>,
+{
+    let is_admin = axum::middleware::from_fn_with_state(
+        session_auth::State::new(cx, cookie_config)
+            .authorize_with(HasPermissions::new(Permission::MapPool)),
+        session_auth,
+    );
+
+    Router::new().route(
+        "/{map_id}/carry-overs",
+        MethodRouter::new()
+            .post(carry_over_records.layer(is_admin.clone()))
+            .get(get_carry_overs.layer(is_admin)),
+    )
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct NewCarryOver {
+    /// The filters to copy records between.
+    ///
+    /// If you omit this, records are copied between the filters of courses that were linked to
+    /// the previous version when the map was approved.
+    #[serde(default, deserialize_with = "crate::serde::deserialize_non_empty")]
+    filters: Option<Vec<FilterMapping>>,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct FilterMapping {
+    /// A filter on an older version of the map.
+    #[schema(value_type = u16, minimum = 1)]
+    from: CourseFilterId,
+
+    /// The filter on this map to copy the records to.
+    #[schema(value_type = u16, minimum = 1)]
+    to: CourseFilterId,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct CarryOver {
+    #[schema(value_type = u32, minimum = 1)]
+    id: CarryOverId,
+
+    /// The map the records were carried over to.
+    #[schema(value_type = u16, minimum = 1)]
+    map_id: MapId,
+
+    /// The admin who carried the records over.
+    performed_by: UserInfo,
+
+    filters: Vec<CarriedOverFilter>,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    created_at: Timestamp,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct CarriedOverFilter {
+    #[schema(value_type = u16, minimum = 1)]
+    from_filter_id: CourseFilterId,
+
+    #[schema(value_type = u16, minimum = 1)]
+    to_filter_id: CourseFilterId,
+
+    /// How many records were copied.
+    ///
+    /// Records that had already been carried over before are not copied again.
+    records: u64,
+}
+
+/// Carries records over from an older version of a map.
+///
+/// Records are copied from the old filters to the new ones, and the new filters' leaderboards are
+/// rebuilt. Their points are recalculated in the background; see `GET /points/status`.
+#[tracing::instrument(skip(cx, session), fields(session.id = %session.id()))]
+#[utoipa::path(
+    post,
+    path = "/maps/{map_id}/carry-overs",
+    tag = "Maps",
+    params(("map_id" = u16, Path, description = "the map to carry records over to")),
+    request_body = NewCarryOver,
+    responses(
+        (status = 201, body = CarryOver),
+        (status = 400, description = "invalid path parameters"),
+        (status = 401,),
+        (status = 404,),
+        (status = 409, description = "the records cannot be carried over"),
+        (status = 422, description = "invalid request body"),
+    ),
+)]
+async fn carry_over_records(
+    State(cx): State<Context>,
+    session: Session,
+    Path(map_id): Path<MapId>,
+    Json(NewCarryOver { filters }): Json<NewCarryOver>,
+) -> Result<Created<CarryOver>, ErrorResponse> {
+    let filters = filters.map(|filters| {
+        filters
+            .into_iter()
+            .map(|FilterMapping { from, to }| cs2kz::maps::carry_over::FilterMapping { from, to })
+            .collect::<Vec<_>>()
+    });
+
+    let carry_over = cs2kz::maps::carry_over::NewCarryOver {
+        map_id,
+        performed_by: session.user().id(),
+        filters: filters.as_deref(),
+    };
+
+    match cs2kz::maps::carry_over::carry_over(&cx, carry_over).await {
+        Ok(Some(carry_over)) => Ok(Created(carry_over.into())),
+        Ok(None) => Err(ErrorResponse::not_found()),
+        Err(CarryOverError::Database(error)) => Err(ErrorResponse::internal_server_error(error)),
+        Err(error) => Err(ErrorResponse::cannot_carry_over_records(error)),
+    }
+}
+
+/// Returns every carry-over to a map, most recent first.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/maps/{map_id}/carry-overs",
+    tag = "Maps",
+    params(("map_id" = u16, Path, description = "the map's ID")),
+    responses(
+        (status = 200, body = Vec<CarryOver>),
+        (status = 400, description = "invalid path parameters"),
+        (status = 401,),
+    ),
+)]
+async fn get_carry_overs(
+    State(cx): State<Context>,
+    Path(map_id): Path<MapId>,
+) -> Result<Json<Vec<CarryOver>>, ErrorResponse> {
+    let carry_overs = cs2kz::maps::carry_over::get_for_map(&cx, map_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    Ok(Json(carry_overs.into_iter().map(CarryOver::from).collect()))
+}
+
+impl From<cs2kz::maps::carry_over::CarryOver> for CarryOver {
+    fn from(carry_over: cs2kz::maps::carry_over::CarryOver) -> Self {
+        Self {
+            id: carry_over.id,
+            map_id: carry_over.map_id,
+            performed_by: UserInfo {
+                id: carry_over.performed_by.id,
+                name: carry_over.performed_by.name,
+            },
+            filters: carry_over
+                .filters
+                .into_iter()
+                .map(CarriedOverFilter::from)
+                .collect(),
+            created_at: carry_over.created_at,
+        }
+    }
+}
+
+impl From<cs2kz::maps::carry_over::CarriedOverFilter> for CarriedOverFilter {
+    fn from(filter: cs2kz::maps::carry_over::CarriedOverFilter) -> Self {
+        Self {
+            from_filter_id: filter.from_filter_id,
+            to_filter_id: filter.to_filter_id,
+            records: filter.records,
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use axum::extract::State;
+use cs2kz::Context;
+use cs2kz::maps::MapId;
+use cs2kz::maps::deletion::DeleteMapError;
+use cs2kz::time::Timestamp;
+
+use crate::extract::{Json, Path, Query};
+use crate::response::ErrorResponse;
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeleteMapQuery {
+    /// Delete the map even if it has records.
+    #[serde(default)]
+    force: bool,
+
+    /// Archive the map instead of deleting it.
+    ///
+    /// Archived maps are hidden from `GET /maps`, but keep all of their courses and records.
+    /// `force` has no effect when archiving.
+    #[serde(default)]
+    archive: bool,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct RemovedMap {
+    #[schema(value_type = u16, minimum = 1)]
+    map_id: MapId,
+
+    /// When the map was archived.
+    ///
+    /// This is `null` if the map was deleted.
+    #[schema(value_type = Option<crate::openapi::shims::Timestamp>)]
+    archived_at: Option<Timestamp>,
+
+    /// Everything that was deleted along with the map.
+    ///
+    /// Archiving a map does not delete anything.
+    deleted: CascadeEffects,
+}
+
+#[derive(Debug, Default, serde::Serialize, utoipa::ToSchema)]
+pub struct CascadeEffects {
+    courses: u64,
+    filters: u64,
+    records: u64,
+    best_nub_records: u64,
+    best_pro_records: u64,
+
+    /// Point distribution parameters of the map's filters.
+    distribution_data: u64,
+
+    /// Players whose ratings were recalculated because they had ranked records on the map.
+    rated_players: u64,
+}
+
+/// Deletes or archives a map.
+///
+/// Deleting a map also deletes its courses and filters, and every record set on them. Players with
+/// ranked records on the map have their ratings recalculated. Maps with records are only deleted
+/// if `force` is set; otherwise, the response describes what would have been deleted.
+///
+/// Archiving a map only hides it from `GET /maps`.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    delete,
+    path = "/maps/{map_id}",
+    tag = "Maps",
+    params(("map_id" = u16, Path, description = "the map's ID"), DeleteMapQuery),
+    responses(
+        (status = 200, body = RemovedMap),
+        (status = 400, description = "invalid path parameters or query string"),
+        (status = 401,),
+        (status = 404,),
+        (status = 409, description = "the map has records and `force` was not set"),
+    ),
+)]
+pub(super) async fn delete_map(
+    State(cx): State<Context>,
+    Path(map_id): Path<MapId>,
+    Query(DeleteMapQuery { force, archive }): Query<DeleteMapQuery>,
+) -> Result<Json<RemovedMap>, ErrorResponse> {
+    if archive {
+        let archived_at = cs2kz::maps::deletion::archive(&cx, map_id)
+            .await
+            .map_err(|err| ErrorResponse::internal_server_error(err))?
+            .ok_or_else(ErrorResponse::not_found)?;
+
+        return Ok(Json(RemovedMap {
+            map_id,
+            archived_at: Some(archived_at),
+            deleted: CascadeEffects::default(),
+        }));
+    }
+
+    match cs2kz::maps::deletion::delete(&cx, map_id, force).await {
+        Ok(Some(effects)) => Ok(Json(RemovedMap {
+            map_id,
+            archived_at: None,
+            deleted: effects.into(),
+        })),
+        Ok(None) => Err(ErrorResponse::not_found()),
+        Err(DeleteMapError::HasRecords(effects)) => {
+            Err(ErrorResponse::map_has_records(&CascadeEffects::from(effects)))
+        },
+        Err(DeleteMapError::Database(error)) => Err(ErrorResponse::internal_server_error(error)),
+    }
+}
+
+impl From<cs2kz::maps::deletion::CascadeEffects> for CascadeEffects {
+    fn from(effects: cs2kz::maps::deletion::CascadeEffects) -> Self {
+        Self {
+            courses: effects.courses,
+            filters: effects.filters,
+            records: effects.records,
+            best_nub_records: effects.best_nub_records,
+            best_pro_records: effects.best_pro_records,
+            distribution_data: effects.distribution_data,
+            rated_players: effects.rated_players,
+        }
+    }
+}
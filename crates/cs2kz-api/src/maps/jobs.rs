@@ -0,0 +1,314 @@
+//! Background jobs for approving maps.
+//!
+//! Approving a map (or a new version of one) requires downloading and hashing its `.vpk` file,
+//! which can take minutes. The endpoints involved therefore only validate the request and then
+//! spawn a [`Job`] for the rest, which clients can poll via `GET /maps/jobs/{job_id}`.
+//!
+//! Only one job per workshop item can be running at a time, as they would otherwise race on the
+//! same downloaded file. The check for [workshop updates] downloads the same files, so it has to
+//! lock workshop items through [`Jobs`] as well.
+//!
+//! [workshop updates]: super::workshop_updates
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+use axum::extract::State;
+use cs2kz::Context;
+use cs2kz::maps::{MapChecksum, MapId};
+use cs2kz::steam::WorkshopId;
+use cs2kz::time::Timestamp;
+use ulid::Ulid;
+
+use super::ApproveMapState;
+use crate::extract::{Json, Path};
+use crate::response::ErrorResponse;
+use crate::steam;
+use crate::steam::MapDownloader;
+
+/// How long finished jobs can still be polled.
+const RETENTION: time::Duration = time::Duration::hours(1);
+
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash, serde::Serialize, serde::Deserialize)]
+#[serde(transparent)]
+pub struct JobId(Ulid);
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct Job {
+    #[schema(value_type = crate::openapi::shims::MapApprovalJobId)]
+    id: JobId,
+
+    /// The workshop item being processed.
+    #[schema(value_type = u32)]
+    workshop_id: WorkshopId,
+
+    #[serde(flatten)]
+    status: JobStatus,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    created_at: Timestamp,
+
+    /// When the job's status last changed.
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    updated_at: Timestamp,
+}
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+#[serde(tag = "status", rename_all = "kebab-case")]
+pub enum JobStatus {
+    /// The job has not started yet.
+    Queued,
+
+    /// The map is being downloaded from the Steam workshop.
+    Downloading,
+
+    /// The map's checksum is being computed.
+    Hashing,
+
+    /// The map is being written to the database.
+    Writing,
+
+    /// The map has been approved.
+    Completed {
+        #[schema(value_type = u16, minimum = 1)]
+        map_id: MapId,
+    },
+
+    /// The job failed; the request has to be sent again.
+    Failed { reason: String },
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct QueuedJob {
+    /// Poll `GET /maps/jobs/{job_id}` with this ID to follow the job's progress.
+    #[schema(value_type = crate::openapi::shims::MapApprovalJobId)]
+    pub(super) job_id: JobId,
+}
+
+/// All jobs that have not yet expired.
+#[derive(Debug, Clone)]
+pub struct Jobs {
+    cx: Context,
+    inner: Arc<Mutex<JobsInner>>,
+}
+
+#[derive(Debug, Default)]
+struct JobsInner {
+    jobs: HashMap<JobId, Job>,
+
+    /// Workshop items which are currently being downloaded.
+    running: HashMap<WorkshopId, Download>,
+}
+
+/// Who is currently downloading a workshop item.
+#[derive(Debug, Clone, Copy)]
+enum Download {
+    Job(JobId),
+    UpdateCheck,
+}
+
+/// Marks a workshop item as being downloaded until it is dropped.
+///
+/// Dropping also happens when the holder panics, so workshop items can't stay locked forever.
+#[derive(Debug)]
+pub(super) struct DownloadLock {
+    jobs: Jobs,
+    workshop_id: WorkshopId,
+}
+
+/// Allows a running job to report its progress.
+#[derive(Debug)]
+pub(super) struct JobHandle {
+    jobs: Jobs,
+    id: JobId,
+}
+
+impl Jobs {
+    pub fn new(cx: Context) -> Self {
+        Self { cx, inner: Arc::default() }
+    }
+
+    /// Spawns a job processing the given workshop item.
+    ///
+    /// `run` should return the ID of the approved map, or a human-readable reason for why the job
+    /// failed. If another job is already processing `workshop_id`, no job is spawned and an
+    /// appropriate error response is returned instead.
+    pub(super) fn spawn<F, Fut>(
+        &self,
+        workshop_id: WorkshopId,
+        run: F,
+    ) -> Result<QueuedJob, ErrorResponse>
+    where
+        F: FnOnce(JobHandle) -> Fut,
+        Fut: Future<Output = Result<MapId, String>> + Send + 'static,
+    {
+        let job_id = JobId(Ulid::new());
+
+        {
+            let mut inner = self.lock();
+
+            inner.prune();
+
+            match inner.running.get(&workshop_id) {
+                None => {},
+                Some(&Download::Job(running)) => {
+                    return Err(ErrorResponse::map_approval_in_progress(Some(running)));
+                },
+                Some(Download::UpdateCheck) => {
+                    return Err(ErrorResponse::map_approval_in_progress(None));
+                },
+            }
+
+            let now = Timestamp::now();
+
+            inner.jobs.insert(job_id, Job {
+                id: job_id,
+                workshop_id,
+                status: JobStatus::Queued,
+                created_at: now,
+                updated_at: now,
+            });
+
+            inner.running.insert(workshop_id, Download::Job(job_id));
+        }
+
+        let lock = DownloadLock { jobs: self.clone(), workshop_id };
+        let job = run(JobHandle { jobs: self.clone(), id: job_id });
+        let jobs = self.clone();
+
+        self.cx.spawn("map-approval", move |_| async move {
+            let status = match job.await {
+                Ok(map_id) => {
+                    info!(%job_id, %workshop_id, %map_id, "map approval job completed");
+                    JobStatus::Completed { map_id }
+                },
+                Err(reason) => {
+                    warn!(%job_id, %workshop_id, reason, "map approval job failed");
+                    JobStatus::Failed { reason }
+                },
+            };
+
+            jobs.lock().set_status(job_id, status);
+            drop(lock);
+        });
+
+        Ok(QueuedJob { job_id })
+    }
+
+    /// Locks a workshop item for the workshop update check.
+    ///
+    /// Returns [`None`] if a job is currently processing the item.
+    pub(super) fn lock_for_update_check(&self, workshop_id: WorkshopId) -> Option<DownloadLock> {
+        let mut inner = self.lock();
+
+        if inner.running.contains_key(&workshop_id) {
+            return None;
+        }
+
+        inner.running.insert(workshop_id, Download::UpdateCheck);
+
+        Some(DownloadLock { jobs: self.clone(), workshop_id })
+    }
+
+    fn get(&self, job_id: JobId) -> Option<Job> {
+        self.lock().jobs.get(&job_id).cloned()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, JobsInner> {
+        self.inner.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl JobsInner {
+    fn set_status(&mut self, job_id: JobId, status: JobStatus) {
+        if let Some(job) = self.jobs.get_mut(&job_id) {
+            job.status = status;
+            job.updated_at = Timestamp::now();
+        }
+    }
+
+    /// Forgets about finished jobs older than [`RETENTION`].
+    fn prune(&mut self) {
+        let now = Timestamp::now();
+
+        self.jobs
+            .retain(|_, job| !job.status.is_finished() || (now - job.updated_at) < RETENTION);
+    }
+}
+
+impl JobStatus {
+    fn is_finished(&self) -> bool {
+        matches!(self, Self::Completed { .. } | Self::Failed { .. })
+    }
+}
+
+impl Drop for DownloadLock {
+    fn drop(&mut self) {
+        let mut inner = self.jobs.lock();
+
+        if let Some(Download::Job(job_id)) = inner.running.remove(&self.workshop_id) {
+            // the job did not get to report its result, e.g. because it panicked
+            if inner
+                .jobs
+                .get(&job_id)
+                .is_some_and(|job| !job.status.is_finished())
+            {
+                inner.set_status(job_id, JobStatus::Failed {
+                    reason: String::from("the job was aborted"),
+                });
+            }
+        }
+    }
+}
+
+impl JobHandle {
+    pub(super) fn set_status(&self, status: JobStatus) {
+        self.jobs.lock().set_status(self.id, status);
+    }
+
+    /// Downloads the map and computes its checksum.
+    pub(super) async fn download_map(
+        &self,
+        map_downloader: &steam::Downloader,
+        workshop_id: WorkshopId,
+    ) -> Result<MapChecksum, String> {
+        self.set_status(JobStatus::Downloading);
+
+        map_downloader
+            .download(workshop_id)
+            .await
+            .map_err(|err| format!("failed to download map: {err}"))?;
+
+        self.set_status(JobStatus::Hashing);
+
+        map_downloader
+            .compute_checksum(workshop_id)
+            .await
+            .map_err(|err| format!("failed to compute map checksum: {err}"))
+    }
+}
+
+/// Returns the status of a map approval job.
+///
+/// Jobs are forgotten an hour after they finish.
+#[tracing::instrument(skip(jobs))]
+#[utoipa::path(
+    get,
+    path = "/maps/jobs/{job_id}",
+    tag = "Maps",
+    params(("job_id" = crate::openapi::shims::MapApprovalJobId, Path, description = "the job's ID")),
+    responses(
+        (status = 200, body = Job),
+        (status = 400, description = "invalid path parameters"),
+        (status = 401,),
+        (status = 404,),
+    ),
+)]
+pub(super) async fn get_job(
+    State(ApproveMapState { jobs, .. }): State<ApproveMapState>,
+    Path(job_id): Path<JobId>,
+) -> Result<Json<Job>, ErrorResponse> {
+    jobs.get(job_id)
+        .map(Json)
+        .ok_or_else(ErrorResponse::not_found)
+}
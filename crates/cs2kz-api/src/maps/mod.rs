@@ -7,9 +7,11 @@ use axum::extract::{FromRef, State};
 use axum::handler::Handler;
 use axum::response::NoContent;
 use axum::routing::{MethodRouter, Router};
+use axum_extra::either::Either;
 use cs2kz::Context;
 use cs2kz::maps::courses::filters::{CourseFilterState, Tier};
 use cs2kz::maps::{ApproveMapError, CourseId, MapChecksum, MapId, MapState, UpdateMapError};
+use cs2kz::mode::Mode;
 use cs2kz::pagination::{Limit, Offset, Paginated};
 use cs2kz::players::{CreatePlayerError, PlayerId};
 use cs2kz::steam::WorkshopId;
@@ -21,56 +23,115 @@ use crate::config::{CookieConfig, DepotDownloaderConfig, SteamAuthConfig};
 use crate::extract::{Json, Path, Query};
 use crate::middleware::auth::session_auth;
 use crate::middleware::auth::session_auth::authorization::HasPermissions;
-use crate::players::PlayerInfo;
-use crate::response::{Created, ErrorResponse};
-use crate::steam;
+use crate::players::{PlayerIdentifier, PlayerInfo};
+use crate::response::{Accepted, ErrorResponse};
+use crate::steam::{self, MapDownloader};
 
 mod map_identifier;
 pub use map_identifier::MapIdentifier;
 
+pub mod carry_over;
+pub mod deletion;
+pub mod jobs;
+use jobs::{JobStatus, QueuedJob};
+
+pub mod submissions;
+pub mod tier_votes;
+pub mod versions;
+pub mod workshop_metadata;
+use workshop_metadata::PreviewCache;
+
+pub mod workshop_updates;
+
 #[derive(Clone)]
 struct ApproveMapState {
     cx: Context,
-    http_client: reqwest::Client,
-    steam_auth_config: Arc<SteamAuthConfig>,
-    depot_downloader_config: Arc<DepotDownloaderConfig>,
+    map_downloader: Arc<steam::Downloader>,
+    jobs: jobs::Jobs,
+}
+
+impl FromRef<ApproveMapState> for Context {
+    fn from_ref(state: &ApproveMapState) -> Self {
+        state.cx.clone()
+    }
+}
+
+#[derive(Clone)]
+struct MapsState {
+    cx: Context,
+    previews: Arc<PreviewCache>,
+}
+
+impl FromRef<MapsState> for Context {
+    fn from_ref(state: &MapsState) -> Self {
+        state.cx.clone()
+    }
 }
 
 pub fn router<S>(
     cx: Context,
     cookie_config: impl Into<Arc<CookieConfig>>,
-    steam_auth_config: impl Into<Arc<SteamAuthConfig>>,
-    depot_downloader_config: impl Into<Arc<DepotDownloaderConfig>>,
+    steam_auth_config: &SteamAuthConfig,
+    depot_downloader_config: &DepotDownloaderConfig,
+    jobs: jobs::Jobs,
+    previews: Arc<PreviewCache>,
 ) -> Router<S>
 where
     S: Clone + Send + Sync + 'static,
     Context: FromRef<S>,
 {
-    let session_auth_state = session_auth::State::new(cx.clone(), cookie_config)
+    let cookie_config = cookie_config.into();
+    let session_auth_state = session_auth::State::new(cx.clone(), Arc::clone(&cookie_config))
         .authorize_with(HasPermissions::new(Permission::MapPool));
     let is_admin = axum::middleware::from_fn_with_state(session_auth_state, session_auth);
+    let maps_state = MapsState { cx: cx.clone(), previews };
     let approve_map_state = ApproveMapState {
-        cx,
-        http_client: reqwest::Client::new(),
-        steam_auth_config: steam_auth_config.into(),
-        depot_downloader_config: depot_downloader_config.into(),
+        cx: cx.clone(),
+        map_downloader: Arc::new(steam::Downloader::from_config(
+            depot_downloader_config,
+            steam_auth_config,
+        )),
+        jobs,
     };
 
     Router::new()
         .route(
             "/",
             MethodRouter::new()
+                .get(get_maps)
+                .with_state(maps_state.clone())
                 .put(approve_map.layer(is_admin.clone()))
-                .with_state(approve_map_state.clone())
-                .get(get_maps),
+                .with_state(approve_map_state.clone()),
+        )
+        .route(
+            "/pending-updates",
+            MethodRouter::new().get(workshop_updates::get_pending_updates.layer(is_admin.clone())),
+        )
+        .route(
+            "/jobs/{job_id}",
+            MethodRouter::new()
+                .get(jobs::get_job.layer(is_admin.clone()))
+                .with_state(approve_map_state.clone()),
         )
         .route(
             "/{map}",
             MethodRouter::new()
-                .patch(update_map.layer(is_admin))
-                .with_state(approve_map_state)
-                .get(get_map),
+                .get(get_map)
+                .with_state(maps_state.clone())
+                .patch(update_map.layer(is_admin.clone()))
+                .with_state(approve_map_state.clone())
+                .delete(deletion::delete_map.layer(is_admin)),
         )
+        .route(
+            "/previews/{workshop_id}",
+            MethodRouter::new()
+                .get(workshop_metadata::get_preview)
+                .with_state(maps_state),
+        )
+        .route("/{map}/versions", MethodRouter::new().get(versions::get_map_versions))
+        .merge(tier_votes::router(approve_map_state.cx.clone(), Arc::clone(&cookie_config)))
+        .merge(carry_over::router(approve_map_state.cx.clone(), Arc::clone(&cookie_config)))
+        .nest("/submissions", submissions::router(cookie_config, approve_map_state))
 }
 
 #[derive(Debug, serde::Serialize, utoipa::ToSchema)]
@@ -106,6 +167,11 @@ pub struct Map {
     /// When this map was approved.
     #[schema(value_type = crate::openapi::shims::Timestamp)]
     approved_at: Timestamp,
+
+    /// Metadata about the map's Steam Workshop item.
+    ///
+    /// This is `null` until the API has fetched it from Steam for the first time.
+    workshop: Option<workshop_metadata::WorkshopMetadata>,
 }
 
 #[derive(Debug, serde::Serialize, utoipa::ToSchema)]
@@ -190,6 +256,12 @@ pub struct NewMap {
     #[schema(value_type = crate::openapi::shims::MapState)]
     state: MapState,
 
+    /// What changed compared to the previous version of this map.
+    ///
+    /// A map becomes a new version of any existing map with the same name.
+    #[serde(default, deserialize_with = "crate::serde::deserialize_empty_as_none")]
+    changelog: Option<String>,
+
     /// A list of SteamIDs of players who have contributed to the creation of this map.
     ///
     /// You must specify at least 1 player.
@@ -225,12 +297,13 @@ pub struct NewCourse {
 
     /// The filters for this course.
     filters: CourseFilters,
-}
 
-#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
-pub struct ApprovedMap {
-    #[schema(value_type = u16, minimum = 1)]
-    map_id: MapId,
+    /// The name of the course on the previous version of this map that this course is unchanged
+    /// from.
+    ///
+    /// Records on linked courses can be carried over with `POST /maps/{map_id}/carry-overs`.
+    #[serde(default, deserialize_with = "crate::serde::deserialize_empty_as_none")]
+    previous_course: Option<String>,
 }
 
 #[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
@@ -342,6 +415,69 @@ pub struct GetMapsQuery {
     #[param(value_type = Option<crate::openapi::shims::MapState>)]
     state: Option<MapState>,
 
+    /// Only include maps made by this player.
+    ///
+    /// This includes maps on which the player only made some of the courses.
+    mapper: Option<PlayerIdentifier>,
+
+    /// Only consider filters for this mode.
+    ///
+    /// This applies to the tier and `filter_state` parameters, as well as sorting by tier.
+    #[param(value_type = Option<crate::openapi::shims::Mode>)]
+    mode: Option<Mode>,
+
+    /// Only include maps with a filter whose NUB tier is at least this value.
+    #[param(value_type = Option<crate::openapi::shims::CourseFilterTier>)]
+    min_nub_tier: Option<Tier>,
+
+    /// Only include maps with a filter whose NUB tier is at most this value.
+    #[param(value_type = Option<crate::openapi::shims::CourseFilterTier>)]
+    max_nub_tier: Option<Tier>,
+
+    /// Only include maps with a filter whose PRO tier is at least this value.
+    #[param(value_type = Option<crate::openapi::shims::CourseFilterTier>)]
+    min_pro_tier: Option<Tier>,
+
+    /// Only include maps with a filter whose PRO tier is at most this value.
+    #[param(value_type = Option<crate::openapi::shims::CourseFilterTier>)]
+    max_pro_tier: Option<Tier>,
+
+    /// Only include maps with a filter in this state.
+    ///
+    /// All filter-related parameters must be satisfied by the same filter, so
+    /// `mode=classic&max_nub_tier=easy&filter_state=ranked` returns maps with a ranked classic
+    /// filter that is at most 'easy'.
+    #[param(value_type = Option<crate::openapi::shims::CourseFilterState>)]
+    filter_state: Option<CourseFilterState>,
+
+    /// Only include maps with at least this many courses.
+    min_courses: Option<u16>,
+
+    /// Only include maps with at most this many courses.
+    max_courses: Option<u16>,
+
+    /// Only include maps approved after this point in time.
+    #[param(value_type = Option<crate::openapi::shims::Timestamp>)]
+    approved_after: Option<Timestamp>,
+
+    /// Only include maps approved before this point in time.
+    #[param(value_type = Option<crate::openapi::shims::Timestamp>)]
+    approved_before: Option<Timestamp>,
+
+    /// Which value to sort the results by.
+    ///
+    /// If you omit this, maps are returned in the order they were approved in.
+    /// 'popularity' is the total number of records submitted on a map.
+    #[param(value_type = Option<crate::openapi::shims::Maps_SortBy>)]
+    sort_by: Option<cs2kz::maps::SortBy>,
+
+    /// Which direction to sort the results in.
+    ///
+    /// Defaults to 'descending' if `sort_by` is 'approval-date' or 'popularity', and to
+    /// 'ascending' otherwise.
+    #[param(value_type = Option<crate::openapi::shims::Records_SortOrder>)]
+    sort_order: Option<cs2kz::maps::SortOrder>,
+
     #[serde(default)]
     #[param(value_type = crate::openapi::shims::Limit)]
     limit: Limit<1000, 100>,
@@ -352,87 +488,104 @@ pub struct GetMapsQuery {
 }
 
 /// Approves a new map.
-#[tracing::instrument(skip(cx, http_client), ret(level = "debug"))]
+///
+/// If a map with the same name already exists, the new map becomes its next version. See
+/// `GET /maps/{map}/versions`.
+///
+/// The map is downloaded and written to the database in the background; the response contains a
+/// job ID to follow the progress with (`GET /maps/jobs/{job_id}`).
+#[tracing::instrument(skip(cx, map_downloader, jobs), ret(level = "debug"))]
 #[utoipa::path(
     put,
     path = "/maps",
     tag = "Maps",
     request_body = NewMap,
     responses(
-        (status = 201, body = ApprovedMap),
+        (status = 202, body = QueuedJob),
         (status = 401,),
+        (status = 404, description = "the workshop item does not exist"),
+        (status = 409, description = "the workshop item is already being processed"),
         (status = 422, description = "invalid request body"),
     ),
 )]
 async fn approve_map(
-    State(ApproveMapState {
-        cx,
-        http_client,
-        steam_auth_config,
-        depot_downloader_config,
-    }): State<ApproveMapState>,
+    State(ApproveMapState { cx, map_downloader, jobs }): State<ApproveMapState>,
     Json(NewMap {
         workshop_id,
         description,
         state,
+        changelog,
         mappers,
         courses,
     }): Json<NewMap>,
-) -> Result<Created<ApprovedMap>, ErrorResponse> {
-    let (name, vpk_checksum) =
-        fetch_name_and_checksum(&http_client, &depot_downloader_config, workshop_id).await?;
+) -> Result<Accepted<QueuedJob>, ErrorResponse> {
+    let name = fetch_map_name(&map_downloader, workshop_id).await?;
 
     create_missing_mappers(
         &cx,
-        &http_client,
-        &steam_auth_config,
+        &map_downloader,
         iter::chain(&mappers, courses.iter().flat_map(|course| &course.mappers))
             .copied()
             .collect::<HashSet<_>>(),
     )
     .await?;
 
-    let map = cs2kz::maps::NewMap {
-        workshop_id,
-        name,
-        description,
-        state,
-        vpk_checksum,
-        mappers: mappers.into_boxed_slice(),
-        courses: courses
-            .into_iter()
-            .map(|course| cs2kz::maps::NewCourse {
-                name: course.name,
-                description: course.description,
-                mappers: course.mappers.into_boxed_slice(),
-                filters: cs2kz::maps::NewCourseFilters {
-                    vanilla: cs2kz::maps::NewCourseFilter {
-                        nub_tier: course.filters.vanilla.nub_tier,
-                        pro_tier: course.filters.vanilla.pro_tier,
-                        state: course.filters.vanilla.state,
-                        notes: course.filters.vanilla.notes,
-                    },
-                    classic: cs2kz::maps::NewCourseFilter {
-                        nub_tier: course.filters.classic.nub_tier,
-                        pro_tier: course.filters.classic.pro_tier,
-                        state: course.filters.classic.state,
-                        notes: course.filters.classic.notes,
-                    },
+    let mappers = mappers.into_boxed_slice();
+    let courses = courses
+        .into_iter()
+        .map(|course| cs2kz::maps::NewCourse {
+            name: course.name,
+            description: course.description,
+            mappers: course.mappers.into_boxed_slice(),
+            filters: cs2kz::maps::NewCourseFilters {
+                vanilla: cs2kz::maps::NewCourseFilter {
+                    nub_tier: course.filters.vanilla.nub_tier,
+                    pro_tier: course.filters.vanilla.pro_tier,
+                    state: course.filters.vanilla.state,
+                    notes: course.filters.vanilla.notes,
                 },
-            })
-            .collect(),
-    };
-
-    cs2kz::maps::approve(&cx, map)
-        .await
-        .map(|map_id| Created(ApprovedMap { map_id }))
-        .map_err(|err| match err {
-            ApproveMapError::Database(error) => ErrorResponse::internal_server_error(error),
+                classic: cs2kz::maps::NewCourseFilter {
+                    nub_tier: course.filters.classic.nub_tier,
+                    pro_tier: course.filters.classic.pro_tier,
+                    state: course.filters.classic.state,
+                    notes: course.filters.classic.notes,
+                },
+            },
+            previous_version: course.previous_course,
         })
+        .collect();
+
+    jobs.spawn(workshop_id, async move |job| {
+        let vpk_checksum = job.download_map(&map_downloader, workshop_id).await?;
+
+        job.set_status(JobStatus::Writing);
+
+        let map = cs2kz::maps::NewMap {
+            workshop_id,
+            name,
+            description,
+            state,
+            vpk_checksum,
+            changelog,
+            mappers,
+            courses,
+        };
+
+        cs2kz::maps::approve(&cx, map)
+            .await
+            .map_err(|err| match err {
+                ApproveMapError::Database(error) => {
+                    error!(%error, "failed to approve map");
+                    String::from("failed to write map to database")
+                },
+                error @ ApproveMapError::UnknownPreviousCourse { .. } => error.to_string(),
+            })
+    })
+    .map(Accepted)
 }
 
 /// Returns the latest KZ maps.
-#[tracing::instrument(skip(cx))]
+#[tracing::instrument(skip(cx, previews))]
 #[utoipa::path(
     get,
     path = "/maps",
@@ -444,28 +597,84 @@ async fn approve_map(
     ),
 )]
 async fn get_maps(
-    State(cx): State<Context>,
-    Query(GetMapsQuery { workshop_id, name, state, limit, offset }): Query<GetMapsQuery>,
+    State(MapsState { cx, previews }): State<MapsState>,
+    Query(GetMapsQuery {
+        workshop_id,
+        name,
+        state,
+        mapper,
+        mode,
+        min_nub_tier,
+        max_nub_tier,
+        min_pro_tier,
+        max_pro_tier,
+        filter_state,
+        min_courses,
+        max_courses,
+        approved_after,
+        approved_before,
+        sort_by,
+        sort_order,
+        limit,
+        offset,
+    }): Query<GetMapsQuery>,
 ) -> Result<Json<Paginated<Vec<Map>>>, ErrorResponse> {
+    let mapper = match mapper {
+        None => None,
+        Some(PlayerIdentifier::Id(id)) => Some(id),
+        Some(PlayerIdentifier::Name(ref name)) => {
+            match cs2kz::players::get_by_name(&cx, name).await {
+                Ok(Some(player)) => Some(player.id),
+                Ok(None) => return Ok(Json(Paginated::new(0, Vec::new()))),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            }
+        },
+    };
+
     let params = cs2kz::maps::GetMapsParams {
         workshop_id,
         name: name.as_deref(),
         state,
+        mapper,
+        mode,
+        min_nub_tier,
+        max_nub_tier,
+        min_pro_tier,
+        max_pro_tier,
+        filter_state,
+        min_courses,
+        max_courses,
+        approved_after,
+        approved_before,
+        sort_by,
+        sort_order,
         limit,
         offset,
     };
 
     let maps = cs2kz::maps::get(&cx, params)
-        .map_ok(Paginated::map_into)
-        .and_then(Paginated::collect)
+        .and_then(Paginated::collect::<Vec<_>>)
         .map_err(|err| ErrorResponse::internal_server_error(err))
         .await?;
 
+    let workshop_ids = maps
+        .values()
+        .iter()
+        .map(|map| map.workshop_id)
+        .collect::<Vec<_>>();
+
+    let metadata = workshop_metadata::get_for(&cx, &previews, &workshop_ids).await?;
+    let maps = maps.map_values(|map| {
+        // multiple maps can share a workshop item
+        let workshop = metadata.get(&map.workshop_id).cloned();
+        Map { workshop, ..Map::from(map) }
+    });
+
     Ok(Json(maps))
 }
 
 /// Returns the map with the specified ID / name.
-#[tracing::instrument(skip(cx))]
+#[tracing::instrument(skip(cx, previews))]
 #[utoipa::path(
     get,
     path = "/maps/{map}",
@@ -478,7 +687,7 @@ async fn get_maps(
     ),
 )]
 async fn get_map(
-    State(cx): State<Context>,
+    State(MapsState { cx, previews }): State<MapsState>,
     Path(map_identifier): Path<MapIdentifier>,
 ) -> Result<Json<Map>, ErrorResponse> {
     let map = match map_identifier {
@@ -488,14 +697,25 @@ async fn get_map(
     .map_err(|err| ErrorResponse::internal_server_error(err))?
     .ok_or_else(ErrorResponse::not_found)?;
 
-    Ok(Json(map.into()))
+    let workshop = workshop_metadata::get_for(&cx, &previews, &[map.workshop_id])
+        .await?
+        .remove(&map.workshop_id);
+
+    Ok(Json(Map { workshop, ..Map::from(map) }))
 }
 
 /// Updates a map in-place.
 ///
 /// This endpoint is used for simple metadata changes. Gameplay changes should be communicated
 /// through a separate version, i.e. `PUT /maps`.
-#[tracing::instrument(skip(cx, http_client))]
+///
+/// If you specify a new workshop ID, the map has to be downloaded again, so the update is applied
+/// in the background and the response contains a job ID to follow the progress with
+/// (`GET /maps/jobs/{job_id}`).
+///
+/// When finalising the tiers of a map that has been in testing, check
+/// `GET /maps/{map_id}/tier-votes` for what players think first.
+#[tracing::instrument(skip(cx, map_downloader, jobs))]
 #[utoipa::path(
     patch,
     path = "/maps/{map_id}",
@@ -503,49 +723,40 @@ async fn get_map(
     params(("map_id" = u16, Path, description = "the map's ID")),
     request_body = MapUpdate,
     responses(
+        (status = 202, body = QueuedJob, description = "the map is being downloaded"),
         (status = 204,),
         (status = 400, description = "invalid path parameters"),
         (status = 401,),
+        (status = 404,),
         (status = 409,),
         (status = 422, description = "invalid request body"),
     ),
 )]
 async fn update_map(
-    State(ApproveMapState {
-        cx,
-        http_client,
-        steam_auth_config,
-        depot_downloader_config,
-    }): State<ApproveMapState>,
+    State(ApproveMapState { cx, map_downloader, jobs }): State<ApproveMapState>,
     Path(map_id): Path<MapId>,
-    Json(MapUpdate {
-        workshop_id,
-        description,
-        state,
-        added_mappers,
-        deleted_mappers,
-        course_updates,
-    }): Json<MapUpdate>,
-) -> Result<NoContent, ErrorResponse> {
-    let name_and_checksum = if let Some(workshop_id) = workshop_id {
-        Some(fetch_name_and_checksum(&http_client, &depot_downloader_config, workshop_id).await?)
+    Json(update): Json<MapUpdate>,
+) -> Result<Either<NoContent, Accepted<QueuedJob>>, ErrorResponse> {
+    let new_name = if let Some(workshop_id) = update.workshop_id {
+        Some(fetch_map_name(&map_downloader, workshop_id).await?)
     } else {
         None
     };
 
-    let has_mappers = !added_mappers.is_empty()
-        || course_updates
+    let has_mappers = !update.added_mappers.is_empty()
+        || update
+            .course_updates
             .iter()
             .any(|course| !course.added_mappers.is_empty());
 
     if has_mappers {
         create_missing_mappers(
             &cx,
-            &http_client,
-            &steam_auth_config,
+            &map_downloader,
             iter::chain(
-                &added_mappers,
-                course_updates
+                &update.added_mappers,
+                update
+                    .course_updates
                     .iter()
                     .flat_map(|update| &update.added_mappers),
             )
@@ -555,96 +766,76 @@ async fn update_map(
         .await?;
     }
 
-    let update = cs2kz::maps::MapUpdate {
-        id: map_id,
-        workshop_id,
-        name: name_and_checksum.as_ref().map(|(name, _)| name.as_str()),
-        description: description.as_deref(),
-        state,
-        vpk_checksum: name_and_checksum.as_ref().map(|&(_, checksum)| checksum),
-        added_mappers: &added_mappers,
-        deleted_mappers: &deleted_mappers,
-        course_updates: course_updates
-            .iter()
-            .map(|update| cs2kz::maps::CourseUpdate {
-                idx: update.idx,
-                name: update.name.as_deref(),
-                description: update.description.as_deref(),
-                added_mappers: &update.added_mappers,
-                deleted_mappers: &update.deleted_mappers,
-                filter_updates: cs2kz::maps::FilterUpdates {
-                    vanilla: update.filter_updates.vanilla.as_ref().map(|filter_update| {
-                        cs2kz::maps::FilterUpdate {
-                            nub_tier: filter_update.nub_tier,
-                            pro_tier: filter_update.pro_tier,
-                            state: filter_update.state,
-                            notes: filter_update.notes.as_deref(),
-                        }
-                    }),
-                    classic: update.filter_updates.classic.as_ref().map(|filter_update| {
-                        cs2kz::maps::FilterUpdate {
-                            nub_tier: filter_update.nub_tier,
-                            pro_tier: filter_update.pro_tier,
-                            state: filter_update.state,
-                            notes: filter_update.notes.as_deref(),
-                        }
-                    }),
-                },
-            })
-            .collect(),
+    let (Some(workshop_id), Some(name)) = (update.workshop_id, new_name) else {
+        return match cs2kz::maps::update(&cx, update.to_core(map_id, None)).await {
+            Ok(true) => Ok(Either::E1(NoContent)),
+            Ok(false) => Err(ErrorResponse::not_found()),
+            Err(UpdateMapError::MustHaveMappers) => Err(ErrorResponse::map_must_have_mappers()),
+            Err(UpdateMapError::InvalidCourseIndex { idx }) => {
+                Err(ErrorResponse::invalid_course_index(idx))
+            },
+            Err(UpdateMapError::Database(error)) => {
+                Err(ErrorResponse::internal_server_error(error))
+            },
+        };
     };
 
-    match cs2kz::maps::update(&cx, update).await {
-        Ok(true) => Ok(NoContent),
-        Ok(false) => Err(ErrorResponse::not_found()),
-        Err(UpdateMapError::MustHaveMappers) => Err(ErrorResponse::map_must_have_mappers()),
-        Err(UpdateMapError::InvalidCourseIndex { idx }) => {
-            Err(ErrorResponse::invalid_course_index(idx))
-        },
-        Err(UpdateMapError::Database(error)) => Err(ErrorResponse::internal_server_error(error)),
+    if cs2kz::maps::get_by_id(&cx, map_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .is_none()
+    {
+        return Err(ErrorResponse::not_found());
     }
+
+    jobs.spawn(workshop_id, move |job| async move {
+        let vpk_checksum = job.download_map(&map_downloader, workshop_id).await?;
+
+        job.set_status(JobStatus::Writing);
+
+        match cs2kz::maps::update(&cx, update.to_core(map_id, Some((&name, vpk_checksum)))).await {
+            Ok(true) => Ok(map_id),
+            Ok(false) => Err(String::from("the map no longer exists")),
+            Err(UpdateMapError::Database(error)) => {
+                error!(%error, "failed to update map");
+                Err(String::from("failed to write map to database"))
+            },
+            Err(error) => Err(error.to_string()),
+        }
+    })
+    .map(|queued| Either::E2(Accepted(queued)))
 }
 
-async fn fetch_name_and_checksum(
-    http_client: &reqwest::Client,
-    depot_downloader_config: &DepotDownloaderConfig,
+/// Fetches the name of a workshop map.
+async fn fetch_map_name(
+    map_downloader: &steam::Downloader,
     workshop_id: WorkshopId,
-) -> Result<(String, MapChecksum), ErrorResponse> {
-    try_join!(
-        steam::fetch_map_name(http_client, workshop_id).map(|res| match res {
-            Ok(Some(map_name)) => Ok(map_name),
-            Ok(None) => Err(ErrorResponse::not_found()),
-            Err(error) => Err(ErrorResponse::from(error)),
-        }),
-        steam::download_map(
-            workshop_id,
-            &depot_downloader_config.exe_path,
-            &depot_downloader_config.out_dir
-        )
-        .and_then(|()| steam::maps::compute_checksum(depot_downloader_config.vpk_path(workshop_id)))
-        .map_err(|err| ErrorResponse::internal_server_error(err)),
-    )
+) -> Result<String, ErrorResponse> {
+    match map_downloader.fetch_map_name(workshop_id).await {
+        Ok(Some(map_name)) => Ok(map_name),
+        Ok(None) => Err(ErrorResponse::not_found()),
+        Err(error) => Err(ErrorResponse::from(error)),
+    }
 }
 
 async fn create_missing_mappers(
     cx: &Context,
-    http_client: &reqwest::Client,
-    steam_auth_config: &SteamAuthConfig,
+    map_downloader: &steam::Downloader,
     mapper_ids: impl IntoIterator<Item = PlayerId>,
 ) -> Result<(), ErrorResponse> {
     let players = stream::iter(mapper_ids)
         .then(|mapper_id| {
-            steam::fetch_user(http_client, &steam_auth_config.web_api_key, mapper_id.into()).map(
-                |user| match user {
-                    Ok(Some(user)) => Ok(cs2kz::players::NewPlayer {
-                        id: PlayerId::new(user.id),
-                        name: Cow::Owned(user.name),
+            map_downloader
+                .fetch_user_name(mapper_id.into())
+                .map(move |name| match name {
+                    Ok(Some(name)) => Ok(cs2kz::players::NewPlayer {
+                        id: mapper_id,
+                        name: Cow::Owned(name),
                         ip_address: None,
                     }),
                     Ok(None) => Err(ErrorResponse::mapper_does_not_exist()),
                     Err(error) => Err(ErrorResponse::from(error)),
-                },
-            )
+                })
         })
         .try_collect::<Vec<_>>()
         .await?;
@@ -655,6 +846,62 @@ async fn create_missing_mappers(
     }
 }
 
+impl MapUpdate {
+    /// Borrows the update for [`cs2kz::maps::update()`].
+    ///
+    /// `new_version` is the name and checksum of the map behind the new workshop ID, if any.
+    fn to_core<'a>(
+        &'a self,
+        map_id: MapId,
+        new_version: Option<(&'a str, MapChecksum)>,
+    ) -> cs2kz::maps::MapUpdate<'a> {
+        cs2kz::maps::MapUpdate {
+            id: map_id,
+            workshop_id: self.workshop_id,
+            name: new_version.map(|(name, _)| name),
+            description: self.description.as_deref(),
+            state: self.state,
+            vpk_checksum: new_version.map(|(_, checksum)| checksum),
+            added_mappers: &self.added_mappers,
+            deleted_mappers: &self.deleted_mappers,
+            course_updates: self
+                .course_updates
+                .iter()
+                .map(|update| cs2kz::maps::CourseUpdate {
+                    idx: update.idx,
+                    name: update.name.as_deref(),
+                    description: update.description.as_deref(),
+                    added_mappers: &update.added_mappers,
+                    deleted_mappers: &update.deleted_mappers,
+                    filter_updates: cs2kz::maps::FilterUpdates {
+                        vanilla: update
+                            .filter_updates
+                            .vanilla
+                            .as_ref()
+                            .map(FilterUpdate::to_core),
+                        classic: update
+                            .filter_updates
+                            .classic
+                            .as_ref()
+                            .map(FilterUpdate::to_core),
+                    },
+                })
+                .collect(),
+        }
+    }
+}
+
+impl FilterUpdate {
+    fn to_core(&self) -> cs2kz::maps::FilterUpdate<'_> {
+        cs2kz::maps::FilterUpdate {
+            nub_tier: self.nub_tier,
+            pro_tier: self.pro_tier,
+            state: self.state,
+            notes: self.notes.as_deref(),
+        }
+    }
+}
+
 impl From<cs2kz::maps::Map> for Map {
     fn from(map: cs2kz::maps::Map) -> Self {
         Self {
@@ -667,6 +914,7 @@ impl From<cs2kz::maps::Map> for Map {
             mappers: map.mappers.into_iter().map(PlayerInfo::from).collect(),
             courses: map.courses.into_iter().map(Course::from).collect(),
             approved_at: map.approved_at,
+            workshop: None,
         }
     }
 }
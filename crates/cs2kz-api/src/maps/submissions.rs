@@ -0,0 +1,586 @@
+use std::collections::HashSet;
+use std::iter;
+use std::sync::Arc;
+
+use axum::extract::{FromRef, State};
+use axum::handler::Handler;
+use axum::response::NoContent;
+use axum::routing::{MethodRouter, Router};
+use cs2kz::Context;
+use cs2kz::maps::MapId;
+use cs2kz::maps::courses::filters::Tier;
+use cs2kz::maps::submissions::{
+    ReviewSubmissionError,
+    SubmissionId,
+    SubmissionState,
+    UpdateSubmissionError,
+};
+use cs2kz::pagination::{Limit, Offset, Paginated};
+use cs2kz::players::PlayerId;
+use cs2kz::steam::WorkshopId;
+use cs2kz::time::Timestamp;
+use cs2kz::users::{Permission, UserId};
+use futures_util::TryFutureExt;
+
+use super::jobs::{JobId, JobStatus, QueuedJob};
+use super::{ApproveMapState, create_missing_mappers, fetch_map_name};
+use crate::config::CookieConfig;
+use crate::extract::{Json, Path, Query};
+use crate::middleware::auth::session_auth;
+use crate::middleware::auth::session_auth::Session;
+use crate::middleware::auth::session_auth::authorization::HasPermissions;
+use crate::response::{Created, ErrorResponse};
+use crate::users::UserInfo;
+
+pub(super) fn router<S>(
+    cookie_config: Arc<CookieConfig>,
+    approve_map_state: ApproveMapState,
+) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Context: FromRef<S>,
+{
+    let session_auth_state = session_auth::State::new(approve_map_state.cx.clone(), cookie_config);
+    let is_logged_in =
+        axum::middleware::from_fn_with_state(session_auth_state.clone(), session_auth);
+    let is_admin = axum::middleware::from_fn_with_state(
+        session_auth_state.authorize_with(HasPermissions::new(Permission::MapPool)),
+        session_auth,
+    );
+
+    Router::new()
+        .route(
+            "/",
+            MethodRouter::new()
+                .post(submit_map.layer(is_logged_in.clone()))
+                .get(get_submissions),
+        )
+        .route(
+            "/{submission_id}",
+            MethodRouter::new()
+                .put(update_submission.layer(is_logged_in))
+                .get(get_submission),
+        )
+        .route(
+            "/{submission_id}/reviews",
+            MethodRouter::new()
+                .post(review_submission.layer(is_admin))
+                .with_state(approve_map_state),
+        )
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct Submission {
+    #[schema(value_type = u32, minimum = 1)]
+    id: SubmissionId,
+
+    /// The ID of the map's Steam workshop item.
+    #[schema(value_type = u32)]
+    workshop_id: WorkshopId,
+
+    /// The user who submitted the map.
+    submitted_by: UserInfo,
+
+    /// A brief description of the map.
+    description: Option<String>,
+
+    /// The state the submission is currently in.
+    #[schema(value_type = crate::openapi::shims::MapSubmissionState)]
+    state: SubmissionState,
+
+    /// SteamIDs of the players who have contributed to the creation of this map.
+    #[schema(value_type = Vec<crate::openapi::shims::SteamId>)]
+    mappers: Vec<PlayerId>,
+
+    /// The courses on the map, with their proposed tiers.
+    courses: Vec<SubmittedCourse>,
+
+    /// The map this submission turned into, once it has been moved into testing or approved.
+    #[schema(value_type = Option<u16>, minimum = 1)]
+    map_id: Option<MapId>,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    submitted_at: Timestamp,
+
+    /// When the submission was last updated or reviewed.
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    updated_at: Timestamp,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct SubmissionWithReviews {
+    #[serde(flatten)]
+    submission: Submission,
+
+    /// Every review left on this submission, oldest first.
+    reviews: Vec<Review>,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct SubmittedCourse {
+    /// The course's name.
+    #[serde(deserialize_with = "crate::serde::deserialize_non_empty")]
+    name: String,
+
+    /// A brief description of the course.
+    #[serde(default, deserialize_with = "crate::serde::deserialize_empty_as_none")]
+    description: Option<String>,
+
+    /// SteamIDs of the players who have contributed to the creation of this course.
+    ///
+    /// You must specify at least 1 player.
+    #[serde(deserialize_with = "crate::serde::deserialize_non_empty")]
+    #[schema(value_type = Vec<crate::openapi::shims::SteamId>)]
+    mappers: Vec<PlayerId>,
+
+    /// The proposed tiers for the VNL mode.
+    vanilla: ProposedTiers,
+
+    /// The proposed tiers for the CKZ mode.
+    classic: ProposedTiers,
+}
+
+#[derive(Debug, serde::Serialize, serde::Deserialize, utoipa::ToSchema)]
+pub struct ProposedTiers {
+    /// The difficulty level when teleports are allowed.
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    nub_tier: Tier,
+
+    /// The difficulty level when no teleports are allowed.
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    pro_tier: Tier,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct Review {
+    /// The admin who left this review.
+    reviewer: UserInfo,
+
+    /// The state the reviewer moved the submission into.
+    #[schema(value_type = crate::openapi::shims::MapSubmissionState)]
+    state: SubmissionState,
+
+    comment: String,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    created_at: Timestamp,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct NewSubmission {
+    /// The ID of the map's Steam workshop item.
+    #[schema(value_type = u32)]
+    workshop_id: WorkshopId,
+
+    /// A brief description of the map.
+    #[serde(default, deserialize_with = "crate::serde::deserialize_empty_as_none")]
+    description: Option<String>,
+
+    /// SteamIDs of the players who have contributed to the creation of this map.
+    ///
+    /// You must specify at least 1 player.
+    #[serde(deserialize_with = "crate::serde::deserialize_non_empty")]
+    #[schema(value_type = Vec<crate::openapi::shims::SteamId>)]
+    mappers: Vec<PlayerId>,
+
+    /// The courses on the map, with their proposed tiers.
+    ///
+    /// You must specify at least 1 course.
+    #[serde(deserialize_with = "crate::serde::deserialize_non_empty")]
+    courses: Vec<SubmittedCourse>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct CreatedSubmission {
+    #[schema(value_type = u32, minimum = 1)]
+    submission_id: SubmissionId,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct NewReview {
+    /// The state to move the submission into.
+    ///
+    /// Moving a submission into `in-testing` or `approved` creates the map, unless it already
+    /// exists.
+    #[schema(value_type = crate::openapi::shims::MapSubmissionState)]
+    state: SubmissionState,
+
+    /// An explanation for the mapper.
+    #[serde(deserialize_with = "crate::serde::deserialize_non_empty")]
+    comment: String,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct CreatedReview {
+    /// The ID of the job creating the map, if this review created it.
+    ///
+    /// Poll `GET /maps/jobs/{job_id}` to follow its progress; the review is only recorded once
+    /// the map has been created.
+    #[schema(value_type = Option<crate::openapi::shims::MapApprovalJobId>)]
+    job_id: Option<JobId>,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetSubmissionsQuery {
+    /// Only include submissions currently in this state.
+    #[param(value_type = Option<crate::openapi::shims::MapSubmissionState>)]
+    state: Option<SubmissionState>,
+
+    /// Only include submissions by this user.
+    #[param(value_type = Option<crate::openapi::shims::SteamId64>)]
+    submitted_by: Option<UserId>,
+
+    #[serde(default)]
+    #[param(value_type = crate::openapi::shims::Limit)]
+    limit: Limit<1000, 100>,
+
+    #[serde(default)]
+    #[param(value_type = crate::openapi::shims::Offset)]
+    offset: Offset,
+}
+
+/// Submits a map for review.
+///
+/// Any logged-in user can submit maps. Admins will then review the submission and either
+/// approve it, reject it, or ask for changes.
+#[tracing::instrument(skip(cx, session), fields(session.id = %session.id()), ret(level = "debug"))]
+#[utoipa::path(
+    post,
+    path = "/maps/submissions",
+    tag = "Maps",
+    request_body = NewSubmission,
+    responses(
+        (status = 201, body = CreatedSubmission),
+        (status = 401,),
+        (status = 422, description = "invalid request body"),
+    ),
+)]
+async fn submit_map(
+    State(cx): State<Context>,
+    session: Session,
+    Json(NewSubmission { workshop_id, description, mappers, courses }): Json<NewSubmission>,
+) -> Result<Created<CreatedSubmission>, ErrorResponse> {
+    let submission = cs2kz::maps::submissions::NewSubmission {
+        workshop_id,
+        submitted_by: session.user().id(),
+        description,
+        mappers,
+        courses: courses.into_iter().map(Into::into).collect(),
+    };
+
+    cs2kz::maps::submissions::submit(&cx, submission)
+        .await
+        .map(|submission_id| Created(CreatedSubmission { submission_id }))
+        .map_err(|err| ErrorResponse::internal_server_error(err))
+}
+
+/// Returns the latest map submissions.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/maps/submissions",
+    tag = "Maps",
+    params(GetSubmissionsQuery),
+    responses(
+        (status = 200, body = crate::openapi::shims::Paginated<Submission>),
+        (status = 400, description = "invalid query parameters"),
+    ),
+)]
+async fn get_submissions(
+    State(cx): State<Context>,
+    Query(GetSubmissionsQuery { state, submitted_by, limit, offset }): Query<GetSubmissionsQuery>,
+) -> Result<Json<Paginated<Vec<Submission>>>, ErrorResponse> {
+    let params =
+        cs2kz::maps::submissions::GetSubmissionsParams { state, submitted_by, limit, offset };
+
+    let submissions = cs2kz::maps::submissions::get(&cx, params)
+        .map_ok(Paginated::map_into)
+        .and_then(Paginated::collect)
+        .map_err(|err| ErrorResponse::internal_server_error(err))
+        .await?;
+
+    Ok(Json(submissions))
+}
+
+/// Returns a map submission and its reviews.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/maps/submissions/{submission_id}",
+    tag = "Maps",
+    params(("submission_id" = u32, Path, description = "the submission's ID")),
+    responses(
+        (status = 200, body = SubmissionWithReviews),
+        (status = 400, description = "invalid path parameters"),
+        (status = 404,),
+    ),
+)]
+async fn get_submission(
+    State(cx): State<Context>,
+    Path(submission_id): Path<SubmissionId>,
+) -> Result<Json<SubmissionWithReviews>, ErrorResponse> {
+    let submission = cs2kz::maps::submissions::get_by_id(&cx, submission_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .ok_or_else(ErrorResponse::not_found)?;
+
+    let reviews = cs2kz::maps::submissions::get_reviews(&cx, submission_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    Ok(Json(SubmissionWithReviews {
+        submission: submission.into(),
+        reviews: reviews.into_iter().map(Review::from).collect(),
+    }))
+}
+
+/// Replaces the contents of a map submission.
+///
+/// Only the user who submitted the map can update it, and only until it has been moved into
+/// testing, approved or rejected. Updating a submission moves it back into the `submitted` state, so this is how
+/// mappers address requested changes.
+#[tracing::instrument(skip(cx, session), fields(session.id = %session.id()))]
+#[utoipa::path(
+    put,
+    path = "/maps/submissions/{submission_id}",
+    tag = "Maps",
+    params(("submission_id" = u32, Path, description = "the submission's ID")),
+    request_body = NewSubmission,
+    responses(
+        (status = 204,),
+        (status = 401,),
+        (status = 404,),
+        (
+            status = 409,
+            description = "the submission's map has already been created, or it has been \
+                           rejected",
+        ),
+        (status = 422, description = "invalid request body"),
+    ),
+)]
+async fn update_submission(
+    State(cx): State<Context>,
+    session: Session,
+    Path(submission_id): Path<SubmissionId>,
+    Json(NewSubmission { workshop_id, description, mappers, courses }): Json<NewSubmission>,
+) -> Result<NoContent, ErrorResponse> {
+    let update = cs2kz::maps::submissions::SubmissionUpdate {
+        id: submission_id,
+        updated_by: session.user().id(),
+        workshop_id,
+        description,
+        mappers,
+        courses: courses.into_iter().map(Into::into).collect(),
+    };
+
+    match cs2kz::maps::submissions::update(&cx, update).await {
+        Ok(true) => Ok(NoContent),
+        Ok(false) => Err(ErrorResponse::not_found()),
+        Err(UpdateSubmissionError::NotSubmitter) => Err(ErrorResponse::unauthorized()),
+        Err(
+            UpdateSubmissionError::AlreadyFinal { state }
+            | UpdateSubmissionError::AlreadyHasMap { state },
+        ) => Err(ErrorResponse::invalid_submission_state(state, SubmissionState::Submitted)),
+        Err(UpdateSubmissionError::Database(error)) => {
+            Err(ErrorResponse::internal_server_error(error))
+        },
+    }
+}
+
+/// Reviews a map submission.
+///
+/// This moves the submission into a new state and leaves a comment for the mapper. Moving a
+/// submission into testing or approving it downloads the map from the Steam workshop and creates
+/// it in the background, exactly like `PUT /maps` would; every course filter whose lowest proposed
+/// tier is 8 or lower starts out ranked. Once the map exists, later reviews move it along with the
+/// submission: approving the submission approves the map, and rejecting it invalidates the map.
+#[tracing::instrument(skip(cx, map_downloader, jobs, session), fields(session.id = %session.id()))]
+#[utoipa::path(
+    post,
+    path = "/maps/submissions/{submission_id}/reviews",
+    tag = "Maps",
+    params(("submission_id" = u32, Path, description = "the submission's ID")),
+    request_body = NewReview,
+    responses(
+        (status = 201, body = CreatedReview),
+        (status = 401,),
+        (status = 404,),
+        (
+            status = 409,
+            description = "the submission cannot be moved into the requested state, or its map is \
+                           already being processed",
+        ),
+        (status = 422, description = "invalid request body"),
+    ),
+)]
+async fn review_submission(
+    State(ApproveMapState { cx, map_downloader, jobs }): State<ApproveMapState>,
+    session: Session,
+    Path(submission_id): Path<SubmissionId>,
+    Json(NewReview { state, comment }): Json<NewReview>,
+) -> Result<Created<CreatedReview>, ErrorResponse> {
+    let reviewer_id = session.user().id();
+
+    // Only the first review that requires a map creates it; afterwards the map exists and
+    // `review()` moves it along with the submission.
+    let unmapped_submission = if state.requires_map() {
+        let submission = cs2kz::maps::submissions::get_by_id(&cx, submission_id)
+            .await
+            .map_err(|err| ErrorResponse::internal_server_error(err))?
+            .ok_or_else(ErrorResponse::not_found)?;
+
+        submission.map_id.is_none().then_some(submission)
+    } else {
+        None
+    };
+
+    let job_id = if let Some(submission) = unmapped_submission {
+        if !submission.state.can_transition_to(state) {
+            return Err(ErrorResponse::invalid_submission_state(submission.state, state));
+        }
+
+        let workshop_id = submission.workshop_id;
+        let name = fetch_map_name(&map_downloader, workshop_id).await?;
+
+        create_missing_mappers(
+            &cx,
+            &map_downloader,
+            iter::chain(
+                &submission.mappers,
+                submission.courses.iter().flat_map(|course| &course.mappers),
+            )
+            .copied()
+            .collect::<HashSet<_>>(),
+        )
+        .await?;
+
+        let QueuedJob { job_id } = jobs.spawn(workshop_id, move |job| async move {
+            let vpk_checksum = job.download_map(&map_downloader, workshop_id).await?;
+
+            job.set_status(JobStatus::Writing);
+
+            let review = cs2kz::maps::submissions::NewReview {
+                submission_id,
+                reviewer_id,
+                state,
+                comment: &comment,
+            };
+
+            match cs2kz::maps::submissions::approve(&cx, review, name, vpk_checksum).await {
+                Ok(Some(map_id)) => Ok(map_id),
+                Ok(None) => Err(String::from("the submission no longer exists")),
+                Err(ReviewSubmissionError::Database(error)) => {
+                    error!(%error, "failed to approve map submission");
+                    Err(String::from("failed to write map to database"))
+                },
+                Err(error) => Err(error.to_string()),
+            }
+        })?;
+
+        Some(job_id)
+    } else {
+        let review = cs2kz::maps::submissions::NewReview {
+            submission_id,
+            reviewer_id,
+            state,
+            comment: &comment,
+        };
+
+        cs2kz::maps::submissions::review(&cx, review)
+            .await
+            .map_err(review_error)?
+            .then_some(None)
+            .ok_or_else(ErrorResponse::not_found)?
+    };
+
+    Ok(Created(CreatedReview { job_id }))
+}
+
+fn review_error(error: ReviewSubmissionError) -> ErrorResponse {
+    match error {
+        ReviewSubmissionError::InvalidTransition { from, to } => {
+            ErrorResponse::invalid_submission_state(from, to)
+        },
+        ReviewSubmissionError::MustApproveSeparately => {
+            unreachable!("we only call `review()` once the map exists or for other states")
+        },
+        ReviewSubmissionError::Database(error) => ErrorResponse::internal_server_error(error),
+    }
+}
+
+impl From<cs2kz::maps::submissions::Submission> for Submission {
+    fn from(submission: cs2kz::maps::submissions::Submission) -> Self {
+        Self {
+            id: submission.id,
+            workshop_id: submission.workshop_id,
+            submitted_by: UserInfo {
+                id: submission.submitted_by.id,
+                name: submission.submitted_by.name,
+            },
+            description: submission.description,
+            state: submission.state,
+            mappers: submission.mappers,
+            courses: submission.courses.into_iter().map(Into::into).collect(),
+            map_id: submission.map_id,
+            submitted_at: submission.submitted_at,
+            updated_at: submission.updated_at,
+        }
+    }
+}
+
+impl From<cs2kz::maps::submissions::SubmittedCourse> for SubmittedCourse {
+    fn from(course: cs2kz::maps::submissions::SubmittedCourse) -> Self {
+        Self {
+            name: course.name,
+            description: course.description,
+            mappers: course.mappers,
+            vanilla: course.vanilla.into(),
+            classic: course.classic.into(),
+        }
+    }
+}
+
+impl From<SubmittedCourse> for cs2kz::maps::submissions::SubmittedCourse {
+    fn from(course: SubmittedCourse) -> Self {
+        Self {
+            name: course.name,
+            description: course.description,
+            mappers: course.mappers,
+            vanilla: course.vanilla.into(),
+            classic: course.classic.into(),
+        }
+    }
+}
+
+impl From<cs2kz::maps::submissions::ProposedTiers> for ProposedTiers {
+    fn from(tiers: cs2kz::maps::submissions::ProposedTiers) -> Self {
+        Self {
+            nub_tier: tiers.nub_tier,
+            pro_tier: tiers.pro_tier,
+        }
+    }
+}
+
+impl From<ProposedTiers> for cs2kz::maps::submissions::ProposedTiers {
+    fn from(tiers: ProposedTiers) -> Self {
+        Self {
+            nub_tier: tiers.nub_tier,
+            pro_tier: tiers.pro_tier,
+        }
+    }
+}
+
+impl From<cs2kz::maps::submissions::Review> for Review {
+    fn from(review: cs2kz::maps::submissions::Review) -> Self {
+        Self {
+            reviewer: UserInfo {
+                id: review.reviewer.id,
+                name: review.reviewer.name,
+            },
+            state: review.state,
+            comment: review.comment,
+            created_at: review.created_at,
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use axum::extract::{FromRef, State};
+use axum::handler::Handler;
+use axum::response::NoContent;
+use axum::routing::{MethodRouter, Router};
+use cs2kz::Context;
+use cs2kz::maps::courses::filters::votes::CastVoteError;
+use cs2kz::maps::courses::filters::{CourseFilterId, Tier};
+use cs2kz::maps::{CourseId, MapId};
+use cs2kz::mode::Mode;
+use cs2kz::players::PlayerId;
+use cs2kz::time::Timestamp;
+use cs2kz::users::Permission;
+
+use crate::config::CookieConfig;
+use crate::extract::{Json, Path};
+use crate::middleware::auth::session_auth;
+use crate::middleware::auth::session_auth::Session;
+use crate::middleware::auth::session_auth::authorization::HasPermissions;
+use crate::players::PlayerInfo;
+use crate::response::ErrorResponse;
+
+pub(super) fn router<S>(cx: Context, cookie_config: Arc<CookieConfig>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Context: FromRef<S>,
+{
+    let session_auth_state = session_auth::State::new(cx, cookie_config);
+    let is_logged_in =
+        axum::middleware::from_fn_with_state(session_auth_state.clone(), session_auth);
+    let is_admin = axum::middleware::from_fn_with_state(
+        session_auth_state.authorize_with(HasPermissions::new(Permission::MapPool)),
+        session_auth,
+    );
+
+    Router::new()
+        .route(
+            "/filters/{filter_id}/tier-vote",
+            MethodRouter::new().put(cast_tier_vote.layer(is_logged_in)),
+        )
+        .route("/{map_id}/tier-votes", MethodRouter::new().get(get_tier_votes.layer(is_admin)))
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+pub struct NewTierVote {
+    /// The tier you think the filter should have when teleports are allowed.
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    nub_tier: Tier,
+
+    /// The tier you think the filter should have when no teleports are allowed.
+    ///
+    /// You can only specify this if you completed the filter without teleports.
+    #[schema(value_type = Option<crate::openapi::shims::CourseFilterTier>)]
+    pro_tier: Option<Tier>,
+
+    /// Any feedback you have for the mapper or the map-pool admins.
+    #[serde(default, deserialize_with = "crate::serde::deserialize_empty_as_none")]
+    feedback: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct FilterVotes {
+    #[schema(value_type = u16, minimum = 1)]
+    filter_id: CourseFilterId,
+
+    #[schema(value_type = u16, minimum = 1)]
+    course_id: CourseId,
+
+    course_name: String,
+
+    #[schema(value_type = crate::openapi::shims::Mode)]
+    mode: Mode,
+
+    /// How many players voted for each NUB tier.
+    #[schema(value_type = BTreeMap<String, u32>)]
+    nub_tiers: BTreeMap<Tier, u32>,
+
+    /// How many players voted for each PRO tier.
+    #[schema(value_type = BTreeMap<String, u32>)]
+    pro_tiers: BTreeMap<Tier, u32>,
+
+    /// The individual votes, most recent first.
+    votes: Vec<TierVote>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct TierVote {
+    player: PlayerInfo,
+
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    nub_tier: Tier,
+
+    #[schema(value_type = Option<crate::openapi::shims::CourseFilterTier>)]
+    pro_tier: Option<Tier>,
+
+    feedback: Option<String>,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    updated_at: Timestamp,
+}
+
+/// Votes on a filter's tiers.
+///
+/// Only players who completed the filter can vote on it, and only while its map is in testing.
+/// Voting again replaces your previous vote.
+#[tracing::instrument(skip(cx, session), fields(session.id = %session.id()))]
+#[utoipa::path(
+    put,
+    path = "/maps/filters/{filter_id}/tier-vote",
+    tag = "Maps",
+    params(("filter_id" = u16, Path, description = "the filter's ID")),
+    request_body = NewTierVote,
+    responses(
+        (status = 204,),
+        (status = 400, description = "invalid path parameters"),
+        (status = 401,),
+        (status = 404,),
+        (status = 409, description = "you are not allowed to vote on this filter"),
+        (status = 422, description = "invalid request body"),
+    ),
+)]
+async fn cast_tier_vote(
+    State(cx): State<Context>,
+    session: Session,
+    Path(filter_id): Path<CourseFilterId>,
+    Json(NewTierVote { nub_tier, pro_tier, feedback }): Json<NewTierVote>,
+) -> Result<NoContent, ErrorResponse> {
+    let vote = cs2kz::maps::courses::filters::votes::NewVote {
+        filter_id,
+        player_id: PlayerId::new(*session.user().id().as_ref()),
+        nub_tier,
+        pro_tier,
+        feedback: feedback.as_deref(),
+    };
+
+    match cs2kz::maps::courses::filters::votes::cast(&cx, vote).await {
+        Ok(true) => Ok(NoContent),
+        Ok(false) => Err(ErrorResponse::not_found()),
+        Err(CastVoteError::Database(error)) => Err(ErrorResponse::internal_server_error(error)),
+        Err(error) => Err(ErrorResponse::cannot_vote_on_filter(error)),
+    }
+}
+
+/// Returns the tier votes cast on a map's filters.
+///
+/// Use this to inform the final tiers before approving a map that has been in testing.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/maps/{map_id}/tier-votes",
+    tag = "Maps",
+    params(("map_id" = u16, Path, description = "the map's ID")),
+    responses(
+        (status = 200, body = Vec<FilterVotes>),
+        (status = 400, description = "invalid path parameters"),
+        (status = 401,),
+    ),
+)]
+async fn get_tier_votes(
+    State(cx): State<Context>,
+    Path(map_id): Path<MapId>,
+) -> Result<Json<Vec<FilterVotes>>, ErrorResponse> {
+    let votes = cs2kz::maps::courses::filters::votes::get_for_map(&cx, map_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    Ok(Json(votes.into_iter().map(FilterVotes::from).collect()))
+}
+
+impl From<cs2kz::maps::courses::filters::votes::FilterVotes> for FilterVotes {
+    fn from(votes: cs2kz::maps::courses::filters::votes::FilterVotes) -> Self {
+        Self {
+            filter_id: votes.filter_id,
+            course_id: votes.course_id,
+            course_name: votes.course_name,
+            mode: votes.mode,
+            nub_tiers: votes.nub_tiers,
+            pro_tiers: votes.pro_tiers,
+            votes: votes.votes.into_iter().map(TierVote::from).collect(),
+        }
+    }
+}
+
+impl From<cs2kz::maps::courses::filters::votes::Vote> for TierVote {
+    fn from(vote: cs2kz::maps::courses::filters::votes::Vote) -> Self {
+        Self {
+            player: vote.player.into(),
+            nub_tier: vote.nub_tier,
+            pro_tier: vote.pro_tier,
+            feedback: vote.feedback,
+            updated_at: vote.updated_at,
+        }
+    }
+}
@@ -0,0 +1,131 @@
+use axum::extract::State;
+use cs2kz::Context;
+use cs2kz::maps::{CourseId, MapChecksum, MapId, MapState};
+use cs2kz::steam::WorkshopId;
+use cs2kz::time::Timestamp;
+use futures_util::TryStreamExt;
+
+use super::MapIdentifier;
+use crate::extract::{Json, Path};
+use crate::response::ErrorResponse;
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct MapVersion {
+    #[schema(value_type = u16, minimum = 1)]
+    map_id: MapId,
+
+    /// The version number, starting at 1.
+    version: u16,
+
+    #[schema(value_type = u32)]
+    workshop_id: WorkshopId,
+
+    name: String,
+
+    #[schema(value_type = crate::openapi::shims::MapState)]
+    state: MapState,
+
+    /// A checksum of this version's `.vpk` file.
+    #[schema(value_type = str)]
+    vpk_checksum: MapChecksum,
+
+    /// What changed compared to the previous version.
+    changelog: Option<String>,
+
+    /// The ID of the previous version.
+    #[schema(value_type = Option<u16>, minimum = 1)]
+    previous_version: Option<MapId>,
+
+    /// The ID of the next version.
+    #[schema(value_type = Option<u16>, minimum = 1)]
+    next_version: Option<MapId>,
+
+    courses: Vec<CourseVersion>,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    approved_at: Timestamp,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct CourseVersion {
+    #[schema(value_type = u16, minimum = 1)]
+    id: CourseId,
+
+    name: String,
+
+    /// The ID of the course on the previous map version that this course is unchanged from.
+    #[schema(value_type = Option<u16>, minimum = 1)]
+    previous_version: Option<CourseId>,
+}
+
+/// Returns every version of a map, newest first.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/maps/{map}/versions",
+    tag = "Maps",
+    params(("map" = MapIdentifier, Path, description = "a map ID or name")),
+    responses(
+        (status = 200, body = Vec<MapVersion>),
+        (status = 400, description = "invalid path parameters"),
+        (status = 404,),
+    ),
+)]
+pub(super) async fn get_map_versions(
+    State(cx): State<Context>,
+    Path(map_identifier): Path<MapIdentifier>,
+) -> Result<Json<Vec<MapVersion>>, ErrorResponse> {
+    let map_id = match map_identifier {
+        MapIdentifier::Id(id) => id,
+        MapIdentifier::Name(ref name) => {
+            cs2kz::maps::get_by_name(&cx, name)
+                .try_next()
+                .await
+                .map_err(|err| ErrorResponse::internal_server_error(err))?
+                .ok_or_else(ErrorResponse::not_found)?
+                .id
+        },
+    };
+
+    let versions = cs2kz::maps::versions::get(&cx, map_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    if versions.is_empty() {
+        return Err(ErrorResponse::not_found());
+    }
+
+    Ok(Json(versions.into_iter().map(MapVersion::from).collect()))
+}
+
+impl From<cs2kz::maps::versions::MapVersion> for MapVersion {
+    fn from(version: cs2kz::maps::versions::MapVersion) -> Self {
+        Self {
+            map_id: version.map_id,
+            version: version.version,
+            workshop_id: version.workshop_id,
+            name: version.name,
+            state: version.state,
+            vpk_checksum: version.vpk_checksum,
+            changelog: version.changelog,
+            previous_version: version.previous_version,
+            next_version: version.next_version,
+            courses: version
+                .courses
+                .into_iter()
+                .map(CourseVersion::from)
+                .collect(),
+            approved_at: version.approved_at,
+        }
+    }
+}
+
+impl From<cs2kz::maps::versions::CourseVersion> for CourseVersion {
+    fn from(course: cs2kz::maps::versions::CourseVersion) -> Self {
+        Self {
+            id: course.id,
+            name: course.name,
+            previous_version: course.previous_version,
+        }
+    }
+}
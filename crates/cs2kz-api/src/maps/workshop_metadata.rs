@@ -0,0 +1,395 @@
+//! Workshop metadata and preview images of maps.
+//!
+//! [`run()`] periodically fetches metadata about every map's workshop item from Steam, and
+//! mirrors preview images into a [`PreviewCache`], so clients don't have to hot-link Steam's CDN.
+//! See [`cs2kz::maps::workshop_metadata`] for how the metadata is stored.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+use std::{fmt, io};
+
+use axum::extract::State;
+use axum::response::{IntoResponse, Response};
+use bytes::Bytes;
+use cs2kz::Context;
+use cs2kz::maps::workshop_metadata::{
+    GetWorkshopMetadataError,
+    MirroredPreview,
+    NewWorkshopMetadata,
+    SaveWorkshopMetadataError,
+};
+use cs2kz::steam::WorkshopId;
+use cs2kz::time::Timestamp;
+use tokio_util::sync::CancellationToken;
+use url::Url;
+use utoipa::openapi;
+
+use super::MapsState;
+use super::workshop_updates::CHUNK_SIZE;
+use crate::extract::Path;
+use crate::response::ErrorResponse;
+use crate::steam;
+
+/// The largest preview image we are willing to mirror.
+const MAX_PREVIEW_SIZE: u64 = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone, serde::Serialize, utoipa::ToSchema)]
+pub struct WorkshopMetadata {
+    /// The item's preview image, served by the API.
+    #[schema(value_type = Option<String>, format = Uri)]
+    preview_url: Option<Url>,
+
+    /// The size of the item's files, in bytes.
+    file_size: Option<u64>,
+
+    tags: Vec<String>,
+
+    /// When the item was created on the workshop.
+    #[schema(value_type = Option<crate::openapi::shims::Timestamp>)]
+    created_at: Option<Timestamp>,
+
+    /// When the item was last updated on the workshop.
+    #[schema(value_type = Option<crate::openapi::shims::Timestamp>)]
+    updated_at: Option<Timestamp>,
+
+    /// When the API last fetched this metadata from Steam.
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    refreshed_at: Timestamp,
+}
+
+/// Local storage for mirrored preview images.
+///
+/// Images are stored as `{dir}/{workshop_id}`.
+#[derive(Debug)]
+pub struct PreviewCache {
+    dir: PathBuf,
+
+    /// The API's public URL, for building links to the cached images.
+    ///
+    /// This always ends with a `/`, so joining relative paths onto it keeps any path prefix the
+    /// API is served under.
+    #[debug("{:?}", public_url.as_str())]
+    public_url: Url,
+}
+
+#[derive(Debug, Display, Error, From)]
+enum MirrorPreviewError {
+    #[display("failed to download preview image")]
+    Http(reqwest::Error),
+
+    #[display("preview image has unexpected content type {content_type:?}")]
+    #[from(ignore)]
+    NotAnImage { content_type: String },
+
+    #[display("preview image exceeds {MAX_PREVIEW_SIZE} bytes")]
+    TooLarge,
+
+    #[display("failed to store preview image")]
+    Io(io::Error),
+}
+
+#[derive(Debug, Display, Error, From)]
+enum RefreshError {
+    #[display("{_0}")]
+    GetMetadata(GetWorkshopMetadataError),
+
+    #[display("failed to fetch map details from Steam: {_0}")]
+    Steam(steam::ApiError),
+
+    #[display("{_0}")]
+    SaveMetadata(SaveWorkshopMetadataError),
+}
+
+/// A mirrored preview image.
+#[derive(Clone)]
+pub struct PreviewImage {
+    bytes: Bytes,
+    content_type: String,
+}
+
+impl PreviewCache {
+    pub fn new(dir: PathBuf, mut public_url: Url) -> Self {
+        if !public_url.path().ends_with('/') {
+            public_url.set_path(&format!("{}/", public_url.path()));
+        }
+
+        Self { dir, public_url }
+    }
+
+    fn path(&self, workshop_id: WorkshopId) -> PathBuf {
+        self.dir.join(workshop_id.to_string())
+    }
+
+    fn url(&self, workshop_id: WorkshopId) -> Url {
+        self.public_url
+            .join(&format!("maps/previews/{workshop_id}"))
+            .expect("path should be valid")
+    }
+
+    /// Downloads the image at `source_url` and stores it as the preview of `workshop_id`.
+    #[tracing::instrument(skip(self, http_client), err(level = "debug"))]
+    async fn mirror(
+        &self,
+        http_client: &reqwest::Client,
+        workshop_id: WorkshopId,
+        source_url: &str,
+    ) -> Result<MirroredPreview, MirrorPreviewError> {
+        let mut response = http_client
+            .get(source_url)
+            .send()
+            .await?
+            .error_for_status()?;
+        let content_type = response
+            .headers()
+            .get(http::header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_owned();
+
+        if !content_type.starts_with("image/") {
+            return Err(MirrorPreviewError::NotAnImage { content_type });
+        }
+
+        if response
+            .content_length()
+            .is_some_and(|length| length > MAX_PREVIEW_SIZE)
+        {
+            return Err(MirrorPreviewError::TooLarge);
+        }
+
+        // the server might not have sent a `Content-Length`, or lied about it
+        let mut bytes = Vec::new();
+
+        while let Some(chunk) = response.chunk().await? {
+            if (bytes.len() + chunk.len()) as u64 > MAX_PREVIEW_SIZE {
+                return Err(MirrorPreviewError::TooLarge);
+            }
+
+            bytes.extend_from_slice(&chunk);
+        }
+
+        // write to a temporary file first so we never serve half-written images
+        let path = self.path(workshop_id);
+        let tmp_path = path.with_extension("tmp");
+
+        tokio::fs::create_dir_all(&self.dir).await?;
+        tokio::fs::write(&tmp_path, &bytes).await?;
+        tokio::fs::rename(&tmp_path, &path).await?;
+
+        Ok(MirroredPreview {
+            source_url: source_url.to_owned(),
+            content_type,
+        })
+    }
+}
+
+/// Periodically refreshes the workshop metadata of all maps.
+#[tracing::instrument(skip_all)]
+pub async fn run(
+    cx: Context,
+    interval: Duration,
+    previews: Arc<PreviewCache>,
+    cancellation_token: CancellationToken,
+) {
+    let http_client = reqwest::Client::new();
+
+    loop {
+        if let Err(error) = refresh(&cx, &http_client, &previews, &cancellation_token).await {
+            error!(%error, "failed to refresh workshop metadata");
+        }
+
+        select! {
+            () = cancellation_token.cancelled() => break,
+            () = tokio::time::sleep(interval) => {},
+        }
+    }
+}
+
+async fn refresh(
+    cx: &Context,
+    http_client: &reqwest::Client,
+    previews: &PreviewCache,
+    cancellation_token: &CancellationToken,
+) -> Result<(), RefreshError> {
+    let workshop_ids = cs2kz::maps::workshop_metadata::get_workshop_ids(cx).await?;
+
+    debug!(amount = workshop_ids.len(), "refreshing workshop metadata");
+
+    for workshop_ids in workshop_ids.chunks(CHUNK_SIZE) {
+        let stored = cs2kz::maps::workshop_metadata::get(cx, workshop_ids).await?;
+        let details = steam::maps::fetch_map_details(http_client, workshop_ids).await?;
+
+        for details in details {
+            if cancellation_token.is_cancelled() {
+                return Ok(());
+            }
+
+            let mirrored_preview = stored
+                .get(&details.workshop_id)
+                .and_then(|metadata| metadata.mirrored_preview.as_ref());
+
+            let needs_mirroring = match (&details.preview_url, mirrored_preview) {
+                (None, _) => false,
+                (Some(_), None) => true,
+                (Some(preview_url), Some(mirrored)) => {
+                    *preview_url != mirrored.source_url
+                        || !tokio::fs::try_exists(previews.path(details.workshop_id))
+                            .await
+                            .unwrap_or(false)
+                },
+            };
+
+            let new_preview = match details.preview_url {
+                Some(ref preview_url) if needs_mirroring => {
+                    // a single broken image shouldn't prevent us from refreshing the others
+                    match previews
+                        .mirror(http_client, details.workshop_id, preview_url)
+                        .await
+                    {
+                        Ok(preview) => Some(preview),
+                        Err(error) => {
+                            warn!(%error, %details.workshop_id, "failed to mirror preview image");
+                            None
+                        },
+                    }
+                },
+                _ => None,
+            };
+
+            cs2kz::maps::workshop_metadata::save(cx, NewWorkshopMetadata {
+                workshop_id: details.workshop_id,
+                preview_url: details.preview_url.as_deref(),
+                mirrored_preview: new_preview.as_ref(),
+                file_size: details.file_size,
+                tags: &details.tags,
+                created_at: details.created_at,
+                updated_at: Some(details.updated_at),
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns the workshop metadata for the given items, ready to be included in responses.
+pub(super) async fn get_for(
+    cx: &Context,
+    previews: &PreviewCache,
+    workshop_ids: &[WorkshopId],
+) -> Result<HashMap<WorkshopId, WorkshopMetadata>, ErrorResponse> {
+    let metadata = cs2kz::maps::workshop_metadata::get(cx, workshop_ids)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    Ok(metadata
+        .into_iter()
+        .map(|(workshop_id, metadata)| {
+            (workshop_id, WorkshopMetadata {
+                preview_url: metadata.mirrored_preview.map(|_| previews.url(workshop_id)),
+                file_size: metadata.file_size,
+                tags: metadata.tags,
+                created_at: metadata.created_at,
+                updated_at: metadata.updated_at,
+                refreshed_at: metadata.refreshed_at,
+            })
+        })
+        .collect())
+}
+
+/// Returns the preview image of a workshop item.
+///
+/// Images are mirrored from the Steam workshop. Links to this endpoint are included in map
+/// responses as `workshop.preview_url`.
+#[tracing::instrument(skip(cx, previews))]
+#[utoipa::path(
+    get,
+    path = "/maps/previews/{workshop_id}",
+    tag = "Maps",
+    params(("workshop_id" = u32, Path, description = "the workshop item's ID")),
+    responses(
+        (status = 200, body = PreviewImage, content_type = "image/*"),
+        (status = 400, description = "invalid path parameters"),
+        (status = 404,),
+    ),
+)]
+pub(super) async fn get_preview(
+    State(MapsState { cx, previews }): State<MapsState>,
+    Path(workshop_id): Path<WorkshopId>,
+) -> Result<PreviewImage, ErrorResponse> {
+    let content_type = cs2kz::maps::workshop_metadata::get(&cx, &[workshop_id])
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .remove(&workshop_id)
+        .and_then(|metadata| metadata.mirrored_preview)
+        .ok_or_else(ErrorResponse::not_found)?
+        .content_type;
+
+    let bytes = match tokio::fs::read(previews.path(workshop_id)).await {
+        Ok(bytes) => bytes,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            return Err(ErrorResponse::not_found());
+        },
+        Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+    };
+
+    Ok(PreviewImage { bytes: bytes.into(), content_type })
+}
+
+impl fmt::Debug for PreviewImage {
+    fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt.debug_struct("PreviewImage")
+            .field("size", &self.bytes.len())
+            .field("content_type", &self.content_type)
+            .finish()
+    }
+}
+
+impl IntoResponse for PreviewImage {
+    fn into_response(self) -> Response {
+        let headers = [
+            (http::header::CONTENT_TYPE, self.content_type),
+            (http::header::CACHE_CONTROL, String::from("public, max-age=86400")),
+        ];
+
+        (headers, self.bytes).into_response()
+    }
+}
+
+impl utoipa::PartialSchema for PreviewImage {
+    fn schema() -> openapi::RefOr<openapi::Schema> {
+        openapi::Schema::Object(
+            openapi::Object::builder()
+                .content_media_type("image/*")
+                .format(Some(openapi::SchemaFormat::KnownFormat(
+                    openapi::schema::KnownFormat::Binary,
+                )))
+                .build(),
+        )
+        .into()
+    }
+}
+
+impl utoipa::ToSchema for PreviewImage {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn preview_urls_keep_path_prefix() {
+        let workshop_id = WorkshopId::from_inner(3070194623_u32);
+
+        for (public_url, expected) in [
+            ("https://api.cs2kz.org", "https://api.cs2kz.org/maps/previews/3070194623"),
+            ("https://cs2kz.org/api", "https://cs2kz.org/api/maps/previews/3070194623"),
+            ("https://cs2kz.org/api/", "https://cs2kz.org/api/maps/previews/3070194623"),
+        ] {
+            let previews = PreviewCache::new(PathBuf::new(), public_url.parse().unwrap());
+
+            assert_eq!(previews.url(workshop_id).as_str(), expected);
+        }
+    }
+}
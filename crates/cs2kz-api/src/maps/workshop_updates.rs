@@ -0,0 +1,197 @@
+//! Detecting updates to approved maps on the Steam workshop.
+//!
+//! [`run()`] periodically asks Steam which workshop items have been updated since we last looked
+//! at them, downloads those, and compares their checksums against the approved maps. See
+//! [`cs2kz::maps::workshop_updates`] for how the results are stored.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use cs2kz::Context;
+use cs2kz::maps::workshop_updates::{MapCheck, RecordCheckError};
+use cs2kz::maps::{GetMapsError, MapChecksum};
+use cs2kz::steam::WorkshopId;
+use cs2kz::time::Timestamp;
+use tokio_util::sync::CancellationToken;
+
+use super::MapInfo;
+use super::jobs::Jobs;
+use crate::config::{DepotDownloaderConfig, SteamAuthConfig};
+use crate::extract::Json;
+use crate::response::ErrorResponse;
+use crate::steam;
+use crate::steam::MapDownloader;
+
+/// How many workshop items to ask Steam about in a single request.
+pub(super) const CHUNK_SIZE: usize = 100;
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct PendingUpdate {
+    /// The approved map that is now out of date.
+    map: MapInfo,
+
+    /// The ID of the map's Steam workshop item.
+    #[schema(value_type = u32)]
+    workshop_id: WorkshopId,
+
+    /// The name of the map currently on the workshop.
+    name: String,
+
+    /// A checksum of the `.vpk` file currently on the workshop.
+    #[schema(value_type = str)]
+    vpk_checksum: MapChecksum,
+
+    /// When the workshop item was updated.
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    workshop_updated_at: Timestamp,
+
+    /// When the API noticed the update.
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    detected_at: Timestamp,
+}
+
+#[derive(Debug, Display, Error, From)]
+enum CheckForUpdatesError {
+    #[display("{_0}")]
+    GetMaps(GetMapsError),
+
+    #[display("failed to fetch map details from Steam: {_0}")]
+    Steam(steam::ApiError),
+
+    #[display("{_0}")]
+    RecordCheck(RecordCheckError),
+}
+
+/// Periodically checks approved maps for updates on the Steam workshop.
+#[tracing::instrument(skip_all)]
+pub async fn run(
+    cx: Context,
+    interval: Duration,
+    depot_downloader_config: Arc<DepotDownloaderConfig>,
+    steam_auth_config: Arc<SteamAuthConfig>,
+    jobs: Jobs,
+    cancellation_token: CancellationToken,
+) {
+    let http_client = reqwest::Client::new();
+    let map_downloader =
+        steam::Downloader::from_config(&depot_downloader_config, &steam_auth_config);
+
+    loop {
+        if let Err(error) =
+            check_for_updates(&cx, &http_client, &map_downloader, &jobs, &cancellation_token).await
+        {
+            error!(%error, "failed to check for workshop updates");
+        }
+
+        select! {
+            () = cancellation_token.cancelled() => break,
+            () = tokio::time::sleep(interval) => {},
+        }
+    }
+}
+
+async fn check_for_updates(
+    cx: &Context,
+    http_client: &reqwest::Client,
+    map_downloader: &steam::Downloader,
+    jobs: &Jobs,
+    cancellation_token: &CancellationToken,
+) -> Result<(), CheckForUpdatesError> {
+    let maps = cs2kz::maps::workshop_updates::get_tracked(cx).await?;
+
+    debug!(amount = maps.len(), "checking maps for workshop updates");
+
+    for maps in maps.chunks(CHUNK_SIZE) {
+        let workshop_ids = maps.iter().map(|map| map.workshop_id).collect::<Vec<_>>();
+        let details = steam::maps::fetch_map_details(http_client, &workshop_ids)
+            .await?
+            .into_iter()
+            .map(|details| (details.workshop_id, details))
+            .collect::<HashMap<_, _>>();
+
+        for map in maps {
+            if cancellation_token.is_cancelled() {
+                return Ok(());
+            }
+
+            let Some(details) = details.get(&map.workshop_id) else {
+                warn!(%map.id, %map.workshop_id, "workshop item no longer exists");
+                continue;
+            };
+
+            if details.updated_at <= map.last_checked_update {
+                continue;
+            }
+
+            // approval jobs download into the same file
+            let Some(_lock) = jobs.lock_for_update_check(map.workshop_id) else {
+                debug!(%map.id, %map.workshop_id, "workshop item is being approved; skipping");
+                continue;
+            };
+
+            debug!(%map.id, %map.workshop_id, "workshop item was updated; downloading");
+
+            // a single broken download shouldn't prevent us from checking the other maps
+            let download = async {
+                map_downloader.download(map.workshop_id).await?;
+                map_downloader.compute_checksum(map.workshop_id).await
+            };
+
+            let vpk_checksum = match download.await {
+                Ok(vpk_checksum) => vpk_checksum,
+                Err(error) => {
+                    error!(%error, %map.id, %map.workshop_id, "failed to download map");
+                    continue;
+                },
+            };
+
+            cs2kz::maps::workshop_updates::record_check(cx, MapCheck {
+                map_id: map.id,
+                workshop_updated_at: details.updated_at,
+                new_version: (vpk_checksum != map.vpk_checksum)
+                    .then(|| (details.name.clone(), vpk_checksum)),
+            })
+            .await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Returns approved maps that have changed on the Steam workshop.
+///
+/// These maps should get a new version through `PUT /maps`, which resolves the update.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/maps/pending-updates",
+    tag = "Maps",
+    responses(
+        (status = 200, body = Vec<PendingUpdate>),
+        (status = 401,),
+    ),
+)]
+pub(super) async fn get_pending_updates(
+    State(cx): State<Context>,
+) -> Result<Json<Vec<PendingUpdate>>, ErrorResponse> {
+    let updates = cs2kz::maps::workshop_updates::get_pending(&cx)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    Ok(Json(updates.into_iter().map(PendingUpdate::from).collect()))
+}
+
+impl From<cs2kz::maps::workshop_updates::PendingUpdate> for PendingUpdate {
+    fn from(update: cs2kz::maps::workshop_updates::PendingUpdate) -> Self {
+        Self {
+            map: update.map.into(),
+            workshop_id: update.workshop_id,
+            name: update.name,
+            vpk_checksum: update.vpk_checksum,
+            workshop_updated_at: update.workshop_updated_at,
+            detected_at: update.detected_at,
+        }
+    }
+}
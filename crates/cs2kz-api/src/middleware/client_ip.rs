@@ -0,0 +1,30 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::extract::ConnectInfo;
+
+/// Returns the IP address of the client that made the given request.
+///
+/// When the API runs behind a reverse proxy on the same host, every connection appears to come
+/// from localhost. For those connections we use the last address in the `X-Forwarded-For`
+/// header, which is the one appended by the proxy. Entries before it are supplied by the client
+/// and cannot be trusted.
+pub fn client_ip<B>(request: &http::Request<B>) -> IpAddr {
+    let &ConnectInfo(peer_addr) = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .expect("`ConnectInfo` should be injected by the router");
+
+    if !peer_addr.ip().is_loopback() {
+        return peer_addr.ip();
+    }
+
+    request
+        .headers()
+        .get_all("x-forwarded-for")
+        .iter()
+        .next_back()
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .and_then(|addr| addr.trim().parse::<IpAddr>().ok())
+        .unwrap_or(peer_addr.ip())
+}
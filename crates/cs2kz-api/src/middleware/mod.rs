@@ -4,3 +4,5 @@ pub mod trace;
 pub mod trim_trailing_slash;
 pub mod catch_panic;
 pub mod auth;
+pub mod per_client_limit;
+pub mod client_ip;
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::collections::hash_map::Entry;
+use std::net::IpAddr;
+use std::num::NonZero;
+use std::sync::{Arc, Mutex};
+
+use axum::body::Body;
+use axum::extract::{Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use futures_util::StreamExt;
+
+use super::client_ip::client_ip;
+use crate::response::ErrorResponse;
+
+/// Limits how many requests a single client may have in flight at the same time.
+///
+/// Clients are identified by their [`client_ip()`], so requests forwarded by a reverse proxy are
+/// not all counted against the proxy.
+///
+/// A request counts as "in flight" until its response body has been fully sent, which makes this
+/// suitable for long-running streaming responses.
+#[derive(Debug, Clone)]
+pub struct PerClientLimit {
+    max: NonZero<usize>,
+    in_flight: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+#[derive(Debug)]
+struct Permit {
+    client: IpAddr,
+    in_flight: Arc<Mutex<HashMap<IpAddr, usize>>>,
+}
+
+impl PerClientLimit {
+    pub fn new(max: NonZero<usize>) -> Self {
+        Self { max, in_flight: Arc::default() }
+    }
+
+    fn acquire(&self, client: IpAddr) -> Option<Permit> {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|err| err.into_inner());
+        let count = in_flight.entry(client).or_default();
+
+        if *count >= self.max.get() {
+            return None;
+        }
+
+        *count += 1;
+
+        Some(Permit {
+            client,
+            in_flight: Arc::clone(&self.in_flight),
+        })
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let mut in_flight = self.in_flight.lock().unwrap_or_else(|err| err.into_inner());
+
+        if let Entry::Occupied(mut entry) = in_flight.entry(self.client) {
+            *entry.get_mut() -= 1;
+
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+}
+
+/// Middleware to enforce a [`PerClientLimit`].
+#[tracing::instrument(skip_all, err(Debug, level = "debug"))]
+pub async fn per_client_limit(
+    State(limit): State<PerClientLimit>,
+    request: Request,
+    next: Next,
+) -> Result<Response, ErrorResponse> {
+    let permit = limit
+        .acquire(client_ip(&request))
+        .ok_or_else(ErrorResponse::too_many_requests)?;
+    let response = next.run(request).await;
+
+    // hold on to the permit until the body has been sent
+    Ok(response.map(|body| {
+        Body::from_stream(body.into_data_stream().map(move |chunk| {
+            let _ = &permit;
+            chunk
+        }))
+    }))
+}
@@ -47,11 +47,14 @@ static CONFIG: LazyLock<Arc<utoipa_swagger_ui::Config<'static>>> = LazyLock::new
         (name = "Jumpstats"),
         (name = "Records"),
         (name = "Player Bans"),
+        (name = "Points", description = "the points daemon"),
     ),
     components(
         schemas(
             shims::Limit,
             shims::Offset,
+            shims::Cursor,
+            shims::Maps_SortBy,
             shims::Records_SortBy,
             shims::Records_SortOrder,
             crate::players::PlayerIdentifier,
@@ -84,8 +87,11 @@ static CONFIG: LazyLock<Arc<utoipa_swagger_ui::Config<'static>>> = LazyLock::new
         crate::servers::delete_server_access_key,
 
         crate::players::get_players,
+        crate::players::get_player_ranking,
         crate::players::get_player,
         crate::players::get_player_profile,
+        crate::players::compare_players,
+        crate::players::get_player_rating_history,
         crate::players::get_player_steam_profile,
         crate::players::get_player_preferences,
         crate::players::update_player_preferences,
@@ -94,20 +100,43 @@ static CONFIG: LazyLock<Arc<utoipa_swagger_ui::Config<'static>>> = LazyLock::new
         crate::maps::get_maps,
         crate::maps::get_map,
         crate::maps::update_map,
+        crate::maps::versions::get_map_versions,
+        crate::maps::carry_over::carry_over_records,
+        crate::maps::carry_over::get_carry_overs,
+        crate::maps::deletion::delete_map,
+        crate::maps::workshop_metadata::get_preview,
+        crate::maps::submissions::submit_map,
+        crate::maps::submissions::get_submissions,
+        crate::maps::submissions::get_submission,
+        crate::maps::submissions::update_submission,
+        crate::maps::submissions::review_submission,
+        crate::maps::tier_votes::cast_tier_vote,
+        crate::maps::tier_votes::get_tier_votes,
+        crate::maps::workshop_updates::get_pending_updates,
+        crate::maps::jobs::get_job,
 
         crate::jumpstats::get_jumpstats,
+        crate::jumpstats::export_jumpstats,
         crate::jumpstats::get_jumpstat,
         crate::jumpstats::get_jumpstat_replay,
 
         crate::records::get_records,
+        crate::records::export_records,
         crate::records::get_record,
+        crate::records::get_record_points,
         crate::records::get_record_replay,
 
+        crate::leaderboards::get_projection,
+        crate::leaderboards::get_distribution,
+
         crate::bans::create_ban,
         crate::bans::get_bans,
         crate::bans::get_ban,
         crate::bans::update_ban,
         crate::bans::delete_ban,
+
+        crate::points::recalculate_points,
+        crate::points::get_points_status,
     ),
 )]
 pub struct Schema;
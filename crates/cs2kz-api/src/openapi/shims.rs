@@ -11,12 +11,22 @@ pub struct Paginated<T> {
     /// Different endpoints have different hard-limits on how many values they will return at
     /// a time. They usually also have an `offset` query parameter you can use to fetch the next
     /// set of values. You can use `total` to infer when you can stop making requests.
+    ///
+    /// This is omitted for pages fetched with a `cursor`.
     #[allow(dead_code)]
-    total: u64,
+    total: Option<u64>,
 
     /// The values returned for this request.
     #[allow(dead_code)]
     values: Vec<T>,
+
+    /// A cursor pointing at the last value of this page.
+    ///
+    /// Endpoints which support cursor pagination accept this value as their `cursor` query
+    /// parameter to fetch the next page. It is omitted if there are no more values.
+    #[allow(dead_code)]
+    #[schema(value_type = Option<Cursor>)]
+    next_cursor: Option<String>,
 }
 
 #[derive(ToSchema, serde::Serialize)]
@@ -49,6 +59,15 @@ schema_type!(Offset => {
     )
 });
 
+schema_type!(Cursor => {
+    Schema::Object(
+        Object::builder()
+            .description(Some("an opaque pagination cursor"))
+            .schema_type(SchemaType::Type(schema::Type::String))
+            .build(),
+    )
+});
+
 schema_type!(SteamId => {
     Schema::Object(
         Object::builder()
@@ -127,6 +146,17 @@ schema_type!(AccessKey => {
     )
 });
 
+schema_type!(MapApprovalJobId => {
+    Schema::Object(
+        Object::builder()
+            .description(Some("a map approval job's ID"))
+            .schema_type(SchemaType::Type(schema::Type::String))
+            .format(Some(SchemaFormat::KnownFormat(KnownFormat::Ulid)))
+            .examples(["01JG9X9ZMAKCNXH19VMXZ7BC08"])
+            .build(),
+    )
+});
+
 schema_type!(MapState => {
     Schema::Object(
         Object::builder()
@@ -136,6 +166,21 @@ schema_type!(MapState => {
     )
 });
 
+schema_type!(MapSubmissionState => {
+    Schema::Object(
+        Object::builder()
+            .schema_type(SchemaType::Type(schema::Type::String))
+            .enum_values(Some([
+                "submitted",
+                "changes-requested",
+                "in-testing",
+                "approved",
+                "rejected",
+            ]))
+            .build(),
+    )
+});
+
 schema_type!(CourseFilterState => {
     Schema::Object(
         Object::builder()
@@ -215,7 +260,23 @@ schema_type!(Records_SortBy => {
     Schema::Object(
         Object::builder()
             .schema_type(SchemaType::Type(schema::Type::String))
-            .enum_values(Some(["submission-date", "time"]))
+            .enum_values(Some([
+                "submission-date",
+                "time",
+                "nub-points",
+                "pro-points",
+                "nub-rank",
+                "pro-rank",
+            ]))
+            .build(),
+    )
+});
+
+schema_type!(Maps_SortBy => {
+    Schema::Object(
+        Object::builder()
+            .schema_type(SchemaType::Type(schema::Type::String))
+            .enum_values(Some(["name", "nub-tier", "pro-tier", "approval-date", "popularity"]))
             .build(),
     )
 });
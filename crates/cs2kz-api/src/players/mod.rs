@@ -5,14 +5,18 @@ use axum::handler::Handler;
 use axum::response::NoContent;
 use axum::routing::{self, MethodRouter, Router};
 use cs2kz::Context;
+use cs2kz::maps::courses::filters::Tier;
+use cs2kz::maps::{CourseFilterId, CourseId};
 use cs2kz::mode::Mode;
-use cs2kz::pagination::{Limit, Offset, Paginated};
-use cs2kz::players::{PlayerId, Preferences};
-use cs2kz::time::Timestamp;
+use cs2kz::pagination::{Cursor, Limit, Offset, Paginated};
+use cs2kz::players::{ComparePlayersError, PlayerId, PlayersCursor, Preferences};
+use cs2kz::records::RecordId;
+use cs2kz::time::{Seconds, Timestamp};
 use futures_util::TryFutureExt;
 
 use crate::config::{CookieConfig, SteamAuthConfig};
 use crate::extract::{Json, Path, Query};
+use crate::maps::MapInfo;
 use crate::middleware::auth::session_auth;
 use crate::middleware::auth::session_auth::authorization::IsPlayer;
 use crate::response::ErrorResponse;
@@ -42,8 +46,11 @@ where
 
     Router::new()
         .route("/", routing::get(get_players))
+        .route("/ranking", routing::get(get_player_ranking))
         .route("/{player}", routing::get(get_player))
         .route("/{player}/profile", routing::get(get_player_profile))
+        .route("/{player}/compare/{opponent}", routing::get(compare_players))
+        .route("/{player}/rating-history", routing::get(get_player_rating_history))
         .route(
             "/{player}/steam-profile",
             routing::get(get_player_steam_profile).with_state(GetSteamProfileState {
@@ -66,6 +73,12 @@ pub struct GetPlayersQuery {
     #[serde(default, deserialize_with = "crate::serde::deserialize_non_empty")]
     name: Option<String>,
 
+    /// The `next_cursor` of a previous response.
+    ///
+    /// This cannot be combined with `offset`.
+    #[param(value_type = Option<crate::openapi::shims::Cursor>)]
+    cursor: Option<PlayersCursor>,
+
     #[serde(default)]
     #[param(value_type = crate::openapi::shims::Limit)]
     limit: Limit<1000, 250>,
@@ -75,6 +88,21 @@ pub struct GetPlayersQuery {
     offset: Offset,
 }
 
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetPlayerRankingQuery {
+    #[param(value_type = crate::openapi::shims::Mode)]
+    mode: Mode,
+
+    #[serde(default)]
+    #[param(value_type = crate::openapi::shims::Limit)]
+    limit: Limit<1000, 100>,
+
+    #[serde(default)]
+    #[param(value_type = crate::openapi::shims::Offset)]
+    offset: Offset,
+}
+
 #[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
 #[into_params(parameter_in = Query)]
 pub struct GetPlayerProfileQuery {
@@ -114,6 +142,148 @@ pub struct PlayerProfile {
     first_joined_at: Timestamp,
 }
 
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct RankedPlayer {
+    /// The player's position on the leaderboard.
+    rank: u64,
+
+    /// The player's SteamID.
+    #[schema(value_type = crate::openapi::shims::SteamId)]
+    id: PlayerId,
+
+    /// The player's name on Steam.
+    name: String,
+
+    rating: f64,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct GetRatingHistoryQuery {
+    #[param(value_type = crate::openapi::shims::Mode)]
+    mode: Mode,
+
+    /// Also include the player's ranks on this course filter's leaderboards.
+    #[param(value_type = Option<u16>, minimum = 1)]
+    filter_id: Option<CourseFilterId>,
+
+    /// How many days to return.
+    #[serde(default)]
+    #[param(value_type = crate::openapi::shims::Limit)]
+    limit: Limit<3650, 365>,
+}
+
+/// A player's rating and rank at the start of a day.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct RatingSnapshot {
+    /// When the snapshot was taken (midnight UTC).
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    date: Timestamp,
+
+    rating: f64,
+
+    /// The player's position on the global rating leaderboard.
+    rank: u32,
+
+    /// The player's rank on the requested filter's NUB leaderboard.
+    ///
+    /// This is only recorded for the top 100 players of each mode, and `null` if no `filter_id`
+    /// was requested.
+    nub_rank: Option<u32>,
+
+    /// The player's rank on the requested filter's PRO leaderboard.
+    ///
+    /// This is only recorded for the top 100 players of each mode, and `null` if no `filter_id`
+    /// was requested.
+    pro_rank: Option<u32>,
+}
+
+#[derive(Debug, serde::Deserialize, utoipa::IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ComparePlayersQuery {
+    #[param(value_type = crate::openapi::shims::Mode)]
+    mode: Mode,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct PlayerComparison {
+    player: PlayerInfo,
+    opponent: PlayerInfo,
+
+    /// Every course both players have finished.
+    courses: Vec<CourseComparison>,
+
+    /// Aggregate results on the NUB leaderboards.
+    nub_summary: ComparisonSummary,
+
+    /// Aggregate results on the PRO leaderboards.
+    pro_summary: ComparisonSummary,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct CourseComparison {
+    map: MapInfo,
+
+    /// The course's ID.
+    #[schema(value_type = u16, minimum = 1)]
+    course_id: CourseId,
+
+    /// The course's name.
+    course_name: String,
+
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    nub_tier: Tier,
+
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    pro_tier: Tier,
+
+    /// The first player's best records on this course.
+    player: ComparedRecords,
+
+    /// The second player's best records on this course.
+    opponent: ComparedRecords,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ComparedRecords {
+    nub: Option<ComparedRecord>,
+    pro: Option<ComparedRecord>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ComparedRecord {
+    #[schema(value_type = u32, minimum = 1)]
+    record_id: RecordId,
+
+    /// Time in seconds.
+    #[schema(value_type = f64)]
+    time: Seconds,
+
+    rank: u32,
+
+    /// This is `null` if the course filter is not ranked.
+    points: Option<f64>,
+}
+
+/// Win / loss counts from the first player's point of view.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ComparisonSummary {
+    /// Courses on which the first player is faster.
+    wins: u32,
+
+    /// Courses on which the second player is faster.
+    losses: u32,
+
+    /// Courses on which both players have the same time.
+    ties: u32,
+
+    /// Courses only the first player has finished.
+    only_player: u32,
+
+    /// Courses only the second player has finished.
+    only_opponent: u32,
+}
+
 #[derive(Debug, serde::Serialize, utoipa::ToSchema)]
 pub struct PlayerInfo {
     /// The player's SteamID.
@@ -138,10 +308,46 @@ pub struct PlayerInfo {
 )]
 async fn get_players(
     State(cx): State<Context>,
-    Query(GetPlayersQuery { name, limit, offset }): Query<GetPlayersQuery>,
+    Query(GetPlayersQuery { name, cursor, limit, offset }): Query<GetPlayersQuery>,
 ) -> Result<Json<Paginated<Vec<Player>>>, ErrorResponse> {
-    let params = cs2kz::players::GetPlayersParams { name: name.as_deref(), limit, offset };
+    if cursor.is_some() && offset.value() != 0 {
+        return Err(ErrorResponse::cursor_with_offset());
+    }
+
+    let params = cs2kz::players::GetPlayersParams { name: name.as_deref(), cursor, limit, offset };
+
     let players = cs2kz::players::get(&cx, params)
+        .map_ok(Paginated::map_into)
+        .and_then(Paginated::collect)
+        .map_err(|err| ErrorResponse::internal_server_error(err))
+        .await?
+        .with_next_cursor(limit.value(), |player: &Player| Cursor::new((), player.id));
+
+    Ok(Json(players))
+}
+
+/// Returns players ordered by their rating.
+///
+/// Ratings are recalculated in the background whenever a leaderboard changes, so they can lag
+/// behind for players other than the one who most recently submitted a record.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/players/ranking",
+    tag = "Players",
+    params(GetPlayerRankingQuery),
+    responses(
+        (status = 200, body = crate::openapi::shims::Paginated<RankedPlayer>),
+        (status = 400, description = "invalid query parameters"),
+    ),
+)]
+async fn get_player_ranking(
+    State(cx): State<Context>,
+    Query(GetPlayerRankingQuery { mode, limit, offset }): Query<GetPlayerRankingQuery>,
+) -> Result<Json<Paginated<Vec<RankedPlayer>>>, ErrorResponse> {
+    let params = cs2kz::players::GetRankingParams { mode, limit, offset };
+
+    let players = cs2kz::players::get_ranking(&cx, params)
         .map_ok(Paginated::map_into)
         .and_then(Paginated::collect)
         .map_err(|err| ErrorResponse::internal_server_error(err))
@@ -167,12 +373,10 @@ async fn get_player(
     State(cx): State<Context>,
     Path(player_identifier): Path<PlayerIdentifier>,
 ) -> Result<Json<Player>, ErrorResponse> {
-    let player = match player_identifier {
-        PlayerIdentifier::Id(id) => cs2kz::players::get_by_id(&cx, id).await,
-        PlayerIdentifier::Name(ref name) => cs2kz::players::get_by_name(&cx, name).await,
-    }
-    .map_err(|err| ErrorResponse::internal_server_error(err))?
-    .ok_or_else(ErrorResponse::not_found)?;
+    let player = get_by_identifier(&cx, &player_identifier)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .ok_or_else(ErrorResponse::not_found)?;
 
     Ok(Json(player.into()))
 }
@@ -206,6 +410,100 @@ async fn get_player_profile(
     Ok(Json(profile.into()))
 }
 
+/// Compares two players' best records on every course they have finished.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/players/{player}/compare/{opponent}",
+    tag = "Players",
+    params(
+        ("player" = PlayerIdentifier, Path, description = "a SteamID or name"),
+        ("opponent" = PlayerIdentifier, Path, description = "a SteamID or name"),
+        ComparePlayersQuery,
+    ),
+    responses(
+        (status = 200, body = PlayerComparison),
+        (status = 400, description = "invalid path parameters, or both players are the same"),
+        (status = 404,),
+    ),
+)]
+async fn compare_players(
+    State(cx): State<Context>,
+    Path((player_identifier, opponent_identifier)): Path<(PlayerIdentifier, PlayerIdentifier)>,
+    Query(ComparePlayersQuery { mode }): Query<ComparePlayersQuery>,
+) -> Result<Json<PlayerComparison>, ErrorResponse> {
+    let (player, opponent) = try_join!(
+        get_by_identifier(&cx, &player_identifier),
+        get_by_identifier(&cx, &opponent_identifier),
+    )
+    .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    let (Some(player), Some(opponent)) = (player, opponent) else {
+        return Err(ErrorResponse::not_found());
+    };
+
+    let comparison = cs2kz::players::compare(&cx, player.id, opponent.id, mode)
+        .await
+        .map_err(|err| match err {
+            ComparePlayersError::SamePlayer => ErrorResponse::invalid_path_params(|details| {
+                details.set_detail(err.to_string());
+            }),
+            ComparePlayersError::Database(error) => ErrorResponse::internal_server_error(error),
+        })?;
+
+    Ok(Json(PlayerComparison {
+        player: PlayerInfo { id: player.id, name: player.name },
+        opponent: PlayerInfo { id: opponent.id, name: opponent.name },
+        courses: comparison.courses.into_iter().map(Into::into).collect(),
+        nub_summary: comparison.nub_summary.into(),
+        pro_summary: comparison.pro_summary.into(),
+    }))
+}
+
+/// Returns a player's daily rating snapshots, newest first.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/players/{player}/rating-history",
+    tag = "Players",
+    params(
+        ("player" = PlayerIdentifier, Path, description = "a SteamID or name"),
+        GetRatingHistoryQuery,
+    ),
+    responses(
+        (status = 200, body = [RatingSnapshot]),
+        (status = 400, description = "invalid path parameters or query string"),
+        (status = 404,),
+    ),
+)]
+async fn get_player_rating_history(
+    State(cx): State<Context>,
+    Path(player_identifier): Path<PlayerIdentifier>,
+    Query(GetRatingHistoryQuery { mode, filter_id, limit }): Query<GetRatingHistoryQuery>,
+) -> Result<Json<Vec<RatingSnapshot>>, ErrorResponse> {
+    let player = get_by_identifier(&cx, &player_identifier)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .ok_or_else(ErrorResponse::not_found)?;
+
+    let params = cs2kz::players::GetRatingHistoryParams { mode, filter_id, limit };
+    let history = cs2kz::players::get_rating_history(&cx, player.id, params)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    Ok(Json(history.into_iter().map(Into::into).collect()))
+}
+
+async fn get_by_identifier(
+    cx: &Context,
+    identifier: &PlayerIdentifier,
+) -> Result<Option<cs2kz::players::Player>, cs2kz::players::GetPlayersError> {
+    match *identifier {
+        PlayerIdentifier::Id(id) => cs2kz::players::get_by_id(cx, id).await,
+        PlayerIdentifier::Name(ref name) => cs2kz::players::get_by_name(cx, name).await,
+    }
+}
+
 /// Returns a player's Steam profile.
 #[tracing::instrument(skip(http_client))]
 #[utoipa::path(
@@ -309,3 +607,72 @@ impl From<cs2kz::players::Profile> for PlayerProfile {
         }
     }
 }
+
+impl From<cs2kz::players::RankedPlayer> for RankedPlayer {
+    fn from(player: cs2kz::players::RankedPlayer) -> Self {
+        Self {
+            rank: player.rank,
+            id: player.id,
+            name: player.name,
+            rating: player.rating,
+        }
+    }
+}
+
+impl From<cs2kz::players::RatingSnapshot> for RatingSnapshot {
+    fn from(snapshot: cs2kz::players::RatingSnapshot) -> Self {
+        Self {
+            date: snapshot.date,
+            rating: snapshot.rating,
+            rank: snapshot.rank,
+            nub_rank: snapshot.nub_rank,
+            pro_rank: snapshot.pro_rank,
+        }
+    }
+}
+
+impl From<cs2kz::players::CourseComparison> for CourseComparison {
+    fn from(course: cs2kz::players::CourseComparison) -> Self {
+        Self {
+            map: course.map.into(),
+            course_id: course.course_id,
+            course_name: course.course_name,
+            nub_tier: course.nub_tier,
+            pro_tier: course.pro_tier,
+            player: course.player.into(),
+            opponent: course.opponent.into(),
+        }
+    }
+}
+
+impl From<cs2kz::players::ComparedRecords> for ComparedRecords {
+    fn from(records: cs2kz::players::ComparedRecords) -> Self {
+        Self {
+            nub: records.nub.map(Into::into),
+            pro: records.pro.map(Into::into),
+        }
+    }
+}
+
+impl From<cs2kz::players::ComparedRecord> for ComparedRecord {
+    fn from(record: cs2kz::players::ComparedRecord) -> Self {
+        Self {
+            record_id: record.record_id,
+            time: record.time,
+            rank: record.rank,
+            points: record.points,
+        }
+    }
+}
+
+impl From<cs2kz::players::ComparisonSummary> for ComparisonSummary {
+    fn from(summary: cs2kz::players::ComparisonSummary) -> Self {
+        Self {
+            wins: summary.wins,
+            losses: summary.losses,
+            ties: summary.ties,
+            only_player: summary.only_player,
+            only_opponent: summary.only_opponent,
+        }
+    }
+}
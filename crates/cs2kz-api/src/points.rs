@@ -0,0 +1,210 @@
+use std::sync::Arc;
+
+use axum::extract::{FromRef, State};
+use axum::handler::Handler;
+use axum::routing::{MethodRouter, Router};
+use cs2kz::Context;
+use cs2kz::maps::CourseFilterId;
+use cs2kz::mode::Mode;
+use cs2kz::points::daemon::{self, RecalculationScope};
+use cs2kz::time::{Seconds, Timestamp};
+use cs2kz::users::Permission;
+use futures_util::TryStreamExt;
+
+use crate::config::CookieConfig;
+use crate::extract::Json;
+use crate::maps::MapIdentifier;
+use crate::middleware::auth::session_auth;
+use crate::middleware::auth::session_auth::Session;
+use crate::middleware::auth::session_auth::authorization::HasPermissions;
+use crate::response::{Accepted, ErrorResponse};
+
+pub fn router<S>(cx: Context, cookie_config: impl Into<Arc<CookieConfig>>) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+    Context: FromRef<S>,
+{
+    let session_auth_state = session_auth::State::new(cx, cookie_config)
+        .authorize_with(HasPermissions::new(Permission::MapPool));
+
+    let is_admin = axum::middleware::from_fn_with_state(session_auth_state, session_auth);
+
+    Router::new()
+        .route("/recalculate", MethodRouter::new().post(recalculate_points.layer(is_admin.clone())))
+        .route("/status", MethodRouter::new().get(get_points_status.layer(is_admin)))
+}
+
+/// Which filters to recalculate.
+#[derive(Debug, serde::Deserialize, utoipa::ToSchema)]
+#[serde(tag = "scope", rename_all = "snake_case")]
+pub enum RecalculatePointsRequest {
+    /// A single course filter.
+    Filter {
+        #[schema(value_type = u16, minimum = 1)]
+        filter_id: CourseFilterId,
+    },
+
+    /// Every filter on a map.
+    Map { map: MapIdentifier },
+
+    /// Every filter of a mode.
+    Mode {
+        #[schema(value_type = crate::openapi::shims::Mode)]
+        mode: Mode,
+    },
+
+    /// Every filter.
+    All,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct QueuedRecalculation {
+    /// How many filters were queued.
+    queued: usize,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct PointsStatus {
+    /// How many filters are waiting to be processed.
+    queued: usize,
+
+    /// Filters that are currently being processed.
+    in_progress: Vec<InProgressFilter>,
+
+    /// How many filters have been processed successfully since the API started.
+    processed: u64,
+
+    /// How many filters failed to process since the API started.
+    failed: u64,
+
+    /// The most recently processed filters, newest first.
+    recent: Vec<ProcessedFilter>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct InProgressFilter {
+    #[schema(value_type = u16, minimum = 1)]
+    filter_id: CourseFilterId,
+
+    /// How many new records caused this filter to be queued.
+    new_records: u64,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    started_at: Timestamp,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct ProcessedFilter {
+    #[schema(value_type = u16, minimum = 1)]
+    filter_id: CourseFilterId,
+
+    #[schema(value_type = crate::openapi::shims::Timestamp)]
+    started_at: Timestamp,
+
+    /// How long processing took, in seconds.
+    #[schema(value_type = f64)]
+    took: Seconds,
+
+    /// The error that occurred while processing the filter, if any.
+    error: Option<String>,
+}
+
+impl From<daemon::Status> for PointsStatus {
+    fn from(status: daemon::Status) -> Self {
+        Self {
+            queued: status.queued,
+            in_progress: status.in_progress.into_iter().map(Into::into).collect(),
+            processed: status.processed,
+            failed: status.failed,
+            recent: status.recent.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+impl From<daemon::InProgressFilter> for InProgressFilter {
+    fn from(filter: daemon::InProgressFilter) -> Self {
+        Self {
+            filter_id: filter.filter_id,
+            new_records: filter.new_records,
+            started_at: filter.started_at,
+        }
+    }
+}
+
+impl From<daemon::ProcessedFilter> for ProcessedFilter {
+    fn from(filter: daemon::ProcessedFilter) -> Self {
+        Self {
+            filter_id: filter.filter_id,
+            started_at: filter.started_at,
+            took: filter.took,
+            error: filter.error,
+        }
+    }
+}
+
+/// Queues course filters to have their points recalculated.
+#[tracing::instrument(skip(cx, session), fields(
+    session.id = %session.id(),
+    session.user.id = %session.user().id(),
+    session.user.permissions = ?session.user().permissions(),
+))]
+#[utoipa::path(
+    post,
+    path = "/points/recalculate",
+    tag = "Points",
+    request_body = RecalculatePointsRequest,
+    responses(
+        (status = 202, body = QueuedRecalculation),
+        (status = 401,),
+        (status = 404, description = "the filter or map does not exist"),
+        (status = 422, description = "invalid request body"),
+    ),
+)]
+async fn recalculate_points(
+    State(cx): State<Context>,
+    session: Session,
+    Json(request): Json<RecalculatePointsRequest>,
+) -> Result<Accepted<QueuedRecalculation>, ErrorResponse> {
+    let scope = match request {
+        RecalculatePointsRequest::Filter { filter_id } => RecalculationScope::Filter(filter_id),
+        RecalculatePointsRequest::Map { map: MapIdentifier::Id(map_id) } => {
+            RecalculationScope::Map(map_id)
+        },
+        RecalculatePointsRequest::Map { map: MapIdentifier::Name(ref map_name) } => {
+            let map = cs2kz::maps::get_by_name(&cx, map_name)
+                .try_next()
+                .await
+                .map_err(|err| ErrorResponse::internal_server_error(err))?
+                .ok_or_else(ErrorResponse::not_found)?;
+
+            RecalculationScope::Map(map.id)
+        },
+        RecalculatePointsRequest::Mode { mode } => RecalculationScope::Mode(mode),
+        RecalculatePointsRequest::All => RecalculationScope::All,
+    };
+
+    let queued = daemon::enqueue(&cx, scope)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?;
+
+    if queued == 0 && matches!(scope, RecalculationScope::Filter(_) | RecalculationScope::Map(_)) {
+        return Err(ErrorResponse::not_found());
+    }
+
+    Ok(Accepted(QueuedRecalculation { queued }))
+}
+
+/// Returns what the points daemon is currently doing.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/points/status",
+    tag = "Points",
+    responses(
+        (status = 200, body = PointsStatus),
+        (status = 401,),
+    ),
+)]
+async fn get_points_status(State(cx): State<Context>) -> Json<PointsStatus> {
+    Json(daemon::status(&cx).into())
+}
@@ -18,6 +18,11 @@ pub enum ProblemType {
     MapperDoesNotExist,
     InvalidCourseIndex,
     PlayerAlreadyBanned,
+    InvalidSubmissionState,
+    CannotVoteOnFilter,
+    MapApprovalInProgress,
+    CannotCarryOverRecords,
+    MapHasRecords,
     InvalidRequestBody,
 }
 
@@ -44,6 +49,11 @@ impl problem_details::ProblemType for ProblemType {
             Self::MapperDoesNotExist => uri!("mapper-does-not-exist"),
             Self::InvalidCourseIndex => uri!("invalid-course-index"),
             Self::PlayerAlreadyBanned => uri!("player-already-banned"),
+            Self::InvalidSubmissionState => uri!("invalid-submission-state"),
+            Self::CannotVoteOnFilter => uri!("cannot-vote-on-filter"),
+            Self::MapApprovalInProgress => uri!("map-approval-in-progress"),
+            Self::CannotCarryOverRecords => uri!("cannot-carry-over-records"),
+            Self::MapHasRecords => uri!("map-has-records"),
             Self::InvalidRequestBody => uri!("invalid-request-body"),
         }
     }
@@ -63,7 +73,12 @@ impl problem_details::ProblemType for ProblemType {
             | Self::MapMustHaveMappers
             | Self::MapperDoesNotExist
             | Self::InvalidCourseIndex
-            | Self::PlayerAlreadyBanned => http::StatusCode::CONFLICT,
+            | Self::PlayerAlreadyBanned
+            | Self::InvalidSubmissionState
+            | Self::CannotVoteOnFilter
+            | Self::MapApprovalInProgress
+            | Self::CannotCarryOverRecords
+            | Self::MapHasRecords => http::StatusCode::CONFLICT,
             Self::InvalidRequestBody => http::StatusCode::UNPROCESSABLE_ENTITY,
         }
     }
@@ -95,6 +110,15 @@ impl problem_details::ProblemType for ProblemType {
             Self::MapperDoesNotExist => write!(fmt, "mapper does not exist"),
             Self::InvalidCourseIndex => write!(fmt, "invalid index for course in map update"),
             Self::PlayerAlreadyBanned => write!(fmt, "player is already banned"),
+            Self::InvalidSubmissionState => {
+                write!(fmt, "map submission cannot be moved into the requested state")
+            },
+            Self::CannotVoteOnFilter => write!(fmt, "you are not allowed to vote on this filter"),
+            Self::MapApprovalInProgress => {
+                write!(fmt, "this workshop item is already being processed")
+            },
+            Self::CannotCarryOverRecords => write!(fmt, "records cannot be carried over"),
+            Self::MapHasRecords => write!(fmt, "map has records"),
             Self::InvalidRequestBody => write!(fmt, "failed to parse request body"),
         }
     }
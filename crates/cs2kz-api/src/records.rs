@@ -3,28 +3,39 @@ use std::num::NonZero;
 use axum::extract::{FromRef, State};
 use axum::routing::{self, Router};
 use cs2kz::Context;
+use cs2kz::maps::courses::filters::Tier;
 use cs2kz::mode::Mode;
 use cs2kz::pagination::{Limit, Offset, Paginated};
-use cs2kz::records::RecordId;
+use cs2kz::records::{GetRecordsError, GetRecordsParams, RecordId, RecordsCursor};
 use cs2kz::styles::Styles;
 use cs2kz::time::{Seconds, Timestamp};
 use futures_util::TryStreamExt;
+use futures_util::stream::{self, Stream, StreamExt};
 
+use crate::export::{self, CsvRow, Export, ExportFormat, ToCsv};
 use crate::extract::{Json, Path, Query};
 use crate::maps::{CourseInfo, MapIdentifier, MapInfo};
+use crate::middleware::per_client_limit::{PerClientLimit, per_client_limit};
 use crate::players::{PlayerIdentifier, PlayerInfo};
+use crate::plugin::PluginVersionIdentifier;
 use crate::replays::ReplayFile;
 use crate::response::ErrorResponse;
 use crate::servers::{ServerIdentifier, ServerInfo};
 
-pub fn router<S>() -> Router<S>
+pub fn router<S>(export_limit: PerClientLimit) -> Router<S>
 where
     S: Clone + Send + Sync + 'static,
     Context: FromRef<S>,
 {
     Router::new()
         .route("/", routing::get(get_records))
+        .route(
+            "/export",
+            routing::get(export_records)
+                .layer(axum::middleware::from_fn_with_state(export_limit, per_client_limit)),
+        )
         .route("/{record_id}", routing::get(get_record))
+        .route("/{record_id}/points", routing::get(get_record_points))
         .route("/{record_id}/replay", routing::get(get_record_replay))
 }
 
@@ -91,9 +102,34 @@ pub struct GetRecordsQuery {
     #[param(value_type = Option<u32>, minimum = 1)]
     max_rank: Option<NonZero<u32>>,
 
+    /// Only include records which have (at least) these styles enabled.
+    #[serde(default)]
+    #[param(value_type = crate::openapi::shims::Styles)]
+    styles: Styles,
+
+    /// Only include records submitted after this point in time.
+    #[param(value_type = Option<crate::openapi::shims::Timestamp>)]
+    submitted_after: Option<Timestamp>,
+
+    /// Only include records submitted before this point in time.
+    #[param(value_type = Option<crate::openapi::shims::Timestamp>)]
+    submitted_before: Option<Timestamp>,
+
+    /// Only include records submitted on this version of cs2kz-metamod.
+    #[param(value_type = Option<PluginVersionIdentifier>)]
+    plugin_version: Option<PluginVersionIdentifier>,
+
+    /// The minimum amount of points any record should have.
+    ///
+    /// Records qualify if either their NUB or PRO points reach this value.
+    #[param(minimum = 0.0)]
+    min_points: Option<f64>,
+
     /// Which value to sort the results by.
     ///
     /// Defaults to 'submission-date'.
+    /// Records which are not on the relevant leaderboard will always be returned last when
+    /// sorting by points or rank.
     #[serde(default)]
     #[param(value_type = Option<crate::openapi::shims::Records_SortBy>)]
     sort_by: cs2kz::records::SortBy,
@@ -103,6 +139,13 @@ pub struct GetRecordsQuery {
     #[param(value_type = Option<crate::openapi::shims::Records_SortOrder>)]
     sort_order: Option<cs2kz::records::SortOrder>,
 
+    /// The `next_cursor` of a previous response.
+    ///
+    /// Cursors are tied to the `sort_by` value they were created with, and cannot be combined
+    /// with `offset`.
+    #[param(value_type = Option<crate::openapi::shims::Cursor>)]
+    cursor: Option<RecordsCursor>,
+
     #[serde(default)]
     #[param(value_type = crate::openapi::shims::Limit)]
     limit: Limit<1000, 100>,
@@ -126,93 +169,23 @@ pub struct GetRecordsQuery {
 )]
 async fn get_records(
     State(cx): State<Context>,
-    Query(GetRecordsQuery {
-        top,
-        player,
-        server,
-        map,
-        course,
-        mode,
-        has_teleports,
-        max_rank,
-        sort_by,
-        sort_order,
-        limit,
-        offset,
-    }): Query<GetRecordsQuery>,
+    Query(query): Query<GetRecordsQuery>,
 ) -> Result<Json<Paginated<Vec<Record>>>, ErrorResponse> {
-    let player_id = match player {
-        None => None,
-        Some(PlayerIdentifier::Id(id)) => Some(id),
-        Some(PlayerIdentifier::Name(ref name)) => {
-            match cs2kz::players::get_by_name(&cx, name).await {
-                Ok(Some(player)) => Some(player.id),
-                Ok(None) => return Ok(Json(Paginated::new(0, Vec::new()))),
-                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
-            }
-        },
-    };
-
-    let server_id = match server {
-        None => None,
-        Some(ServerIdentifier::Id(id)) => Some(id),
-        Some(ServerIdentifier::Name(ref name)) => {
-            match cs2kz::servers::get_by_name(&cx, name).await {
-                Ok(Some(server)) => Some(server.id),
-                Ok(None) => return Ok(Json(Paginated::new(0, Vec::new()))),
-                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
-            }
-        },
-    };
-
-    let (map_id, map) = match map {
-        None => (None, None),
-        Some(MapIdentifier::Id(map_id)) => (Some(map_id), None),
-        Some(MapIdentifier::Name(ref map_name)) => {
-            match cs2kz::maps::get_by_name(&cx, map_name).try_next().await {
-                Ok(Some(map)) => (Some(map.id), Some(map)),
-                Ok(None) => return Ok(Json(Paginated::new(0, Vec::new()))),
-                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
-            }
-        },
-    };
+    if query.cursor.is_some() && query.offset.value() != 0 {
+        return Err(ErrorResponse::cursor_with_offset());
+    }
 
-    let course_id = match course {
-        None => None,
-        Some(ref course) => match (map_id, map) {
-            (_, Some(ref map)) => match map.find_course_by_name(course).map(|course| course.id) {
-                Some(course_id) => Some(course_id),
-                None => return Ok(Json(Paginated::new(0, Vec::new()))),
-            },
-            (Some(map_id), None) => match cs2kz::maps::get_by_id(&cx, map_id).await {
-                Ok(Some(map)) => match map.find_course_by_name(course).map(|course| course.id) {
-                    Some(course_id) => Some(course_id),
-                    None => return Ok(Json(Paginated::new(0, Vec::new()))),
-                },
-                Ok(None) => return Ok(Json(Paginated::new(0, Vec::new()))),
-                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
-            },
-            (None, None) => match cs2kz::maps::get_course_id_by_name(&cx, course).await {
-                Ok(Some(course_id)) => Some(course_id),
-                Ok(None) => return Ok(Json(Paginated::new(0, Vec::new()))),
-                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
-            },
-        },
-    };
+    if query
+        .cursor
+        .is_some_and(|cursor| cursor.sort_key().sort_by() != query.sort_by)
+    {
+        return Err(ErrorResponse::invalid_query_string(|details| {
+            details.set_detail("`cursor` was created with a different `sort_by` value");
+        }));
+    }
 
-    let params = cs2kz::records::GetRecordsParams {
-        top,
-        player_id,
-        server_id,
-        map_id,
-        course_id,
-        mode,
-        has_teleports,
-        max_rank,
-        sort_by,
-        sort_order,
-        limit,
-        offset,
+    let Some(params) = resolve_params(&cx, query).await? else {
+        return Ok(Json(Paginated::new(0, Vec::new())));
     };
 
     let records = cs2kz::records::get(&cx, params)
@@ -223,6 +196,42 @@ async fn get_records(
     Ok(Json(records))
 }
 
+/// Exports all records matching the given filters.
+///
+/// This accepts the same filters and sorting as `/records`, but ignores `cursor`, `limit` and
+/// `offset`. The response is streamed as either NDJSON or CSV, depending on the `Accept` header.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/records/export",
+    tag = "Records",
+    params(GetRecordsQuery),
+    responses(
+        (status = 200, content(
+            (Record = "application/x-ndjson"),
+            (String = "text/csv"),
+        )),
+        (status = 400, description = "invalid query parameters"),
+        (status = 406, description = "none of the supported formats are acceptable"),
+        (status = 429, description = "too many concurrent exports"),
+    ),
+)]
+async fn export_records(
+    format: ExportFormat,
+    State(cx): State<Context>,
+    Query(query): Query<GetRecordsQuery>,
+) -> Result<Export<impl Stream<Item = Result<Record, GetRecordsError>>>, ErrorResponse> {
+    let params = resolve_params(&cx, query).await?;
+    let records = export::spawn_stream(&cx, move |cx| match params {
+        Some(params) => cs2kz::records::export(cx, params)
+            .map_ok(Record::from)
+            .boxed(),
+        None => stream::empty().boxed(),
+    });
+
+    Ok(Export::new(format, "records", records))
+}
+
 /// Returns the record with the specified ID.
 #[tracing::instrument(skip(cx))]
 #[utoipa::path(
@@ -248,6 +257,114 @@ async fn get_record(
     Ok(Json(record.into()))
 }
 
+/// How a record's points came together.
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct PointsBreakdown {
+    /// Whether the record's course filter is ranked.
+    ///
+    /// Records on unranked filters are not awarded any points, so `nub` and `pro` will always be
+    /// `null`.
+    is_ranked: bool,
+
+    /// Points on the NUB leaderboard.
+    ///
+    /// This is `null` if the record is not the player's best NUB record on its course.
+    nub: Option<LeaderboardPoints>,
+
+    /// Points on the PRO leaderboard.
+    ///
+    /// This is `null` if the record is not the player's best PRO record on its course.
+    pro: Option<LeaderboardPoints>,
+}
+
+#[derive(Debug, serde::Serialize, utoipa::ToSchema)]
+pub struct LeaderboardPoints {
+    #[schema(value_type = crate::openapi::shims::CourseFilterTier)]
+    tier: Tier,
+
+    rank: u32,
+    leaderboard_size: u32,
+
+    /// The total amount of points.
+    ///
+    /// This is the sum of `tier_points`, `rank_points` and `distribution_points`.
+    points: f64,
+
+    /// Points awarded for the course's difficulty.
+    tier_points: f64,
+
+    /// Points awarded for the record's rank on the leaderboard.
+    rank_points: f64,
+
+    /// Points awarded for the record's performance relative to the rest of the leaderboard.
+    distribution_points: f64,
+
+    /// The share of the maximum distribution points the record achieved (`0..=1`).
+    distribution_share: f64,
+
+    /// Whether the leaderboard was too small to fit a distribution, in which case
+    /// `distribution_share` is based only on the record's time relative to the top time.
+    small_leaderboard: bool,
+
+    /// Whether `distribution_share` is based on the PRO leaderboard.
+    ///
+    /// PRO records are awarded whichever is higher: points based on the PRO leaderboard, or
+    /// points based on the NUB leaderboard. This is always `false` for NUB records.
+    based_on_pro_leaderboard: bool,
+}
+
+impl From<cs2kz::records::PointsBreakdown> for PointsBreakdown {
+    fn from(breakdown: cs2kz::records::PointsBreakdown) -> Self {
+        Self {
+            is_ranked: breakdown.is_ranked,
+            nub: breakdown.nub.map(Into::into),
+            pro: breakdown.pro.map(Into::into),
+        }
+    }
+}
+
+impl From<cs2kz::records::LeaderboardPoints> for LeaderboardPoints {
+    fn from(points: cs2kz::records::LeaderboardPoints) -> Self {
+        Self {
+            tier: points.tier,
+            rank: points.rank,
+            leaderboard_size: points.leaderboard_size,
+            points: points.breakdown.total(),
+            tier_points: points.breakdown.for_tier,
+            rank_points: points.breakdown.for_rank,
+            distribution_points: points.breakdown.from_dist,
+            distribution_share: points.dist_points,
+            small_leaderboard: points.small_leaderboard,
+            based_on_pro_leaderboard: points.based_on_pro_leaderboard,
+        }
+    }
+}
+
+/// Returns a breakdown of how a record's points were calculated.
+#[tracing::instrument(skip(cx))]
+#[utoipa::path(
+    get,
+    path = "/records/{record_id}/points",
+    tag = "Records",
+    params(("record_id" = u32, Path)),
+    responses(
+        (status = 200, body = PointsBreakdown),
+        (status = 400, description = "invalid path parameters"),
+        (status = 404,),
+    ),
+)]
+async fn get_record_points(
+    State(cx): State<Context>,
+    Path(record_id): Path<RecordId>,
+) -> Result<Json<PointsBreakdown>, ErrorResponse> {
+    let breakdown = cs2kz::records::get_points_breakdown(&cx, record_id)
+        .await
+        .map_err(|err| ErrorResponse::internal_server_error(err))?
+        .ok_or_else(ErrorResponse::not_found)?;
+
+    Ok(Json(breakdown.into()))
+}
+
 /// Returns the replay file for a specific record.
 #[tracing::instrument(skip(cx))]
 #[utoipa::path(
@@ -293,3 +410,179 @@ impl From<cs2kz::records::Record> for Record {
         }
     }
 }
+
+impl ToCsv for Record {
+    const HEADER: &'static [&'static str] = &[
+        "id",
+        "player_id",
+        "player_name",
+        "server_id",
+        "server_name",
+        "map_id",
+        "map_name",
+        "course_id",
+        "course_name",
+        "mode",
+        "styles",
+        "teleports",
+        "time",
+        "nub_rank",
+        "nub_points",
+        "pro_rank",
+        "pro_points",
+        "submitted_at",
+    ];
+
+    fn write_row(&self, row: &mut CsvRow<'_>) {
+        row.field(self.id)
+            .field(self.player.id)
+            .field(&self.player.name)
+            .field(self.server.id)
+            .field(&self.server.name)
+            .field(self.map.id)
+            .field(&self.map.name)
+            .field(self.course.id)
+            .field(&self.course.name)
+            .field(self.mode.as_str())
+            .field(
+                self.styles
+                    .iter()
+                    .map(|style| style.as_str())
+                    .collect::<Vec<_>>()
+                    .join("|"),
+            )
+            .field(self.teleports)
+            .field(self.time.0.as_secs_f64())
+            .opt_field(self.nub_rank)
+            .opt_field(self.nub_points)
+            .opt_field(self.pro_rank)
+            .opt_field(self.pro_points)
+            .field(self.submitted_at);
+    }
+}
+
+/// Resolves the identifiers in a records query to IDs.
+///
+/// Returns [`None`] if any of the identifiers do not exist, in which case there cannot be any
+/// matching records.
+async fn resolve_params(
+    cx: &Context,
+    GetRecordsQuery {
+        top,
+        player,
+        server,
+        map,
+        course,
+        mode,
+        has_teleports,
+        max_rank,
+        styles,
+        submitted_after,
+        submitted_before,
+        plugin_version,
+        min_points,
+        sort_by,
+        sort_order,
+        cursor,
+        limit,
+        offset,
+    }: GetRecordsQuery,
+) -> Result<Option<GetRecordsParams>, ErrorResponse> {
+    let player_id = match player {
+        None => None,
+        Some(PlayerIdentifier::Id(id)) => Some(id),
+        Some(PlayerIdentifier::Name(ref name)) => {
+            match cs2kz::players::get_by_name(cx, name).await {
+                Ok(Some(player)) => Some(player.id),
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            }
+        },
+    };
+
+    let server_id = match server {
+        None => None,
+        Some(ServerIdentifier::Id(id)) => Some(id),
+        Some(ServerIdentifier::Name(ref name)) => {
+            match cs2kz::servers::get_by_name(cx, name).await {
+                Ok(Some(server)) => Some(server.id),
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            }
+        },
+    };
+
+    let (map_id, map) = match map {
+        None => (None, None),
+        Some(MapIdentifier::Id(map_id)) => (Some(map_id), None),
+        Some(MapIdentifier::Name(ref map_name)) => {
+            match cs2kz::maps::get_by_name(cx, map_name).try_next().await {
+                Ok(Some(map)) => (Some(map.id), Some(map)),
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            }
+        },
+    };
+
+    let course_id = match course {
+        None => None,
+        Some(ref course) => match (map_id, map) {
+            (_, Some(ref map)) => match map.find_course_by_name(course).map(|course| course.id) {
+                Some(course_id) => Some(course_id),
+                None => return Ok(None),
+            },
+            (Some(map_id), None) => match cs2kz::maps::get_by_id(cx, map_id).await {
+                Ok(Some(map)) => match map.find_course_by_name(course).map(|course| course.id) {
+                    Some(course_id) => Some(course_id),
+                    None => return Ok(None),
+                },
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            },
+            (None, None) => match cs2kz::maps::get_course_id_by_name(cx, course).await {
+                Ok(Some(course_id)) => Some(course_id),
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            },
+        },
+    };
+
+    let plugin_version_id = match plugin_version {
+        None => None,
+        Some(PluginVersionIdentifier::SemVer(ref version)) => {
+            match cs2kz::plugin::get_version(cx, version).await {
+                Ok(Some(plugin_version)) => Some(plugin_version.id),
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            }
+        },
+        Some(PluginVersionIdentifier::GitRevision(ref git_revision)) => {
+            match cs2kz::plugin::get_version_by_git_revision(cx, git_revision).await {
+                Ok(Some(plugin_version)) => Some(plugin_version.id),
+                Ok(None) => return Ok(None),
+                Err(error) => return Err(ErrorResponse::internal_server_error(error)),
+            }
+        },
+    };
+
+    Ok(Some(GetRecordsParams {
+        top,
+        player_id,
+        server_id,
+        map_id,
+        course_id,
+        mode,
+        has_teleports,
+        max_rank,
+        styles: (styles.count() > 0).then_some(styles),
+        submitted_after,
+        submitted_before,
+        plugin_version_id,
+        min_points,
+        sort_by,
+        sort_order,
+        cursor,
+        limit,
+        offset,
+    }))
+}
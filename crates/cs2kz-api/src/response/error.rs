@@ -1,8 +1,13 @@
 use std::panic::Location;
 
 use axum::response::{IntoResponse, Response};
+use cs2kz::maps::carry_over::CarryOverError;
+use cs2kz::maps::courses::filters::votes::CastVoteError;
+use cs2kz::maps::submissions::SubmissionState;
 use headers::Header;
 
+use crate::maps::deletion::CascadeEffects;
+use crate::maps::jobs::JobId;
 use crate::problem_details::{ProblemDetails, ProblemType};
 use crate::steam;
 
@@ -14,6 +19,8 @@ pub struct ErrorResponse(ErrorKind);
 enum ErrorKind {
     Unauthorized,
     NotFound,
+    NotAcceptable,
+    TooManyRequests,
     FailedToBufferBody,
     InternalServerError,
     BadGateway,
@@ -35,6 +42,14 @@ impl ErrorResponse {
         Self(ErrorKind::NotFound)
     }
 
+    pub(crate) fn not_acceptable() -> Self {
+        Self(ErrorKind::NotAcceptable)
+    }
+
+    pub(crate) fn too_many_requests() -> Self {
+        Self(ErrorKind::TooManyRequests)
+    }
+
     pub(crate) fn failed_to_buffer_body() -> Self {
         Self(ErrorKind::FailedToBufferBody)
     }
@@ -85,6 +100,12 @@ impl ErrorResponse {
         Self::detailed(problem_details(ProblemType::InvalidQueryString, modify))
     }
 
+    pub(crate) fn cursor_with_offset() -> Self {
+        Self::invalid_query_string(|details| {
+            details.set_detail("`cursor` cannot be combined with `offset`");
+        })
+    }
+
     pub(crate) fn plugin_version_already_exists() -> Self {
         Self::detailed(problem_details(ProblemType::PluginVersionAlreadyExists, |_| {}))
     }
@@ -132,6 +153,43 @@ impl ErrorResponse {
     pub(crate) fn player_already_banned() -> Self {
         Self::detailed(problem_details(ProblemType::PlayerAlreadyBanned, |_| {}))
     }
+
+    pub(crate) fn invalid_submission_state(from: SubmissionState, to: SubmissionState) -> Self {
+        Self::detailed(problem_details(ProblemType::InvalidSubmissionState, |details| {
+            details.set_detail(format!("cannot move submission from {from} to {to}"));
+        }))
+    }
+
+    pub(crate) fn cannot_vote_on_filter(error: CastVoteError) -> Self {
+        Self::detailed(problem_details(ProblemType::CannotVoteOnFilter, |details| {
+            details.set_detail(error.to_string());
+        }))
+    }
+
+    /// `job_id` is [`None`] if the workshop item is being checked for updates rather than being
+    /// approved.
+    pub(crate) fn map_approval_in_progress(job_id: Option<JobId>) -> Self {
+        Self::detailed(problem_details(
+            ProblemType::MapApprovalInProgress,
+            |details| match job_id {
+                Some(job_id) => details.add_extension("job_id", &job_id),
+                None => details.set_detail("the map is being checked for workshop updates"),
+            },
+        ))
+    }
+
+    pub(crate) fn cannot_carry_over_records(error: CarryOverError) -> Self {
+        Self::detailed(problem_details(ProblemType::CannotCarryOverRecords, |details| {
+            details.set_detail(error.to_string());
+        }))
+    }
+
+    pub(crate) fn map_has_records(cascade_effects: &CascadeEffects) -> Self {
+        Self::detailed(problem_details(ProblemType::MapHasRecords, |details| {
+            details.set_detail("deleting the map requires `force`");
+            details.add_extension("cascade_effects", cascade_effects);
+        }))
+    }
 }
 
 fn problem_details(
@@ -170,11 +228,25 @@ impl From<steam::ApiError> for ErrorResponse {
     }
 }
 
+impl From<steam::downloader::LookupError> for ErrorResponse {
+    #[track_caller]
+    fn from(error: steam::downloader::LookupError) -> Self {
+        match error {
+            steam::downloader::LookupError::Steam(error) => Self::from(error),
+            steam::downloader::LookupError::ReadMetadata(error) => {
+                Self::internal_server_error(error)
+            },
+        }
+    }
+}
+
 impl IntoResponse for ErrorResponse {
     fn into_response(self) -> Response {
         match self.0 {
             ErrorKind::Unauthorized => http::StatusCode::UNAUTHORIZED.into_response(),
             ErrorKind::NotFound => http::StatusCode::NOT_FOUND.into_response(),
+            ErrorKind::NotAcceptable => http::StatusCode::NOT_ACCEPTABLE.into_response(),
+            ErrorKind::TooManyRequests => http::StatusCode::TOO_MANY_REQUESTS.into_response(),
             ErrorKind::FailedToBufferBody => http::StatusCode::PAYLOAD_TOO_LARGE.into_response(),
             ErrorKind::InternalServerError => {
                 http::StatusCode::INTERNAL_SERVER_ERROR.into_response()
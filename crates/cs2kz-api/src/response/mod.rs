@@ -22,3 +22,17 @@ where
         (http::StatusCode::CREATED, Json(self.0)).into_response()
     }
 }
+
+#[derive(Debug)]
+pub struct Accepted<T>(pub T)
+where
+    Json<T>: IntoResponse;
+
+impl<T> IntoResponse for Accepted<T>
+where
+    Json<T>: IntoResponse,
+{
+    fn into_response(self) -> Response {
+        (http::StatusCode::ACCEPTED, Json(self.0)).into_response()
+    }
+}
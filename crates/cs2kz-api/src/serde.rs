@@ -1,4 +1,6 @@
-use cs2kz::time::Timestamp;
+use std::time::Duration;
+
+use cs2kz::time::{Seconds, Timestamp};
 use serde::de::{self, Deserialize, Deserializer};
 
 /// Deserializes a `T` and ensures it isn't empty.
@@ -45,6 +47,22 @@ where
     })
 }
 
+/// Deserializes a strictly positive amount of [`Seconds`].
+pub fn deserialize_positive_seconds<'de, D>(deserializer: D) -> Result<Seconds, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let secs = f64::deserialize(deserializer)?;
+
+    if !secs.is_finite() || secs <= 0.0 {
+        return Err(de::Error::invalid_value(de::Unexpected::Float(secs), &"a positive duration"));
+    }
+
+    Duration::try_from_secs_f64(secs)
+        .map(Seconds)
+        .map_err(|_| de::Error::invalid_value(de::Unexpected::Float(secs), &"a valid duration"))
+}
+
 #[allow(private_bounds, reason = "implementation detail of `deserialize_non_empty`")]
 trait IsEmpty {
     fn is_empty(&self) -> bool;
@@ -0,0 +1,282 @@
+//! Acquiring the `.vpk` files of workshop maps.
+//!
+//! Approving a map requires its checksum, which means we need the actual map file. In production
+//! we download it using [`DepotDownloader`], but that is neither possible in air-gapped
+//! environments nor desirable in tests, so there are alternative [`MapDownloader`]
+//! implementations. [`Downloader`] picks one based on [`DepotDownloaderConfig::backend`].
+//!
+//! The same goes for the metadata we need alongside the map file: the map's name and the names of
+//! its mappers. Only [`DepotDownloader`] asks the Steam Web API for those.
+
+use std::collections::HashMap;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use cs2kz::maps::MapChecksum;
+use cs2kz::steam::WorkshopId;
+use steam_id::SteamId;
+
+use crate::config::{DepotDownloaderConfig, MapDownloaderBackend, SteamAuthConfig};
+use crate::steam;
+
+/// Something that can produce the `.vpk` files of workshop maps.
+pub trait MapDownloader: Send + Sync + 'static {
+    /// Looks up the name of a workshop map.
+    ///
+    /// Returns [`None`] if the workshop item does not exist.
+    async fn fetch_map_name(&self, workshop_id: WorkshopId) -> Result<Option<String>, LookupError>;
+
+    /// Looks up the name of a Steam user, so mappers can be created before they ever joined a
+    /// server.
+    ///
+    /// Returns [`None`] if the user does not exist.
+    async fn fetch_user_name(&self, steam_id: SteamId) -> Result<Option<String>, LookupError>;
+
+    /// Acquires the map's `.vpk` file.
+    async fn download(&self, workshop_id: WorkshopId) -> io::Result<()>;
+
+    /// Computes the checksum of a map [downloaded] earlier.
+    ///
+    /// [downloaded]: MapDownloader::download()
+    async fn compute_checksum(&self, workshop_id: WorkshopId) -> io::Result<MapChecksum>;
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum LookupError {
+    #[display("{_0}")]
+    Steam(steam::ApiError),
+
+    #[display("failed to read map metadata")]
+    ReadMetadata(io::Error),
+}
+
+/// The [`MapDownloader`] selected in the configuration.
+#[derive(Debug)]
+pub enum Downloader {
+    Depot(DepotDownloader),
+    Local(LocalDirectory),
+    Fake(FakeDownloader),
+}
+
+/// Downloads maps from the Steam workshop by spawning a `DepotDownloader` process.
+///
+/// Metadata is fetched from the Steam Web API.
+#[derive(Debug)]
+pub struct DepotDownloader {
+    exe_path: PathBuf,
+    out_dir: PathBuf,
+    http_client: reqwest::Client,
+
+    #[debug(skip)]
+    web_api_key: String,
+}
+
+/// Reads pre-downloaded maps from `{directory}/{workshop_id}.vpk`.
+///
+/// Map names are read from `{directory}/{workshop_id}.name`. Steam users cannot be looked up
+/// offline, so every user is assumed to exist and is named after their SteamID.
+#[derive(Debug)]
+pub struct LocalDirectory {
+    directory: PathBuf,
+}
+
+/// Serves maps from memory.
+///
+/// Maps that were not [inserted] are treated as if their `.vpk` file consisted of their workshop
+/// ID, so every workshop item "exists" and has a stable checksum. Every map is named after its
+/// workshop ID, and every user after their SteamID.
+///
+/// [inserted]: FakeDownloader::insert()
+#[derive(Debug, Default)]
+pub struct FakeDownloader {
+    maps: HashMap<WorkshopId, Vec<u8>>,
+}
+
+impl Downloader {
+    pub fn from_config(
+        config: &DepotDownloaderConfig,
+        steam_auth_config: &SteamAuthConfig,
+    ) -> Self {
+        match config.backend {
+            MapDownloaderBackend::DepotDownloader => Self::Depot(DepotDownloader {
+                exe_path: config.exe_path.clone(),
+                out_dir: config.out_dir.clone(),
+                http_client: reqwest::Client::new(),
+                web_api_key: steam_auth_config.web_api_key.clone(),
+            }),
+            MapDownloaderBackend::Local => {
+                Self::Local(LocalDirectory { directory: config.out_dir.clone() })
+            },
+            MapDownloaderBackend::Fake => Self::Fake(FakeDownloader::default()),
+        }
+    }
+}
+
+impl MapDownloader for Downloader {
+    async fn fetch_map_name(&self, workshop_id: WorkshopId) -> Result<Option<String>, LookupError> {
+        match *self {
+            Self::Depot(ref downloader) => downloader.fetch_map_name(workshop_id).await,
+            Self::Local(ref downloader) => downloader.fetch_map_name(workshop_id).await,
+            Self::Fake(ref downloader) => downloader.fetch_map_name(workshop_id).await,
+        }
+    }
+
+    async fn fetch_user_name(&self, steam_id: SteamId) -> Result<Option<String>, LookupError> {
+        match *self {
+            Self::Depot(ref downloader) => downloader.fetch_user_name(steam_id).await,
+            Self::Local(ref downloader) => downloader.fetch_user_name(steam_id).await,
+            Self::Fake(ref downloader) => downloader.fetch_user_name(steam_id).await,
+        }
+    }
+
+    async fn download(&self, workshop_id: WorkshopId) -> io::Result<()> {
+        match *self {
+            Self::Depot(ref downloader) => downloader.download(workshop_id).await,
+            Self::Local(ref downloader) => downloader.download(workshop_id).await,
+            Self::Fake(ref downloader) => downloader.download(workshop_id).await,
+        }
+    }
+
+    async fn compute_checksum(&self, workshop_id: WorkshopId) -> io::Result<MapChecksum> {
+        match *self {
+            Self::Depot(ref downloader) => downloader.compute_checksum(workshop_id).await,
+            Self::Local(ref downloader) => downloader.compute_checksum(workshop_id).await,
+            Self::Fake(ref downloader) => downloader.compute_checksum(workshop_id).await,
+        }
+    }
+}
+
+impl MapDownloader for DepotDownloader {
+    async fn fetch_map_name(&self, workshop_id: WorkshopId) -> Result<Option<String>, LookupError> {
+        steam::fetch_map_name(&self.http_client, workshop_id)
+            .await
+            .map_err(LookupError::Steam)
+    }
+
+    async fn fetch_user_name(&self, steam_id: SteamId) -> Result<Option<String>, LookupError> {
+        steam::fetch_user(&self.http_client, &self.web_api_key, steam_id)
+            .await
+            .map(|user| user.map(|user| user.name))
+            .map_err(LookupError::Steam)
+    }
+
+    async fn download(&self, workshop_id: WorkshopId) -> io::Result<()> {
+        steam::download_map(workshop_id, &self.exe_path, &self.out_dir).await
+    }
+
+    async fn compute_checksum(&self, workshop_id: WorkshopId) -> io::Result<MapChecksum> {
+        steam::maps::compute_checksum(vpk_path(&self.out_dir, workshop_id)).await
+    }
+}
+
+impl MapDownloader for LocalDirectory {
+    async fn fetch_map_name(&self, workshop_id: WorkshopId) -> Result<Option<String>, LookupError> {
+        let path = self.directory.join(format!("{workshop_id}.name"));
+
+        match tokio::fs::read_to_string(path).await {
+            Ok(name) => Ok(Some(name.trim().to_owned())),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(error) => Err(LookupError::ReadMetadata(error)),
+        }
+    }
+
+    async fn fetch_user_name(&self, steam_id: SteamId) -> Result<Option<String>, LookupError> {
+        Ok(Some(steam_id.to_string()))
+    }
+
+    /// Pre-downloaded maps are already there, so this only checks that the file exists.
+    async fn download(&self, workshop_id: WorkshopId) -> io::Result<()> {
+        tokio::fs::metadata(vpk_path(&self.directory, workshop_id))
+            .await
+            .map(drop)
+    }
+
+    async fn compute_checksum(&self, workshop_id: WorkshopId) -> io::Result<MapChecksum> {
+        steam::maps::compute_checksum(vpk_path(&self.directory, workshop_id)).await
+    }
+}
+
+impl FakeDownloader {
+    /// Makes `bytes` the contents of the map's `.vpk` file.
+    #[cfg_attr(not(test), expect(dead_code, reason = "only tests need specific map contents"))]
+    pub fn insert(&mut self, workshop_id: WorkshopId, bytes: impl Into<Vec<u8>>) {
+        self.maps.insert(workshop_id, bytes.into());
+    }
+}
+
+impl MapDownloader for FakeDownloader {
+    async fn fetch_map_name(&self, workshop_id: WorkshopId) -> Result<Option<String>, LookupError> {
+        Ok(Some(format!("kz_{workshop_id}")))
+    }
+
+    async fn fetch_user_name(&self, steam_id: SteamId) -> Result<Option<String>, LookupError> {
+        Ok(Some(steam_id.to_string()))
+    }
+
+    async fn download(&self, _: WorkshopId) -> io::Result<()> {
+        Ok(())
+    }
+
+    async fn compute_checksum(&self, workshop_id: WorkshopId) -> io::Result<MapChecksum> {
+        Ok(match self.maps.get(&workshop_id) {
+            Some(bytes) => MapChecksum::from_bytes(bytes),
+            None => MapChecksum::from_bytes(&workshop_id.into_inner().to_le_bytes()),
+        })
+    }
+}
+
+fn vpk_path(directory: &Path, workshop_id: WorkshopId) -> PathBuf {
+    directory.join(format!("{workshop_id}.vpk"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn fake_downloader_serves_inserted_maps() -> io::Result<()> {
+        let mut downloader = FakeDownloader::default();
+        let workshop_id = WorkshopId::from_inner(3070194623_u32);
+
+        let default_checksum = downloader.compute_checksum(workshop_id).await?;
+        assert_eq!(downloader.compute_checksum(workshop_id).await?, default_checksum);
+
+        downloader.insert(workshop_id, b"kz_grotto".as_slice());
+        assert_eq!(downloader.compute_checksum(workshop_id).await?, MapChecksum::from_bytes(b"kz_grotto"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn local_directory_reads_vpk_files() -> io::Result<()> {
+        let directory = std::env::temp_dir().join(format!("cs2kz-api-test-{}", std::process::id()));
+        let downloader = LocalDirectory { directory: directory.clone() };
+        let workshop_id = WorkshopId::from_inner(3070194623_u32);
+
+        fs::create_dir_all(&directory)?;
+        fs::write(vpk_path(&directory, workshop_id), b"kz_grotto")?;
+        fs::write(directory.join(format!("{workshop_id}.name")), "kz_grotto\n")?;
+
+        let checksum = async {
+            downloader.download(workshop_id).await?;
+            downloader.compute_checksum(workshop_id).await
+        }
+        .await;
+        let name = downloader.fetch_map_name(workshop_id).await;
+        let missing = downloader.download(WorkshopId::from_inner(1_u32)).await;
+        let missing_name = downloader
+            .fetch_map_name(WorkshopId::from_inner(1_u32))
+            .await;
+
+        fs::remove_dir_all(&directory)?;
+
+        assert_eq!(checksum?, MapChecksum::from_bytes(b"kz_grotto"));
+        assert_eq!(name.ok().flatten().as_deref(), Some("kz_grotto"));
+        assert_eq!(missing.map_err(|err| err.kind()), Err(io::ErrorKind::NotFound));
+        assert!(matches!(missing_name, Ok(None)));
+
+        Ok(())
+    }
+}
@@ -6,8 +6,10 @@ use std::time::Duration;
 
 use cs2kz::maps::MapChecksum;
 use cs2kz::steam::WorkshopId;
+use cs2kz::time::Timestamp;
 use futures_util::stream::{self, StreamExt};
 use serde::ser::{Serialize, SerializeMap, Serializer};
+use time::OffsetDateTime;
 use tokio::process::Command;
 use tokio::task;
 use tokio_util::codec::{FramedRead, LinesCodec};
@@ -51,6 +53,115 @@ pub async fn fetch_map_name(
         })
 }
 
+/// A workshop item, as returned by [`fetch_map_details()`].
+#[derive(Debug)]
+pub struct MapDetails {
+    pub workshop_id: WorkshopId,
+    pub name: String,
+
+    /// The item's preview image.
+    pub preview_url: Option<String>,
+
+    /// The size of the item's files, in bytes.
+    pub file_size: Option<u64>,
+
+    pub tags: Vec<String>,
+
+    /// When the item was created.
+    pub created_at: Option<Timestamp>,
+
+    /// When the item was last updated.
+    pub updated_at: Timestamp,
+}
+
+/// Fetches details about multiple workshop items at once.
+///
+/// Items that do not exist (anymore) are omitted from the result.
+#[tracing::instrument(skip(http_client), err(level = "debug"))]
+pub async fn fetch_map_details(
+    http_client: &reqwest::Client,
+    workshop_ids: &[WorkshopId],
+) -> Result<Vec<MapDetails>, steam::ApiError> {
+    struct Form<'a> {
+        workshop_ids: &'a [WorkshopId],
+    }
+
+    impl Serialize for Form<'_> {
+        fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+        where
+            S: Serializer,
+        {
+            let mut serializer = serializer.serialize_map(Some(self.workshop_ids.len() + 1))?;
+            serializer.serialize_entry("itemcount", &self.workshop_ids.len())?;
+
+            for (idx, workshop_id) in self.workshop_ids.iter().enumerate() {
+                serializer
+                    .serialize_entry(&format_args!("publishedfileids[{idx}]"), workshop_id)?;
+            }
+
+            serializer.end()
+        }
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Response {
+        publishedfiledetails: Vec<Details>,
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Details {
+        publishedfileid: String,
+        title: Option<String>,
+        preview_url: Option<String>,
+        file_size: Option<FileSize>,
+        #[serde(default)]
+        tags: Vec<Tag>,
+        time_created: Option<i64>,
+        time_updated: Option<i64>,
+    }
+
+    /// Steam sometimes sends 64-bit integers as strings.
+    #[derive(Debug, serde::Deserialize)]
+    #[serde(untagged)]
+    enum FileSize {
+        Int(u64),
+        Str(String),
+    }
+
+    #[derive(Debug, serde::Deserialize)]
+    struct Tag {
+        tag: String,
+    }
+
+    steam::request(http_client.post(MAP_URL).form(&Form { workshop_ids }))
+        .await
+        .map(|Response { publishedfiledetails }| {
+            publishedfiledetails
+                .into_iter()
+                .filter_map(|details| {
+                    let updated_at =
+                        OffsetDateTime::from_unix_timestamp(details.time_updated?).ok()?;
+
+                    Some(MapDetails {
+                        workshop_id: details.publishedfileid.parse().ok()?,
+                        name: details.title?,
+                        preview_url: details.preview_url.filter(|url| !url.is_empty()),
+                        file_size: details.file_size.and_then(|size| match size {
+                            FileSize::Int(size) => Some(size),
+                            FileSize::Str(size) => size.parse().ok(),
+                        }),
+                        tags: details.tags.into_iter().map(|Tag { tag }| tag).collect(),
+                        created_at: details
+                            .time_created
+                            .and_then(|time| OffsetDateTime::from_unix_timestamp(time).ok())
+                            .map(Timestamp::from),
+                        updated_at: updated_at.into(),
+                    })
+                })
+                .collect()
+        })
+}
+
 #[tracing::instrument(err(level = "debug"))]
 pub async fn download_map(
     workshop_id: WorkshopId,
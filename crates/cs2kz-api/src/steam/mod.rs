@@ -7,6 +7,9 @@ pub use users::{SteamUser, fetch_user};
 pub mod maps;
 pub use maps::{download_map, fetch_map_name};
 
+pub mod downloader;
+pub use downloader::{Downloader, MapDownloader};
+
 #[derive(Debug, Display, Error, From)]
 pub enum ApiError {
     #[display("failed to make http request")]
@@ -4,6 +4,7 @@ use std::net::Ipv4Addr;
 use std::time::Duration;
 
 use axum::extract::ws::Message as RawMessage;
+use cs2kz::maps::courses::filters::Tier;
 use cs2kz::maps::{CourseFilterId, Map, MapId};
 use cs2kz::players::{PlayerId, PlayerInfo, Preferences};
 use cs2kz::records::{Record, RecordId};
@@ -89,6 +90,15 @@ pub enum Incoming {
         teleports: u32,
         time: Seconds,
     },
+
+    /// A player voted on a filter's tiers.
+    TierVote {
+        player_id: PlayerId,
+        filter_id: CourseFilterId,
+        nub_tier: Tier,
+        pro_tier: Option<Tier>,
+        feedback: Option<String>,
+    },
 }
 
 #[derive(Debug, serde::Serialize)]
@@ -122,6 +132,9 @@ pub enum Outgoing {
         pro_points: Option<f64>,
         pro_leaderboard_size: u32,
     },
+    TierVoteAck {
+        filter_id: CourseFilterId,
+    },
 }
 
 #[derive(Debug, Display, Error, From)]
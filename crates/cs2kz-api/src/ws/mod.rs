@@ -7,6 +7,7 @@ use std::time::Duration;
 
 use axum::extract::ws::{CloseFrame, Message as RawMessage, close_code};
 use cs2kz::Context;
+use cs2kz::maps::courses::filters::votes::NewVote;
 use cs2kz::players::{NewPlayer, PlayerId, PlayerInfo};
 use cs2kz::plugin::PluginVersionId;
 use cs2kz::records::{GetRecordsParams, NewRecord};
@@ -382,6 +383,31 @@ where
 
             conn.send(reply).await.map_err(Into::into)?;
         },
+
+        P::TierVote {
+            player_id,
+            filter_id,
+            nub_tier,
+            pro_tier,
+            ref feedback,
+        } => {
+            let vote = NewVote {
+                filter_id,
+                player_id,
+                nub_tier,
+                pro_tier,
+                feedback: feedback.as_deref(),
+            };
+
+            if !cs2kz::maps::courses::filters::votes::cast(cx, vote).await? {
+                return Err("unknown filter".into());
+            }
+
+            let reply =
+                Message::reply(&message, message::Outgoing::TierVoteAck { filter_id }).encode()?;
+
+            conn.send(reply).await.map_err(Into::into)?;
+        },
     }
 
     Ok(())
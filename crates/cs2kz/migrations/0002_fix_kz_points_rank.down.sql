@@ -0,0 +1,48 @@
+CREATE OR REPLACE FUNCTION KZ_POINTS(
+  tier INT1 UNSIGNED,
+  is_pro_leaderboard BOOLEAN,
+  rank INT4 UNSIGNED,
+  dist_points FLOAT8
+) RETURNS FLOAT8
+BEGIN
+  DECLARE for_tier, remaining, for_rank FLOAT8;
+
+  SET for_tier = CASE tier
+    WHEN 1 THEN 0
+    WHEN 2 THEN 500
+    WHEN 3 THEN 2000
+    WHEN 4 THEN 3500
+    WHEN 5 THEN 5000
+    WHEN 6 THEN 6500
+    WHEN 7 THEN 8000
+    WHEN 8 THEN 9500
+  END;
+
+  IF (is_pro_leaderboard) THEN
+    SET for_tier = for_tier + (10000 - for_tier) * 0.1;
+  END IF;
+
+  SET remaining = 10000 - for_tier;
+
+  SET for_rank = 0;
+
+  IF (rank < 100) THEN
+    SET for_rank = (100 - rank) * 0.004;
+  END IF;
+
+  IF (rank < 20) THEN
+    SET for_rank = for_rank + (20 - rank) * 0.02;
+  END IF;
+
+  SET for_rank = for_rank + (
+    CASE rank
+      WHEN 0 THEN 0.2
+      WHEN 1 THEN 0.12
+      WHEN 2 THEN 0.09
+      WHEN 3 THEN 0.06
+      WHEN 4 THEN 0.02
+    END
+  );
+
+  RETURN for_tier + (0.125 * remaining * for_rank) + (0.875 * remaining * dist_points);
+END;
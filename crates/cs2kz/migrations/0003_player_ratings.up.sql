@@ -0,0 +1,63 @@
+CREATE TABLE IF NOT EXISTS PlayerRatings (
+  player_id INT8 UNSIGNED NOT NULL REFERENCES Players(id) ON DELETE CASCADE,
+  `mode` INT1 UNSIGNED NOT NULL,
+  rating FLOAT8 NOT NULL,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (player_id, `mode`),
+  INDEX (`mode`, rating)
+);
+
+INSERT INTO PlayerRatings (player_id, `mode`, rating)
+WITH NubLeaderboard AS (
+  SELECT
+    b.player_id,
+    cf.mode,
+    b.points,
+    cf.nub_tier AS tier,
+    RANK() OVER (
+      PARTITION BY b.filter_id
+      ORDER BY
+        r.time ASC,
+        r.submitted_at ASC
+    ) AS rank
+  FROM BestNubRecords AS b
+  JOIN Records AS r ON r.id = b.record_id
+  JOIN CourseFilters AS cf ON cf.id = b.filter_id
+),
+ProLeaderboard AS (
+  SELECT
+    b.player_id,
+    cf.mode,
+    b.points,
+    cf.pro_tier AS tier,
+    RANK() OVER (
+      PARTITION BY b.filter_id
+      ORDER BY
+        r.time ASC,
+        r.submitted_at ASC
+    ) AS rank
+  FROM BestProRecords AS b
+  JOIN Records AS r ON r.id = b.record_id
+  JOIN CourseFilters AS cf ON cf.id = b.filter_id
+),
+Points AS (
+  SELECT player_id, `mode`, KZ_POINTS(tier, false, rank - 1, points) AS points
+  FROM NubLeaderboard
+  UNION ALL
+  SELECT player_id, `mode`, KZ_POINTS(tier, true, rank - 1, points) AS points
+  FROM ProLeaderboard
+),
+RankedPoints AS (
+  SELECT
+    player_id,
+    `mode`,
+    points,
+    ROW_NUMBER() OVER (
+      PARTITION BY player_id, `mode`
+      ORDER BY points DESC
+    ) AS n
+  FROM Points
+)
+SELECT player_id, `mode`, SUM(points * POWER(0.975, n - 1))
+FROM RankedPoints
+GROUP BY player_id, `mode`;
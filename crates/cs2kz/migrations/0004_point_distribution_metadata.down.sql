@@ -0,0 +1,3 @@
+ALTER TABLE PointDistributionData
+  DROP COLUMN leaderboard_size,
+  DROP COLUMN updated_at;
@@ -0,0 +1,10 @@
+ALTER TABLE PointDistributionData
+  ADD COLUMN leaderboard_size INT4 UNSIGNED NOT NULL DEFAULT 0,
+  ADD COLUMN updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP;
+
+UPDATE PointDistributionData AS d
+SET d.leaderboard_size = CASE
+  WHEN d.is_pro_leaderboard
+  THEN (SELECT COUNT(*) FROM BestProRecords AS b WHERE b.filter_id = d.filter_id)
+  ELSE (SELECT COUNT(*) FROM BestNubRecords AS b WHERE b.filter_id = d.filter_id)
+END;
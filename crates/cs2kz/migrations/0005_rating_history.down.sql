@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS LeaderboardHistory;
+DROP TABLE IF EXISTS PlayerRatingHistory;
@@ -0,0 +1,19 @@
+CREATE TABLE IF NOT EXISTS PlayerRatingHistory (
+  player_id INT8 UNSIGNED NOT NULL REFERENCES Players(id) ON DELETE CASCADE,
+  `mode` INT1 UNSIGNED NOT NULL,
+  `date` DATE NOT NULL,
+  rating FLOAT8 NOT NULL,
+  rank INT4 UNSIGNED NOT NULL,
+  PRIMARY KEY (player_id, `mode`, `date`),
+  INDEX (`mode`, `date`, rank)
+);
+
+CREATE TABLE IF NOT EXISTS LeaderboardHistory (
+  filter_id INT2 UNSIGNED NOT NULL REFERENCES CourseFilters(id) ON DELETE CASCADE,
+  is_pro_leaderboard BOOLEAN NOT NULL,
+  player_id INT8 UNSIGNED NOT NULL REFERENCES Players(id) ON DELETE CASCADE,
+  `date` DATE NOT NULL,
+  rank INT4 UNSIGNED NOT NULL,
+  PRIMARY KEY (filter_id, is_pro_leaderboard, player_id, `date`),
+  INDEX (player_id, `date`)
+);
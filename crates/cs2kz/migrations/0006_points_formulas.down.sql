@@ -0,0 +1,7 @@
+ALTER TABLE BestProRecords
+  DROP COLUMN points_formula_version;
+
+ALTER TABLE BestNubRecords
+  DROP COLUMN points_formula_version;
+
+DROP TABLE IF EXISTS PointsFormulas;
@@ -0,0 +1,12 @@
+CREATE TABLE IF NOT EXISTS PointsFormulas (
+  version INT2 UNSIGNED NOT NULL PRIMARY KEY,
+  definition JSON NOT NULL,
+  installed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- every existing record was scored by the original formula
+ALTER TABLE BestNubRecords
+  ADD COLUMN points_formula_version INT2 UNSIGNED NOT NULL DEFAULT 1;
+
+ALTER TABLE BestProRecords
+  ADD COLUMN points_formula_version INT2 UNSIGNED NOT NULL DEFAULT 1;
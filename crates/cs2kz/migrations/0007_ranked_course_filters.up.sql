@@ -0,0 +1,23 @@
+-- Course filters whose records are awarded points.
+--
+-- A filter is ranked if it has been marked as such, and its map is approved.
+CREATE OR REPLACE VIEW RankedCourseFilters AS
+SELECT cf.id, cf.mode, c.map_id
+FROM CourseFilters AS cf
+JOIN Courses AS c ON c.id = cf.course_id
+JOIN Maps AS m ON m.id = c.map_id
+WHERE cf.state = 1
+AND m.state = 1;
+
+-- Filters which aren't ranked may still have points from before this view existed; the points
+-- daemon clears them once it gets to these filters.
+INSERT IGNORE INTO FiltersToRecalculate (filter_id)
+SELECT filter_id
+FROM BestNubRecords
+WHERE filter_id NOT IN (SELECT id FROM RankedCourseFilters)
+AND points != 0
+UNION
+SELECT filter_id
+FROM BestProRecords
+WHERE filter_id NOT IN (SELECT id FROM RankedCourseFilters)
+AND points != 0;
@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS MapSubmissionReviews;
+DROP TABLE IF EXISTS MapSubmissions;
@@ -0,0 +1,26 @@
+CREATE TABLE IF NOT EXISTS MapSubmissions (
+  id INT4 UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  workshop_id INT4 UNSIGNED NOT NULL,
+  submitted_by INT8 UNSIGNED NOT NULL REFERENCES Users(id),
+  description TEXT,
+  -- see `cs2kz::maps::submissions::SubmissionState` enum in the Rust code
+  state INT1 UNSIGNED NOT NULL DEFAULT 0,
+  -- SteamIDs of the map's mappers
+  mappers JSON NOT NULL,
+  -- see `cs2kz::maps::submissions::SubmittedCourse` struct in the Rust code
+  courses JSON NOT NULL,
+  -- the map this submission turned into once it was approved
+  map_id INT2 UNSIGNED REFERENCES Maps(id),
+  submitted_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS MapSubmissionReviews (
+  id INT4 UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  submission_id INT4 UNSIGNED NOT NULL REFERENCES MapSubmissions(id) ON DELETE CASCADE,
+  reviewer_id INT8 UNSIGNED NOT NULL REFERENCES Users(id),
+  -- the state the reviewer moved the submission into
+  state INT1 UNSIGNED NOT NULL,
+  comment TEXT NOT NULL,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,11 @@
+CREATE TABLE IF NOT EXISTS TierVotes (
+  filter_id INT2 UNSIGNED NOT NULL REFERENCES CourseFilters(id) ON DELETE CASCADE,
+  player_id INT8 UNSIGNED NOT NULL REFERENCES Players(id),
+  nub_tier INT1 UNSIGNED NOT NULL,
+  -- only players who completed the filter without teleports can vote on its PRO tier
+  pro_tier INT1 UNSIGNED,
+  feedback TEXT,
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (filter_id, player_id)
+);
@@ -0,0 +1,4 @@
+DROP TABLE IF EXISTS PendingMapUpdates;
+
+ALTER TABLE Maps
+  DROP COLUMN workshop_checked_at;
@@ -0,0 +1,13 @@
+-- the `time_updated` of the map's workshop item when we last checked it for updates
+ALTER TABLE Maps
+  ADD COLUMN workshop_checked_at TIMESTAMP NULL DEFAULT NULL;
+
+-- approved maps whose workshop item has changed since they were approved
+CREATE TABLE IF NOT EXISTS PendingMapUpdates (
+  map_id INT2 UNSIGNED NOT NULL PRIMARY KEY REFERENCES Maps(id) ON DELETE CASCADE,
+  -- the name and checksum of the map currently on the workshop
+  name VARCHAR(255) NOT NULL,
+  vpk_checksum BINARY(16) NOT NULL,
+  workshop_updated_at TIMESTAMP NOT NULL,
+  detected_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
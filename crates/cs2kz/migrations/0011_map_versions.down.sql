@@ -0,0 +1,11 @@
+ALTER TABLE Courses
+  DROP FOREIGN KEY FK_previous_course_version,
+  DROP INDEX UC_previous_course_version,
+  DROP COLUMN previous_version_id;
+
+ALTER TABLE Maps
+  DROP FOREIGN KEY FK_previous_map_version,
+  DROP INDEX UC_previous_map_version,
+  DROP COLUMN version,
+  DROP COLUMN previous_version_id,
+  DROP COLUMN changelog;
@@ -0,0 +1,27 @@
+-- maps form a chain of versions, starting at 1
+ALTER TABLE Maps
+  ADD COLUMN version INT2 UNSIGNED NOT NULL DEFAULT 1,
+  ADD COLUMN previous_version_id INT2 UNSIGNED NULL DEFAULT NULL,
+  ADD COLUMN changelog TEXT,
+  ADD CONSTRAINT FK_previous_map_version
+    FOREIGN KEY (previous_version_id) REFERENCES Maps(id) ON DELETE SET NULL,
+  ADD CONSTRAINT UC_previous_map_version UNIQUE (previous_version_id);
+
+-- the course on the previous map version this course is unchanged from
+ALTER TABLE Courses
+  ADD COLUMN previous_version_id INT2 UNSIGNED NULL DEFAULT NULL,
+  ADD CONSTRAINT FK_previous_course_version
+    FOREIGN KEY (previous_version_id) REFERENCES Courses(id) ON DELETE SET NULL,
+  ADD CONSTRAINT UC_previous_course_version UNIQUE (previous_version_id);
+
+-- versions used to be implied by maps sharing a name
+UPDATE Maps AS m
+JOIN (
+  SELECT
+    id,
+    ROW_NUMBER() OVER (PARTITION BY name ORDER BY id) AS version,
+    LAG(id) OVER (PARTITION BY name ORDER BY id) AS previous_version_id
+  FROM Maps
+) AS v ON v.id = m.id
+SET m.version = v.version,
+    m.previous_version_id = v.previous_version_id;
@@ -0,0 +1,6 @@
+DROP TABLE IF EXISTS RecordCarryOverFilters;
+DROP TABLE IF EXISTS RecordCarryOvers;
+
+ALTER TABLE Records
+  DROP FOREIGN KEY FK_carried_over_from,
+  DROP COLUMN carried_over_from;
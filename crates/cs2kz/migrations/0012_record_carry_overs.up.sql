@@ -0,0 +1,23 @@
+-- the record this record was copied from when it was carried over to a new map version
+ALTER TABLE Records
+  ADD COLUMN carried_over_from INT4 UNSIGNED NULL DEFAULT NULL,
+  ADD CONSTRAINT FK_carried_over_from
+    FOREIGN KEY (carried_over_from) REFERENCES Records(id) ON DELETE SET NULL;
+
+-- audit trail of admins carrying records over to new map versions
+CREATE TABLE IF NOT EXISTS RecordCarryOvers (
+  id INT4 UNSIGNED NOT NULL AUTO_INCREMENT PRIMARY KEY,
+  -- the map the records were carried over to
+  map_id INT2 UNSIGNED NOT NULL REFERENCES Maps(id) ON DELETE CASCADE,
+  performed_by INT8 UNSIGNED NOT NULL REFERENCES Users(id),
+  created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE IF NOT EXISTS RecordCarryOverFilters (
+  carry_over_id INT4 UNSIGNED NOT NULL REFERENCES RecordCarryOvers(id) ON DELETE CASCADE,
+  from_filter_id INT2 UNSIGNED NOT NULL REFERENCES CourseFilters(id) ON DELETE CASCADE,
+  to_filter_id INT2 UNSIGNED NOT NULL REFERENCES CourseFilters(id) ON DELETE CASCADE,
+  -- how many records were copied
+  records INT4 UNSIGNED NOT NULL,
+  PRIMARY KEY (carry_over_id, to_filter_id)
+);
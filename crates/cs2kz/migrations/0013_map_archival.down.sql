@@ -0,0 +1,2 @@
+ALTER TABLE Maps
+  DROP COLUMN archived_at;
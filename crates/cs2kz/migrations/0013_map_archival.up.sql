@@ -0,0 +1,3 @@
+-- archived maps are hidden from listings, but keep all of their courses and records
+ALTER TABLE Maps
+  ADD COLUMN archived_at TIMESTAMP NULL DEFAULT NULL;
@@ -0,0 +1,14 @@
+-- metadata about the Steam workshop items of maps, refreshed periodically
+CREATE TABLE IF NOT EXISTS WorkshopMetadata (
+  workshop_id INT4 UNSIGNED NOT NULL PRIMARY KEY,
+  -- the item's preview image on Steam's CDN
+  preview_url VARCHAR(2048),
+  -- the `preview_url` our local copy of the preview image was downloaded from
+  preview_mirrored_from VARCHAR(2048),
+  preview_content_type VARCHAR(255),
+  file_size INT8 UNSIGNED,
+  tags JSON NOT NULL DEFAULT '[]',
+  workshop_created_at TIMESTAMP NULL DEFAULT NULL,
+  workshop_updated_at TIMESTAMP NULL DEFAULT NULL,
+  refreshed_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
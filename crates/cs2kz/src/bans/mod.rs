@@ -4,7 +4,7 @@ use std::num::NonZero;
 use futures_util::{Stream, TryStreamExt};
 use sqlx::Row;
 
-use crate::pagination::{Limit, Offset, Paginated};
+use crate::pagination::{Cursor, Limit, Offset, Paginated};
 use crate::players::{PlayerId, PlayerInfo};
 use crate::time::Timestamp;
 use crate::users::UserId;
@@ -45,10 +45,19 @@ pub struct GetBansParams {
     pub player_id: Option<PlayerId>,
     pub banned_by: Option<UserId>,
     pub reason: Option<BanReason>,
+
+    /// Only include bans which come after this cursor.
+    pub cursor: Option<BansCursor>,
+
     pub limit: Limit<1000, 100>,
     pub offset: Offset,
 }
 
+/// A cursor for paginating through [`get()`] results.
+///
+/// Bans are sorted by their ID, so the cursor has no separate sort key.
+pub type BansCursor = Cursor<(), BanId>;
+
 #[derive(Debug)]
 pub struct NewBan {
     pub player_id: PlayerId,
@@ -100,18 +109,34 @@ pub struct UpdateBanError(database::Error);
 #[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
 pub async fn get(
     cx: &Context,
-    GetBansParams { player_id, banned_by, reason, limit, offset }: GetBansParams,
+    GetBansParams {
+        player_id,
+        banned_by,
+        reason,
+        cursor,
+        limit,
+        offset,
+    }: GetBansParams,
 ) -> Result<Paginated<impl Stream<Item = Result<Ban, GetBansError>>>, GetBansError> {
-    let total = database::count!(cx.database().as_ref(), "Bans").await?;
+    let total = match cursor {
+        None => Some(database::count!(cx.database().as_ref(), "Bans").await?),
+        Some(_) => None,
+    };
+    let cursor_id = cursor.map(|cursor| *cursor.id());
+
     let bans = self::macros::select!(
         "WHERE b.player_id = COALESCE(?, b.player_id)
          AND b.banned_by = COALESCE(?, b.banned_by)
          AND b.reason = COALESCE(?, b.reason)
+         AND (? IS NULL OR b.id > ?)
+         ORDER BY b.id ASC
          LIMIT ?
          OFFSET ?",
         player_id,
         banned_by,
         reason,
+        cursor_id,
+        cursor_id,
         limit.value(),
         offset.value(),
     )
@@ -119,7 +144,10 @@ pub async fn get(
     .map_ok(|row| self::macros::parse_row!(row))
     .map_err(GetBansError::from);
 
-    Ok(Paginated::new(total, bans))
+    Ok(match total {
+        Some(total) => Paginated::new(total, bans),
+        None => Paginated::without_total(bans),
+    })
 }
 
 #[tracing::instrument(skip(cx), err(level = "debug"))]
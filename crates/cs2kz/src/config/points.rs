@@ -0,0 +1,19 @@
+use std::num::NonZero;
+
+#[derive(Debug, serde::Deserialize)]
+#[serde(default, rename_all = "kebab-case", deny_unknown_fields)]
+pub struct PointsConfig {
+    /// How many course filters the points daemon may recalculate at the same time.
+    #[serde(default = "default_workers")]
+    pub workers: NonZero<usize>,
+}
+
+impl Default for PointsConfig {
+    fn default() -> Self {
+        Self { workers: default_workers() }
+    }
+}
+
+fn default_workers() -> NonZero<usize> {
+    std::thread::available_parallelism().unwrap_or(NonZero::<usize>::MIN)
+}
@@ -14,6 +14,7 @@ use crate::database::{
     DatabaseConnectionOptions,
     EstablishDatabaseConnectionError,
 };
+use crate::points;
 
 mod inner {
     use super::*;
@@ -24,6 +25,7 @@ mod inner {
         pub(super) database: Database,
         pub(super) shutdown_token: CancellationToken,
         pub(super) tasks: TaskTracker,
+        pub(super) points_daemon: points::daemon::State,
     }
 }
 
@@ -38,6 +40,9 @@ pub enum InitializeContextError {
 
     #[display("failed to run database migrations: {_0}")]
     RunDatabaseMigrations(sqlx::migrate::MigrateError),
+
+    #[display("{_0}")]
+    InstallPointsFormula(points::formula::InstallFormulaError),
 }
 
 impl Context {
@@ -63,10 +68,17 @@ impl Context {
         .await?;
 
         database::MIGRATIONS.run(database.as_ref()).await?;
+        points::formula::install(&database).await?;
 
         let tasks = TaskTracker::new();
 
-        Ok(Self(Arc::new(inner::Context { config, database, shutdown_token, tasks })))
+        Ok(Self(Arc::new(inner::Context {
+            config,
+            database,
+            shutdown_token,
+            tasks,
+            points_daemon: Default::default(),
+        })))
     }
 
     pub fn config(&self) -> &Config {
@@ -77,6 +89,10 @@ impl Context {
         &self.0.database
     }
 
+    pub(crate) fn points_daemon(&self) -> &points::daemon::State {
+        &self.0.points_daemon
+    }
+
     /// Executes an `async` closure in the context of a database transaction.
     ///
     /// If the closure returns <code>[Ok](())</code>, the transaction will be committed.
@@ -7,7 +7,7 @@ use tokio_stream::wrappers::BroadcastStream;
 use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
 
 use crate::maps::courses::CourseFilterId;
-use crate::maps::{MapChecksum, MapState, NewCourse};
+use crate::maps::{MapChecksum, MapId, MapState, NewCourse};
 use crate::players::PlayerId;
 use crate::plugin::PluginVersionId;
 use crate::servers::ServerId;
@@ -30,6 +30,12 @@ pub enum Event {
         courses: Box<[NewCourse]>,
     },
 
+    /// An approved map has changed on the Steam workshop.
+    MapUpdateDetected { map_id: MapId, name: String, vpk_checksum: MapChecksum },
+
+    /// A map has been deleted, along with its filters.
+    MapDeleted { map_id: MapId, filter_ids: Box<[CourseFilterId]> },
+
     /// A new record has been submitted.
     NewRecord {
         player_id: PlayerId,
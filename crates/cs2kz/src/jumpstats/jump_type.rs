@@ -14,6 +14,21 @@ pub enum JumpType {
     Fall,
 }
 
+impl JumpType {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::LongJump => "long-jump",
+            Self::Bhop => "bhop",
+            Self::MultiBhop => "multi-bhop",
+            Self::WeirdJump => "weird-jump",
+            Self::LadderJump => "ladder-jump",
+            Self::Ladderhop => "ladderhop",
+            Self::Jumpbug => "jumpbug",
+            Self::Fall => "fall",
+        }
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for JumpType {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -3,7 +3,7 @@ use std::num::NonZero;
 use futures_util::{Stream, TryStreamExt};
 
 use crate::mode::Mode;
-use crate::pagination::{Limit, Offset, Paginated};
+use crate::pagination::{Cursor, Limit, Offset, Paginated};
 use crate::players::{PlayerId, PlayerInfo};
 use crate::servers::{ServerId, ServerInfo};
 use crate::styles::Styles;
@@ -46,10 +46,18 @@ pub struct Jumpstat {
 
 #[derive(Debug)]
 pub struct GetJumpstatsParams {
+    /// Only include jumpstats which come after this cursor.
+    pub cursor: Option<JumpstatsCursor>,
+
     pub limit: Limit<1000, 100>,
     pub offset: Offset,
 }
 
+/// A cursor for paginating through [`get()`] results.
+///
+/// Jumpstats are sorted by their ID, so the cursor has no separate sort key.
+pub type JumpstatsCursor = Cursor<(), JumpstatId>;
+
 #[derive(Debug, Display, Error, From)]
 #[display("failed to get jumpstats")]
 #[from(forward)]
@@ -58,15 +66,44 @@ pub struct GetJumpstatsError(database::Error);
 #[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
 pub async fn get(
     cx: &Context,
-    GetJumpstatsParams { limit, offset }: GetJumpstatsParams,
+    GetJumpstatsParams { cursor, limit, offset }: GetJumpstatsParams,
 ) -> Result<Paginated<impl Stream<Item = Result<Jumpstat, GetJumpstatsError>>>, GetJumpstatsError> {
-    let total = database::count!(cx.database().as_ref(), "Jumps").await?;
-    let jumpstats = self::macros::select!("LIMIT ? OFFSET ?", limit.value(), offset.value())
+    let total = match cursor {
+        None => Some(database::count!(cx.database().as_ref(), "Jumps").await?),
+        Some(_) => None,
+    };
+    let cursor_id = cursor.map(|cursor| *cursor.id());
+
+    let jumpstats = self::macros::select!(
+        "WHERE (? IS NULL OR j.id > ?)
+         ORDER BY j.id ASC
+         LIMIT ?
+         OFFSET ?",
+        cursor_id,
+        cursor_id,
+        limit.value(),
+        offset.value(),
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| self::macros::parse_row!(row))
+    .map_err(GetJumpstatsError::from);
+
+    Ok(match total {
+        Some(total) => Paginated::new(total, jumpstats),
+        None => Paginated::without_total(jumpstats),
+    })
+}
+
+/// Returns a stream of all jumpstats.
+///
+/// Unlike [`get()`], this does not paginate the results, so it is suitable for exporting large
+/// amounts of jumpstats.
+#[tracing::instrument(skip(cx))]
+pub fn export(cx: &Context) -> impl Stream<Item = Result<Jumpstat, GetJumpstatsError>> {
+    self::macros::select!("ORDER BY j.id ASC")
         .fetch(cx.database().as_ref())
         .map_ok(|row| self::macros::parse_row!(row))
-        .map_err(GetJumpstatsError::from);
-
-    Ok(Paginated::new(total, jumpstats))
+        .map_err(GetJumpstatsError::from)
 }
 
 #[tracing::instrument(skip(cx), err(level = "debug"))]
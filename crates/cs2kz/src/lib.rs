@@ -65,7 +65,5 @@ pub mod steam;
 pub mod styles;
 pub mod time;
 
-mod python;
-
 mod fmt;
 mod num;
@@ -0,0 +1,436 @@
+//! Carrying records over to new map versions.
+//!
+//! Approving a new version of a map invalidates the old one, and with it every record set on it.
+//! If some courses did not change, an admin can copy the records from the old version's filters to
+//! the new version's filters. By default, filters are paired up through the course links stored
+//! with the new version (see [`versions`]), but the pairs can also be specified explicitly.
+//!
+//! Copied records point back at their originals, and every carry-over is recorded along with the
+//! admin who performed it. Records that have already been carried over to a filter are skipped,
+//! so repeating a carry-over is harmless.
+//!
+//! [`versions`]: super::versions
+
+use std::collections::{BTreeSet, HashMap};
+use std::num::NonZero;
+
+use futures_util::TryStreamExt;
+use sqlx::Row as _;
+
+use super::{CourseFilterId, MapId};
+use crate::mode::Mode;
+use crate::time::Timestamp;
+use crate::users::{UserId, UserInfo};
+use crate::{Context, database, points};
+
+define_id_type! {
+    /// A unique identifier for record carry-overs.
+    #[derive(sqlx::Type)]
+    #[sqlx(transparent)]
+    pub struct CarryOverId(NonZero<u32>);
+}
+
+/// A pair of filters to copy records between.
+#[derive(Debug, Clone, Copy)]
+pub struct FilterMapping {
+    /// The filter on an older map version.
+    pub from: CourseFilterId,
+
+    /// The filter on the map the records are carried over to.
+    pub to: CourseFilterId,
+}
+
+#[derive(Debug)]
+pub struct NewCarryOver<'a> {
+    /// The map to carry records over to.
+    pub map_id: MapId,
+
+    /// The admin carrying the records over.
+    pub performed_by: UserId,
+
+    /// The filters to copy records between.
+    ///
+    /// If this is [`None`], the filters of courses linked to the previous map version are used.
+    pub filters: Option<&'a [FilterMapping]>,
+}
+
+/// A record of records having been carried over.
+#[derive(Debug)]
+pub struct CarryOver {
+    pub id: CarryOverId,
+    pub map_id: MapId,
+    pub performed_by: UserInfo,
+    pub filters: Vec<CarriedOverFilter>,
+    pub created_at: Timestamp,
+}
+
+#[derive(Debug)]
+pub struct CarriedOverFilter {
+    pub from_filter_id: CourseFilterId,
+    pub to_filter_id: CourseFilterId,
+
+    /// How many records were copied.
+    pub records: u64,
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum CarryOverError {
+    #[display(
+        "no courses on this map are linked to a previous version; specify the filters explicitly"
+    )]
+    NoLinkedCourses,
+
+    #[display("filter {filter_id} does not exist")]
+    #[error(ignore)]
+    #[from(ignore)]
+    UnknownFilter { filter_id: CourseFilterId },
+
+    #[display("filter {filter_id} does not belong to this map")]
+    #[error(ignore)]
+    #[from(ignore)]
+    TargetNotOnMap { filter_id: CourseFilterId },
+
+    #[display(
+        "filter {filter_id} belongs to this map; records can only be carried over from other maps"
+    )]
+    #[error(ignore)]
+    #[from(ignore)]
+    SourceOnMap { filter_id: CourseFilterId },
+
+    #[display("filters {from} and {to} are for different modes")]
+    #[error(ignore)]
+    #[from(ignore)]
+    ModeMismatch { from: CourseFilterId, to: CourseFilterId },
+
+    #[display("filter {filter_id} is the target of multiple mappings")]
+    #[error(ignore)]
+    #[from(ignore)]
+    DuplicateTarget { filter_id: CourseFilterId },
+
+    #[display("{_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to get record carry-overs")]
+#[from(forward)]
+pub struct GetCarryOversError(database::Error);
+
+/// Copies records from older map versions to a map, and rebuilds the affected leaderboards.
+///
+/// The points of the target filters are recalculated in the background.
+///
+/// Returns [`None`] if the map does not exist.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn carry_over(
+    cx: &Context,
+    NewCarryOver { map_id, performed_by, filters }: NewCarryOver<'_>,
+) -> Result<Option<CarryOver>, CarryOverError> {
+    let carry_over = cx
+        .database_transaction(async move |conn| {
+            let map_exists = sqlx::query!("SELECT id FROM Maps WHERE id = ? FOR UPDATE", map_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .is_some();
+
+            if !map_exists {
+                return Ok(None);
+            }
+
+            let mappings = match filters {
+                Some(filters) => {
+                    validate_mappings(&mut *conn, map_id, filters).await?;
+                    filters.to_vec()
+                },
+                None => get_linked_filters(&mut *conn, map_id).await?,
+            };
+
+            if mappings.is_empty() {
+                return Err(CarryOverError::NoLinkedCourses);
+            }
+
+            let carry_over_id = sqlx::query!(
+                "INSERT INTO RecordCarryOvers (map_id, performed_by)
+                 VALUES (?, ?)
+                 RETURNING id",
+                map_id,
+                performed_by,
+            )
+            .fetch_one(&mut *conn)
+            .await?
+            .try_get::<CarryOverId, _>(0)?;
+
+            for FilterMapping { from, to } in mappings {
+                let records = copy_records(&mut *conn, from, to).await?;
+
+                sqlx::query!(
+                    "INSERT INTO RecordCarryOverFilters (
+                       carry_over_id,
+                       from_filter_id,
+                       to_filter_id,
+                       records
+                     )
+                     VALUES (?, ?, ?, ?)",
+                    carry_over_id,
+                    from,
+                    to,
+                    records,
+                )
+                .execute(&mut *conn)
+                .await?;
+
+                points::consistency::rebuild_best_records(&mut *conn, to).await?;
+
+                info!(%carry_over_id, %from, %to, records, "carried over records");
+            }
+
+            get_carry_overs(&mut *conn, map_id, Some(carry_over_id))
+                .await
+                .map(|mut carry_overs| carry_overs.pop())
+                .map_err(CarryOverError::from)
+        })
+        .await?;
+
+    if let Some(ref carry_over) = carry_over {
+        points::daemon::request(cx, carry_over.filters.iter().map(|filter| filter.to_filter_id));
+    }
+
+    Ok(carry_over)
+}
+
+/// Returns every carry-over to the given map, most recent first.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_for_map(
+    cx: &Context,
+    map_id: MapId,
+) -> Result<Vec<CarryOver>, GetCarryOversError> {
+    let mut conn = cx.database().as_ref().acquire().await?;
+
+    get_carry_overs(&mut conn, map_id, None)
+        .await
+        .map_err(GetCarryOversError::from)
+}
+
+/// Makes sure explicitly specified filter mappings make sense.
+async fn validate_mappings(
+    conn: &mut database::Connection,
+    map_id: MapId,
+    mappings: &[FilterMapping],
+) -> Result<(), CarryOverError> {
+    if mappings.is_empty() {
+        return Ok(());
+    }
+
+    let mut targets = BTreeSet::new();
+
+    for mapping in mappings {
+        if !targets.insert(mapping.to) {
+            return Err(CarryOverError::DuplicateTarget { filter_id: mapping.to });
+        }
+    }
+
+    let mut query = database::QueryBuilder::new(
+        "SELECT cf.id, c.map_id, cf.mode
+         FROM CourseFilters AS cf
+         JOIN Courses AS c ON c.id = cf.course_id
+         WHERE cf.id IN ",
+    );
+
+    query.push_tuples(
+        mappings
+            .iter()
+            .flat_map(|mapping| [mapping.from, mapping.to]),
+        |mut query, filter_id| {
+            query.push_bind(filter_id);
+        },
+    );
+
+    let filters = query
+        .build()
+        .fetch_all(conn)
+        .await?
+        .into_iter()
+        .map(|row| {
+            let filter_id = row.try_get::<CourseFilterId, _>(0)?;
+            let map_id = row.try_get::<MapId, _>(1)?;
+            let mode = row.try_get::<Mode, _>(2)?;
+
+            Ok((filter_id, (map_id, mode)))
+        })
+        .collect::<database::Result<HashMap<_, _>>>()?;
+
+    for &FilterMapping { from, to } in mappings {
+        let Some(&(from_map_id, from_mode)) = filters.get(&from) else {
+            return Err(CarryOverError::UnknownFilter { filter_id: from });
+        };
+
+        let Some(&(to_map_id, to_mode)) = filters.get(&to) else {
+            return Err(CarryOverError::UnknownFilter { filter_id: to });
+        };
+
+        if to_map_id != map_id {
+            return Err(CarryOverError::TargetNotOnMap { filter_id: to });
+        }
+
+        if from_map_id == map_id {
+            return Err(CarryOverError::SourceOnMap { filter_id: from });
+        }
+
+        if from_mode != to_mode {
+            return Err(CarryOverError::ModeMismatch { from, to });
+        }
+    }
+
+    Ok(())
+}
+
+/// Pairs up the filters of courses linked to their previous version.
+async fn get_linked_filters(
+    conn: &mut database::Connection,
+    map_id: MapId,
+) -> database::Result<Vec<FilterMapping>> {
+    sqlx::query_as!(
+        FilterMapping,
+        "SELECT old.id AS `from: CourseFilterId`, new.id AS `to: CourseFilterId`
+         FROM Courses AS c
+         JOIN CourseFilters AS new ON new.course_id = c.id
+         JOIN CourseFilters AS old ON old.course_id = c.previous_version_id AND old.mode = new.mode
+         WHERE c.map_id = ?
+         ORDER BY new.id ASC",
+        map_id,
+    )
+    .fetch_all(conn)
+    .await
+    .map_err(database::Error::from)
+}
+
+/// Copies the records (and their replays) of one filter to another.
+///
+/// Returns how many records were copied.
+async fn copy_records(
+    conn: &mut database::Connection,
+    from: CourseFilterId,
+    to: CourseFilterId,
+) -> database::Result<u64> {
+    let copied = sqlx::query!(
+        "INSERT INTO Records (
+           player_id,
+           server_id,
+           filter_id,
+           styles,
+           teleports,
+           time,
+           plugin_version_id,
+           submitted_at,
+           carried_over_from
+         )
+         SELECT
+           r.player_id,
+           r.server_id,
+           ?,
+           r.styles,
+           r.teleports,
+           r.time,
+           r.plugin_version_id,
+           r.submitted_at,
+           r.id
+         FROM Records AS r
+         WHERE r.filter_id = ?
+         AND NOT EXISTS (
+           SELECT 1
+           FROM Records AS copy
+           WHERE copy.filter_id = ?
+           AND copy.carried_over_from = r.id
+         )",
+        to,
+        from,
+        to,
+    )
+    .execute(&mut *conn)
+    .await?
+    .rows_affected();
+
+    sqlx::query!(
+        "INSERT IGNORE INTO RecordReplays (record_id, data)
+         SELECT copy.id, replay.data
+         FROM Records AS copy
+         JOIN RecordReplays AS replay ON replay.record_id = copy.carried_over_from
+         WHERE copy.filter_id = ?",
+        to,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    Ok(copied)
+}
+
+async fn get_carry_overs(
+    conn: &mut database::Connection,
+    map_id: MapId,
+    carry_over_id: Option<CarryOverId>,
+) -> database::Result<Vec<CarryOver>> {
+    let mut carry_overs = sqlx::query!(
+        "SELECT
+           co.id AS `id: CarryOverId`,
+           co.map_id AS `map_id: MapId`,
+           u.id AS `performed_by_id: UserId`,
+           u.name AS performed_by_name,
+           co.created_at
+         FROM RecordCarryOvers AS co
+         JOIN Users AS u ON u.id = co.performed_by
+         WHERE co.map_id = ?
+         AND co.id = COALESCE(?, co.id)
+         ORDER BY co.id DESC",
+        map_id,
+        carry_over_id,
+    )
+    .fetch(&mut *conn)
+    .map_ok(|row| CarryOver {
+        id: row.id,
+        map_id: row.map_id,
+        performed_by: UserInfo {
+            id: row.performed_by_id,
+            name: row.performed_by_name,
+        },
+        filters: Vec::new(),
+        created_at: row.created_at.into(),
+    })
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    let indices = carry_overs
+        .iter()
+        .enumerate()
+        .map(|(idx, carry_over)| (carry_over.id, idx))
+        .collect::<HashMap<_, _>>();
+
+    let filters = sqlx::query!(
+        "SELECT
+           f.carry_over_id AS `carry_over_id: CarryOverId`,
+           f.from_filter_id AS `from_filter_id: CourseFilterId`,
+           f.to_filter_id AS `to_filter_id: CourseFilterId`,
+           f.records
+         FROM RecordCarryOverFilters AS f
+         JOIN RecordCarryOvers AS co ON co.id = f.carry_over_id
+         WHERE co.map_id = ?
+         AND co.id = COALESCE(?, co.id)
+         ORDER BY f.to_filter_id ASC",
+        map_id,
+        carry_over_id,
+    )
+    .fetch_all(&mut *conn)
+    .await?;
+
+    for row in filters {
+        carry_overs[indices[&row.carry_over_id]]
+            .filters
+            .push(CarriedOverFilter {
+                from_filter_id: row.from_filter_id,
+                to_filter_id: row.to_filter_id,
+                records: row.records.into(),
+            });
+    }
+
+    Ok(carry_overs)
+}
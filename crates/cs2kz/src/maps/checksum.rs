@@ -3,7 +3,7 @@ use std::{fmt, io};
 use md5::{Digest, Md5};
 
 /// The MD5 hash of a map's `.vpk` file.
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[cfg_attr(feature = "fake", derive(fake::Dummy))]
 pub struct MapChecksum {
     #[debug("{self}")]
@@ -9,6 +9,7 @@ use crate::mode::Mode;
 use crate::{Context, database};
 
 mod stream;
+pub mod votes;
 
 define_id_type! {
     /// A unique identifier for course filters.
@@ -95,6 +96,25 @@ pub async fn get_by_id(
     })
 }
 
+/// Returns whether records on a filter are awarded points.
+///
+/// This is the case if the filter is [ranked] and its map is [approved].
+///
+/// [ranked]: CourseFilterState::Ranked
+/// [approved]: crate::maps::MapState::Approved
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn is_ranked(
+    cx: &Context,
+    filter_id: CourseFilterId,
+) -> Result<bool, GetCourseFiltersError> {
+    sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM RankedCourseFilters WHERE id = ?")
+        .bind(filter_id)
+        .fetch_one(cx.database().as_ref())
+        .await
+        .map(|count| count > 0)
+        .map_err(GetCourseFiltersError::from)
+}
+
 #[tracing::instrument(skip(cx))]
 pub fn get(
     cx: &Context,
@@ -0,0 +1,205 @@
+//! Tier votes.
+//!
+//! While a map is [in testing], players who completed one of its filters can [`cast()`] a vote on
+//! the filter's tiers and leave some feedback. Map-pool admins can look at the aggregated votes
+//! with [`get_for_map()`] before they decide on the final tiers.
+//!
+//! [in testing]: crate::maps::MapState::InTesting
+
+use std::collections::BTreeMap;
+
+use super::{CourseFilterId, Tier};
+use crate::maps::{CourseId, MapId, MapState};
+use crate::mode::Mode;
+use crate::players::{PlayerId, PlayerInfo};
+use crate::time::Timestamp;
+use crate::{Context, database};
+
+/// How many votes each tier received.
+pub type TierDistribution = BTreeMap<Tier, u32>;
+
+/// All votes cast on a single filter.
+#[derive(Debug, serde::Serialize)]
+pub struct FilterVotes {
+    pub filter_id: CourseFilterId,
+    pub course_id: CourseId,
+    pub course_name: String,
+    pub mode: Mode,
+    pub nub_tiers: TierDistribution,
+    pub pro_tiers: TierDistribution,
+    pub votes: Vec<Vote>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct Vote {
+    pub player: PlayerInfo,
+    pub nub_tier: Tier,
+    pub pro_tier: Option<Tier>,
+    pub feedback: Option<String>,
+    pub updated_at: Timestamp,
+}
+
+#[derive(Debug)]
+pub struct NewVote<'a> {
+    pub filter_id: CourseFilterId,
+    pub player_id: PlayerId,
+    pub nub_tier: Tier,
+
+    /// Only players who completed the filter without teleports can vote on its PRO tier.
+    pub pro_tier: Option<Tier>,
+
+    pub feedback: Option<&'a str>,
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum CastVoteError {
+    #[display("votes can only be cast on maps that are in testing")]
+    MapNotInTesting,
+
+    #[display("you have to complete a filter before voting on it")]
+    FilterNotCompleted,
+
+    #[display("you have to complete a filter without teleports before voting on its pro tier")]
+    ProFilterNotCompleted,
+
+    #[display("{_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to get tier votes")]
+#[from(forward)]
+pub struct GetVotesError(database::Error);
+
+/// Casts a vote on a filter's tiers.
+///
+/// Every player has a single vote per filter; voting again replaces the previous vote.
+/// Returns `false` if the filter does not exist.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn cast(cx: &Context, vote: NewVote<'_>) -> Result<bool, CastVoteError> {
+    let Some(row) = sqlx::query!(
+        "SELECT
+           m.state AS `map_state: MapState`,
+           (SELECT COUNT(*)
+            FROM Records
+            WHERE filter_id = cf.id
+            AND player_id = ?) AS `records!`,
+           (SELECT COUNT(*)
+            FROM Records
+            WHERE filter_id = cf.id
+            AND player_id = ?
+            AND teleports = 0) AS `pro_records!`
+         FROM CourseFilters AS cf
+         JOIN Courses AS c ON c.id = cf.course_id
+         JOIN Maps AS m ON m.id = c.map_id
+         WHERE cf.id = ?",
+        vote.player_id,
+        vote.player_id,
+        vote.filter_id,
+    )
+    .fetch_optional(cx.database().as_ref())
+    .await?
+    else {
+        return Ok(false);
+    };
+
+    if !matches!(row.map_state, MapState::InTesting) {
+        return Err(CastVoteError::MapNotInTesting);
+    }
+
+    if row.records == 0 {
+        return Err(CastVoteError::FilterNotCompleted);
+    }
+
+    if vote.pro_tier.is_some() && row.pro_records == 0 {
+        return Err(CastVoteError::ProFilterNotCompleted);
+    }
+
+    sqlx::query!(
+        "INSERT INTO TierVotes (filter_id, player_id, nub_tier, pro_tier, feedback)
+         VALUES (?, ?, ?, ?, ?)
+         ON DUPLICATE KEY
+         UPDATE nub_tier = VALUES(nub_tier),
+                pro_tier = VALUES(pro_tier),
+                feedback = VALUES(feedback),
+                updated_at = NOW()",
+        vote.filter_id,
+        vote.player_id,
+        vote.nub_tier,
+        vote.pro_tier,
+        vote.feedback,
+    )
+    .execute(cx.database().as_ref())
+    .await?;
+
+    Ok(true)
+}
+
+/// Returns the votes cast on every filter of a map.
+///
+/// Filters nobody voted on are omitted.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_for_map(cx: &Context, map_id: MapId) -> Result<Vec<FilterVotes>, GetVotesError> {
+    let rows = sqlx::query!(
+        "SELECT
+           cf.id AS `filter_id: CourseFilterId`,
+           c.id AS `course_id: CourseId`,
+           c.name AS course_name,
+           cf.mode AS `mode: Mode`,
+           p.id AS `player_id: PlayerId`,
+           p.name AS player_name,
+           v.nub_tier AS `nub_tier: Tier`,
+           v.pro_tier AS `pro_tier: Tier`,
+           v.feedback,
+           v.updated_at
+         FROM TierVotes AS v
+         JOIN CourseFilters AS cf ON cf.id = v.filter_id
+         JOIN Courses AS c ON c.id = cf.course_id
+         JOIN Players AS p ON p.id = v.player_id
+         WHERE c.map_id = ?
+         ORDER BY cf.id ASC, v.updated_at DESC",
+        map_id,
+    )
+    .fetch_all(cx.database().as_ref())
+    .await?;
+
+    let mut filters = Vec::<FilterVotes>::new();
+
+    for row in rows {
+        let vote = Vote {
+            player: PlayerInfo { id: row.player_id, name: row.player_name },
+            nub_tier: row.nub_tier,
+            pro_tier: row.pro_tier,
+            feedback: row.feedback,
+            updated_at: row.updated_at.into(),
+        };
+
+        let filter = match filters.last_mut() {
+            Some(filter) if filter.filter_id == row.filter_id => filter,
+            _ => {
+                filters.push(FilterVotes {
+                    filter_id: row.filter_id,
+                    course_id: row.course_id,
+                    course_name: row.course_name,
+                    mode: row.mode,
+                    nub_tiers: TierDistribution::new(),
+                    pro_tiers: TierDistribution::new(),
+                    votes: Vec::new(),
+                });
+
+                filters.last_mut().expect("we just pushed")
+            },
+        };
+
+        *filter.nub_tiers.entry(vote.nub_tier).or_default() += 1;
+
+        if let Some(pro_tier) = vote.pro_tier {
+            *filter.pro_tiers.entry(pro_tier).or_default() += 1;
+        }
+
+        filter.votes.push(vote);
+    }
+
+    Ok(filters)
+}
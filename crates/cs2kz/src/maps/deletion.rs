@@ -0,0 +1,228 @@
+//! Removing maps.
+//!
+//! Deleting a map removes its courses and filters, and with them every record, best record and
+//! point distribution set on them. Players who had ranked records on the map have their ratings
+//! recalculated as part of the same transaction. Because this cannot be undone, maps with records
+//! are only deleted if explicitly requested.
+//!
+//! Archiving is the non-destructive alternative: the map is hidden from listings, but everything
+//! else stays as it is.
+
+use super::{CourseFilterId, MapId, MapState};
+use crate::events::{self, Event};
+use crate::mode::Mode;
+use crate::time::Timestamp;
+use crate::{Context, database, players};
+
+/// The rows affected by deleting a map.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CascadeEffects {
+    pub courses: u64,
+    pub filters: u64,
+    pub records: u64,
+    pub best_nub_records: u64,
+    pub best_pro_records: u64,
+    pub distribution_data: u64,
+
+    /// Players whose ratings are recalculated because they had ranked records on the map.
+    pub rated_players: u64,
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum DeleteMapError {
+    #[display("map has {} records; deleting it requires `force`", _0.records)]
+    #[error(ignore)]
+    #[from(ignore)]
+    HasRecords(CascadeEffects),
+
+    #[display("{_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to archive map")]
+#[from(forward)]
+pub struct ArchiveMapError(database::Error);
+
+/// Deletes a map along with everything that belongs to it.
+///
+/// If the map has any records, this fails with [`DeleteMapError::HasRecords`] unless `force` is
+/// set. Returns [`None`] if the map does not exist.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn delete(
+    cx: &Context,
+    map_id: MapId,
+    force: bool,
+) -> Result<Option<CascadeEffects>, DeleteMapError> {
+    let deleted = cx
+        .database_transaction(async move |conn| {
+            let exists = sqlx::query!("SELECT id FROM Maps WHERE id = ? FOR UPDATE", map_id)
+                .fetch_optional(&mut *conn)
+                .await?
+                .is_some();
+
+            if !exists {
+                return Ok(None);
+            }
+
+            let effects = cascade_effects(&mut *conn, map_id).await?;
+
+            if effects.records > 0 && !force {
+                return Err(DeleteMapError::HasRecords(effects));
+            }
+
+            let ranked_filters = sqlx::query!(
+                "SELECT id AS `id: CourseFilterId`, mode AS `mode: Mode`
+             FROM RankedCourseFilters
+             WHERE map_id = ?",
+                map_id,
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+
+            if !ranked_filters.is_empty() {
+                // unrank the map first, so its records no longer count towards anyone's rating
+                sqlx::query!("UPDATE Maps SET state = ? WHERE id = ?", MapState::Invalid, map_id)
+                    .execute(&mut *conn)
+                    .await?;
+
+                for filter in ranked_filters {
+                    players::update_ratings_in(&mut *conn, filter.id, filter.mode).await?;
+                }
+            }
+
+            let filter_ids = sqlx::query_scalar!(
+                "SELECT cf.id AS `id: CourseFilterId`
+             FROM CourseFilters AS cf
+             JOIN Courses AS c ON c.id = cf.course_id
+             WHERE c.map_id = ?",
+                map_id,
+            )
+            .fetch_all(&mut *conn)
+            .await?;
+
+            // these don't cascade, so they have to be removed before the filters
+            for table in [
+                "BestNubRecords",
+                "BestProRecords",
+                "PointDistributionData",
+                "RecordCounts",
+                "FiltersToRecalculate",
+                "Records",
+            ] {
+                sqlx::query(&format!(
+                    "DELETE FROM {table}
+                 WHERE filter_id IN (
+                   SELECT cf.id
+                   FROM CourseFilters AS cf
+                   JOIN Courses AS c ON c.id = cf.course_id
+                   WHERE c.map_id = ?
+                 )"
+                ))
+                .bind(map_id)
+                .persistent(false)
+                .execute(&mut *conn)
+                .await?;
+            }
+
+            // keep the submission history around
+            sqlx::query!("UPDATE MapSubmissions SET map_id = NULL WHERE map_id = ?", map_id)
+                .execute(&mut *conn)
+                .await?;
+
+            sqlx::query!("DELETE FROM Maps WHERE id = ?", map_id)
+                .execute(&mut *conn)
+                .await?;
+
+            Ok(Some((effects, filter_ids)))
+        })
+        .await?;
+
+    let Some((effects, filter_ids)) = deleted else {
+        return Ok(None);
+    };
+
+    events::dispatch(Event::MapDeleted { map_id, filter_ids: filter_ids.into() });
+
+    Ok(Some(effects))
+}
+
+/// Hides a map from listings without deleting anything.
+///
+/// Returns when the map was archived, or [`None`] if it does not exist. Archiving a map that is
+/// already archived does not change anything.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn archive(cx: &Context, map_id: MapId) -> Result<Option<Timestamp>, ArchiveMapError> {
+    cx.database_transaction(async move |conn| {
+        sqlx::query!(
+            "UPDATE Maps SET archived_at = COALESCE(archived_at, NOW()) WHERE id = ?",
+            map_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query_scalar!(
+            "SELECT archived_at AS `archived_at!: Timestamp` FROM Maps WHERE id = ?",
+            map_id,
+        )
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(ArchiveMapError::from)
+    })
+    .await
+}
+
+async fn cascade_effects(
+    conn: &mut database::Connection,
+    map_id: MapId,
+) -> database::Result<CascadeEffects> {
+    let row = sqlx::query!(
+        "WITH Filters AS (
+           SELECT cf.id
+           FROM CourseFilters AS cf
+           JOIN Courses AS c ON c.id = cf.course_id
+           WHERE c.map_id = ?
+         )
+         SELECT
+           (SELECT COUNT(*) FROM Courses WHERE map_id = ?) AS `courses!`,
+           (SELECT COUNT(*) FROM Filters) AS `filters!`,
+           (SELECT COUNT(*)
+            FROM Records
+            WHERE filter_id IN (SELECT id FROM Filters)) AS `records!`,
+           (SELECT COUNT(*)
+            FROM BestNubRecords
+            WHERE filter_id IN (SELECT id FROM Filters)) AS `best_nub_records!`,
+           (SELECT COUNT(*)
+            FROM BestProRecords
+            WHERE filter_id IN (SELECT id FROM Filters)) AS `best_pro_records!`,
+           (SELECT COUNT(*)
+            FROM PointDistributionData
+            WHERE filter_id IN (SELECT id FROM Filters)) AS `distribution_data!`,
+           (SELECT COUNT(DISTINCT player_id)
+            FROM BestNubRecords
+            WHERE filter_id IN (SELECT id FROM RankedCourseFilters WHERE map_id = ?))
+             AS `rated_players!`",
+        map_id,
+        map_id,
+        map_id,
+    )
+    .fetch_one(&mut *conn)
+    .await?;
+
+    let count = |count: i64| -> u64 {
+        count
+            .try_into()
+            .expect("`COUNT(…)` should not return a negative value")
+    };
+
+    Ok(CascadeEffects {
+        courses: count(row.courses),
+        filters: count(row.filters),
+        records: count(row.records),
+        best_nub_records: count(row.best_nub_records),
+        best_pro_records: count(row.best_pro_records),
+        distribution_data: count(row.distribution_data),
+        rated_players: count(row.rated_players),
+    })
+}
@@ -3,11 +3,10 @@ use std::num::NonZero;
 use std::{array, iter};
 
 use futures_util::{Stream, StreamExt, TryFutureExt, TryStreamExt};
-use sqlx::Row;
+use sqlx::{FromRow, Row};
 
 use self::courses::filters::{CourseFilterState, Tier};
 use self::stream::{GetMapsStream, RawCourse, RawCourseFilters, RawMap};
-use crate::Context;
 use crate::database::{self, QueryBuilder};
 use crate::events::{self, Event};
 use crate::mode::Mode;
@@ -15,6 +14,7 @@ use crate::pagination::{Limit, Offset, Paginated};
 use crate::players::{PlayerId, PlayerInfo};
 use crate::steam::WorkshopId;
 use crate::time::Timestamp;
+use crate::{Context, points};
 
 mod state;
 pub use state::MapState;
@@ -24,7 +24,13 @@ pub use checksum::MapChecksum;
 
 mod stream;
 
+pub mod carry_over;
 pub mod courses;
+pub mod deletion;
+pub mod submissions;
+pub mod versions;
+pub mod workshop_metadata;
+pub mod workshop_updates;
 pub use courses::filters::CourseFilterId;
 pub use courses::{CourseId, CourseInfo};
 
@@ -88,15 +94,136 @@ pub struct CourseFilter {
     pub notes: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct GetMapsParams<'a> {
     pub workshop_id: Option<WorkshopId>,
     pub name: Option<&'a str>,
     pub state: Option<MapState>,
+
+    /// Only include maps which were made by this player.
+    ///
+    /// This includes maps on which the player only made some of the courses.
+    pub mapper: Option<PlayerId>,
+
+    /// Restrict `*_tier` and `filter_state` to filters for this mode.
+    ///
+    /// When sorting by tier, only filters for this mode are considered.
+    pub mode: Option<Mode>,
+
+    pub min_nub_tier: Option<Tier>,
+    pub max_nub_tier: Option<Tier>,
+    pub min_pro_tier: Option<Tier>,
+    pub max_pro_tier: Option<Tier>,
+
+    /// Only include maps with at least one filter in this state.
+    ///
+    /// Maps qualify if a single filter matches all of `mode`, the `*_tier` ranges, and this
+    /// state.
+    pub filter_state: Option<CourseFilterState>,
+
+    pub min_courses: Option<u16>,
+    pub max_courses: Option<u16>,
+
+    /// Only include maps approved after this point in time.
+    pub approved_after: Option<Timestamp>,
+
+    /// Only include maps approved before this point in time.
+    pub approved_before: Option<Timestamp>,
+
+    /// Which value to sort the results by.
+    ///
+    /// If this is `None`, maps are returned in the order they were approved in.
+    pub sort_by: Option<SortBy>,
+
+    /// Which direction to sort the results in.
+    ///
+    /// Defaults to 'ascending' if `sort_by` is `None`, 'name', 'nub-tier' or 'pro-tier'.
+    /// Defaults to 'descending' if `sort_by` is 'approval-date' or 'popularity'.
+    pub sort_order: Option<SortOrder>,
+
     pub limit: Limit<1000, 100>,
     pub offset: Offset,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortBy {
+    Name,
+
+    /// The highest NUB tier of any of the map's filters.
+    NubTier,
+
+    /// The highest PRO tier of any of the map's filters.
+    ProTier,
+
+    ApprovalDate,
+
+    /// The total amount of records submitted on the map.
+    Popularity,
+}
+
+impl SortBy {
+    /// Pushes the expression to sort by.
+    ///
+    /// This must be used in a context where `m` refers to the `Maps` table.
+    fn push_sql(&self, query: &mut QueryBuilder<'_>, mode: Option<Mode>) {
+        match self {
+            Self::Name => {
+                query.push(" m.name ");
+            },
+            Self::NubTier | Self::ProTier => {
+                query.push(match self {
+                    Self::NubTier => " (SELECT MAX(cf.nub_tier) ",
+                    _ => " (SELECT MAX(cf.pro_tier) ",
+                });
+                query.push(
+                    "FROM CourseFilters AS cf
+                     JOIN Courses AS c ON c.id = cf.course_id
+                     WHERE c.map_id = m.id
+                     AND cf.mode = COALESCE(",
+                );
+                query.push_bind(mode);
+                query.push(", cf.mode)) ");
+            },
+            Self::ApprovalDate => {
+                query.push(" m.approved_at ");
+            },
+            Self::Popularity => {
+                query.push(
+                    " (SELECT COUNT(*)
+                       FROM Records AS r
+                       JOIN CourseFilters AS cf ON cf.id = r.filter_id
+                       JOIN Courses AS c ON c.id = cf.course_id
+                       WHERE c.map_id = m.id) ",
+                );
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum SortOrder {
+    Ascending,
+    Descending,
+}
+
+impl SortOrder {
+    fn from_sort_by(sort_by: Option<SortBy>) -> Self {
+        match sort_by {
+            None | Some(SortBy::Name | SortBy::NubTier | SortBy::ProTier) => Self::Ascending,
+            Some(SortBy::ApprovalDate | SortBy::Popularity) => Self::Descending,
+        }
+    }
+
+    fn sql(&self) -> &'static str {
+        match self {
+            Self::Ascending => " ASC ",
+            Self::Descending => " DESC ",
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct NewMap {
     pub workshop_id: WorkshopId,
@@ -104,6 +231,10 @@ pub struct NewMap {
     pub description: Option<String>,
     pub state: MapState,
     pub vpk_checksum: MapChecksum,
+
+    /// What changed compared to the previous version of the map.
+    pub changelog: Option<String>,
+
     pub mappers: Box<[PlayerId]>,
     pub courses: Box<[NewCourse]>,
 }
@@ -114,6 +245,9 @@ pub struct NewCourse {
     pub description: Option<String>,
     pub mappers: Box<[PlayerId]>,
     pub filters: NewCourseFilters,
+
+    /// The name of the course on the previous version of the map this course is unchanged from.
+    pub previous_version: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -174,6 +308,11 @@ pub struct GetMapsError(database::Error);
 
 #[derive(Debug, Display, Error, From)]
 pub enum ApproveMapError {
+    #[display("the previous version of the map does not have a course named '{name}'")]
+    #[error(ignore)]
+    #[from(ignore)]
+    UnknownPreviousCourse { name: String },
+
     #[display("{_0}")]
     #[from(forward)]
     Database(database::Error),
@@ -197,26 +336,198 @@ pub enum UpdateMapError {
 #[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
 pub async fn get(
     cx: &Context,
-    GetMapsParams { workshop_id, name, state, limit, offset }: GetMapsParams<'_>,
+    params: GetMapsParams<'_>,
 ) -> Result<Paginated<impl Stream<Item = Result<Map, GetMapsError>>>, GetMapsError> {
-    let total = database::count!(cx.database().as_ref(), "Maps").await?;
-    let stream = self::macros::select!(
-        cx.database().as_ref(),
-        "WHERE m.workshop_id = COALESCE(?, m.workshop_id)
-         AND m.name LIKE COALESCE(?, m.name)
-         AND m.state = COALESCE(?, m.state)
-         LIMIT ?
-         OFFSET ?",
+    let GetMapsParams { mode, sort_by, sort_order, limit, offset, .. } = params;
+    let sort_order = sort_order.unwrap_or_else(|| SortOrder::from_sort_by(sort_by));
+
+    // counted separately so pages past the end still report the total
+    let mut count_query = QueryBuilder::new("SELECT COUNT(*) ");
+    push_matching_maps(&mut count_query, &params);
+
+    let total = count_query
+        .build_query_scalar::<i64>()
+        .fetch_one(cx.database().as_ref())
+        .await?
+        .try_into()
+        .expect("`COUNT(…)` should not return a negative value");
+
+    let mut query = QueryBuilder::new("WITH MatchingMaps AS (SELECT m.id, ");
+
+    match sort_by {
+        Some(sort_by) => sort_by.push_sql(&mut query, mode),
+        None => {
+            query.push(" m.id ");
+        },
+    }
+
+    query.push(" AS sort_key ");
+    push_matching_maps(&mut query, &params);
+
+    query.push(
+        "), Page AS (
+           SELECT
+             id,
+             ROW_NUMBER() OVER (ORDER BY sort_key",
+    );
+    query
+        .push(sort_order.sql())
+        .push(", id ")
+        .push(sort_order.sql());
+    query.push(
+        ") AS position
+           FROM MatchingMaps
+           ORDER BY position ASC
+           LIMIT ",
+    );
+    query.push_bind(limit.value());
+    query.push(" OFFSET ");
+    query.push_bind(offset.value());
+
+    // this has to select the same columns as `macros::select!()`
+    query.push(
+        ") SELECT
+           m.id,
+           m.workshop_id,
+           m.name,
+           m.description,
+           m.state,
+           m.vpk_checksum,
+           mapper.id AS mapper_id,
+           mapper.name AS mapper_name,
+           c.id AS course_id,
+           c.name AS course_name,
+           c.description AS course_description,
+           cmapper.id AS course_mapper_id,
+           cmapper.name AS course_mapper_name,
+           cf.id AS filter_id,
+           cf.mode AS filter_mode,
+           cf.nub_tier,
+           cf.pro_tier,
+           cf.state AS filter_state,
+           cf.notes AS filter_notes,
+           m.approved_at
+         FROM Page
+         JOIN Maps AS m ON m.id = Page.id
+         JOIN Mappers ON Mappers.map_id = m.id
+         JOIN Players AS mapper ON mapper.id = Mappers.player_id
+         JOIN Courses AS c ON c.map_id = m.id
+         JOIN CourseMappers ON CourseMappers.course_id = c.id
+         JOIN Players AS cmapper ON cmapper.id = CourseMappers.player_id
+         JOIN CourseFilters AS cf ON cf.course_id = c.id
+         ORDER BY Page.position ASC",
+    );
+
+    let rows = query.build().fetch_all(cx.database().as_ref()).await?;
+    let raw_maps = rows
+        .iter()
+        .map(|row| RawMap::from_row(row).map_err(database::Error::from))
+        .collect::<Vec<_>>();
+
+    Ok(Paginated::new(total, GetMapsStream::new(futures_util::stream::iter(raw_maps))))
+}
+
+/// Pushes the `FROM` and `WHERE` clauses selecting the maps that match `params`.
+fn push_matching_maps(
+    query: &mut QueryBuilder<'_>,
+    &GetMapsParams {
         workshop_id,
-        name.map(|name| format!("%{name}%")),
+        name,
         state,
-        limit.value(),
-        offset.value(),
-    )
-    .skip(offset.value().unsigned_abs() as usize)
-    .take(limit.value() as usize);
+        mapper,
+        mode,
+        min_nub_tier,
+        max_nub_tier,
+        min_pro_tier,
+        max_pro_tier,
+        filter_state,
+        min_courses,
+        max_courses,
+        approved_after,
+        approved_before,
+        ..
+    }: &GetMapsParams<'_>,
+) {
+    query.push(" FROM Maps AS m WHERE m.workshop_id = COALESCE(");
+    query.push_bind(workshop_id);
+    query.push(", m.workshop_id) ");
+
+    query.push(" AND m.name LIKE COALESCE(");
+    query.push_bind(name.map(|name| format!("%{name}%")));
+    query.push(", m.name) ");
+
+    query.push(" AND m.state = COALESCE(");
+    query.push_bind(state);
+    query.push(", m.state) ");
+
+    // archived maps are only reachable by their ID or name
+    query.push(" AND m.archived_at IS NULL ");
+
+    if let Some(mapper) = mapper {
+        query.push(
+            " AND (EXISTS (SELECT 1
+                           FROM Mappers
+                           WHERE Mappers.map_id = m.id
+                           AND Mappers.player_id = ",
+        );
+        query.push_bind(mapper);
+        query.push(
+            ") OR EXISTS (SELECT 1
+                          FROM CourseMappers
+                          JOIN Courses AS c ON c.id = CourseMappers.course_id
+                          WHERE c.map_id = m.id
+                          AND CourseMappers.player_id = ",
+        );
+        query.push_bind(mapper);
+        query.push(")) ");
+    }
 
-    Ok(Paginated::new(total, stream))
+    let has_filter_conditions = mode.is_some()
+        || min_nub_tier.is_some()
+        || max_nub_tier.is_some()
+        || min_pro_tier.is_some()
+        || max_pro_tier.is_some()
+        || filter_state.is_some();
+
+    if has_filter_conditions {
+        // all conditions have to be met by the same filter
+        query.push(
+            " AND EXISTS (SELECT 1
+                          FROM CourseFilters AS cf
+                          JOIN Courses AS c ON c.id = cf.course_id
+                          WHERE c.map_id = m.id
+                          AND cf.mode = COALESCE(",
+        );
+        query.push_bind(mode);
+        query.push(", cf.mode) AND cf.nub_tier >= COALESCE(");
+        query.push_bind(min_nub_tier);
+        query.push(", cf.nub_tier) AND cf.nub_tier <= COALESCE(");
+        query.push_bind(max_nub_tier);
+        query.push(", cf.nub_tier) AND cf.pro_tier >= COALESCE(");
+        query.push_bind(min_pro_tier);
+        query.push(", cf.pro_tier) AND cf.pro_tier <= COALESCE(");
+        query.push_bind(max_pro_tier);
+        query.push(", cf.pro_tier) AND cf.state = COALESCE(");
+        query.push_bind(filter_state);
+        query.push(", cf.state)) ");
+    }
+
+    if min_courses.is_some() || max_courses.is_some() {
+        query.push(" AND (SELECT COUNT(*) FROM Courses AS c WHERE c.map_id = m.id) BETWEEN ");
+        query.push_bind(min_courses.unwrap_or(0));
+        query.push(" AND ");
+        query.push_bind(max_courses.unwrap_or(u16::MAX));
+    }
+
+    if let Some(approved_after) = approved_after {
+        query.push(" AND m.approved_at > ");
+        query.push_bind(approved_after);
+    }
+
+    if let Some(approved_before) = approved_before {
+        query.push(" AND m.approved_at < ");
+        query.push_bind(approved_before);
+    }
 }
 
 #[tracing::instrument(skip(cx), err(level = "debug"))]
@@ -250,45 +561,82 @@ pub async fn get_course_id_by_name(
 }
 
 #[tracing::instrument(skip(cx), err(level = "debug"))]
-pub async fn approve(
-    cx: &Context,
+pub async fn approve(cx: &Context, map: NewMap) -> Result<MapId, ApproveMapError> {
+    cx.database_transaction(async move |conn| insert_new_map(conn, map).await)
+        .await
+        .map(|(map_id, unranked_filters)| {
+            if !unranked_filters.is_empty() {
+                points::daemon::request(cx, unranked_filters);
+            }
+
+            map_id
+        })
+}
+
+/// Inserts a new map as the next [version] of any existing map with the same name, invalidating
+/// the older versions.
+///
+/// Returns the new map's ID, as well as the filters that were unranked by invalidating the old
+/// versions. The caller is responsible for requesting a points recalculation for those once the
+/// transaction has been committed.
+///
+/// [version]: versions
+pub(crate) async fn insert_new_map(
+    conn: &mut database::Connection,
     NewMap {
         workshop_id,
         name,
         description,
         state,
         vpk_checksum,
+        changelog,
         mappers,
         courses,
     }: NewMap,
-) -> Result<MapId, ApproveMapError> {
-    cx.database_transaction(async move |conn| {
-        match invalidate_old_map_rows(&mut *conn, &name).await? {
-            0 => info!("approving new map '{name}'"),
-            1 => info!("invalidated old version of '{name}'"),
-            amount => warn!(amount, "invalidated multiple old versions of '{name}'"),
-        }
+) -> Result<(MapId, Vec<CourseFilterId>), ApproveMapError> {
+    let previous_version = versions::get_latest(&mut *conn, &name).await?;
 
-        let map_id =
-            insert_map(&mut *conn, workshop_id, &name, description.as_deref(), state, vpk_checksum)
-                .await?;
-
-        insert_mappers(&mut *conn, map_id, &mappers).await?;
-        insert_courses(&mut *conn, map_id, &courses).await?;
+    // invalidating old versions of the map unranks all of their filters
+    let unranked_filters = get_ranked_filters_by_map_name(&mut *conn, &name).await?;
 
-        events::dispatch(Event::NewMap {
-            workshop_id,
-            name,
-            description,
-            state,
-            vpk_checksum,
-            mappers,
-            courses,
-        });
+    match invalidate_old_map_rows(&mut *conn, &name).await? {
+        0 => info!("approving new map '{name}'"),
+        1 => info!("invalidated old version of '{name}'"),
+        amount => warn!(amount, "invalidated multiple old versions of '{name}'"),
+    }
 
-        Ok(map_id)
+    let map_id = insert_map(&mut *conn, NewMapRow {
+        workshop_id,
+        name: &name,
+        description: description.as_deref(),
+        state,
+        vpk_checksum,
+        version: previous_version
+            .as_ref()
+            .map_or(1, |previous_version| previous_version.version + 1),
+        previous_version_id: previous_version
+            .as_ref()
+            .map(|previous_version| previous_version.map_id),
+        changelog: changelog.as_deref(),
     })
-    .await
+    .await?;
+
+    insert_mappers(&mut *conn, map_id, &mappers).await?;
+    insert_courses(&mut *conn, map_id, &courses).await?;
+    versions::link_courses(&mut *conn, map_id, previous_version.as_ref(), &courses).await?;
+    workshop_updates::resolve(&mut *conn, workshop_id).await?;
+
+    events::dispatch(Event::NewMap {
+        workshop_id,
+        name,
+        description,
+        state,
+        vpk_checksum,
+        mappers,
+        courses,
+    });
+
+    Ok((map_id, unranked_filters))
 }
 
 #[tracing::instrument(skip(cx), err(level = "debug"))]
@@ -307,6 +655,7 @@ pub async fn update(
     }: MapUpdate<'_>,
 ) -> Result<bool, UpdateMapError> {
     cx.database_transaction(async move |conn| {
+        let ranked_before = get_ranked_filters(&mut *conn, id).await?;
         let updated = sqlx::query!(
             "UPDATE Maps
              SET workshop_id = COALESCE(?, workshop_id),
@@ -327,7 +676,7 @@ pub async fn update(
         .map(|result| result.rows_affected() > 0)?;
 
         if !updated {
-            return Ok(false);
+            return Ok(None);
         }
 
         insert_mappers(&mut *conn, id, added_mappers).await?;
@@ -340,7 +689,8 @@ pub async fn update(
         }
 
         if course_updates.is_empty() {
-            return Ok(true);
+            let ranking_changes = get_ranking_changes(&mut *conn, id, ranked_before).await?;
+            return Ok(Some(ranking_changes));
         }
 
         let course_ids =
@@ -396,9 +746,75 @@ pub async fn update(
             }
         }
 
-        Ok(true)
+        let ranking_changes = get_ranking_changes(&mut *conn, id, ranked_before).await?;
+
+        Ok(Some(ranking_changes))
+    })
+    .await
+    .map(|ranking_changes| {
+        let Some(ranking_changes) = ranking_changes else {
+            return false;
+        };
+
+        // filters that became (un)ranked need their points recalculated
+        if !ranking_changes.is_empty() {
+            points::daemon::request(cx, ranking_changes);
+        }
+
+        true
     })
+}
+
+/// Returns the IDs of all [ranked] filters on a map.
+///
+/// [ranked]: courses::filters::is_ranked
+#[tracing::instrument(level = "debug", skip(conn), ret(level = "debug"), err(level = "debug"))]
+async fn get_ranked_filters(
+    conn: &mut database::Connection,
+    map_id: MapId,
+) -> database::Result<BTreeSet<CourseFilterId>> {
+    sqlx::query_scalar::<_, CourseFilterId>("SELECT id FROM RankedCourseFilters WHERE map_id = ?")
+        .bind(map_id)
+        .fetch(conn)
+        .try_collect()
+        .await
+        .map_err(database::Error::from)
+}
+
+/// Returns the filters on a map which were ranked before an update (`ranked_before`), but are
+/// no longer ranked now, or vice versa.
+#[tracing::instrument(level = "debug", skip(conn), ret(level = "debug"), err(level = "debug"))]
+async fn get_ranking_changes(
+    conn: &mut database::Connection,
+    map_id: MapId,
+    ranked_before: BTreeSet<CourseFilterId>,
+) -> database::Result<Vec<CourseFilterId>> {
+    let ranked_after = get_ranked_filters(conn, map_id).await?;
+
+    Ok(ranked_before
+        .symmetric_difference(&ranked_after)
+        .copied()
+        .collect())
+}
+
+/// Returns the IDs of all [ranked] filters on any map with the given name.
+///
+/// [ranked]: courses::filters::is_ranked
+#[tracing::instrument(level = "debug", skip(conn), ret(level = "debug"), err(level = "debug"))]
+async fn get_ranked_filters_by_map_name(
+    conn: &mut database::Connection,
+    name: &str,
+) -> database::Result<Vec<CourseFilterId>> {
+    sqlx::query_scalar!(
+        "SELECT cf.id AS `id: CourseFilterId`
+         FROM RankedCourseFilters AS cf
+         JOIN Maps AS m ON m.id = cf.map_id
+         WHERE m.name = ?",
+        name,
+    )
+    .fetch_all(conn)
     .await
+    .map_err(database::Error::from)
 }
 
 #[tracing::instrument(level = "debug", skip(conn), ret(level = "debug"), err(level = "debug"))]
@@ -413,24 +829,45 @@ async fn invalidate_old_map_rows(
         .map_err(database::Error::from)
 }
 
-#[tracing::instrument(level = "debug", skip(conn), ret(level = "debug"), err(level = "debug"))]
-async fn insert_map(
-    conn: &mut database::Connection,
+/// The columns of a new row in the `Maps` table.
+#[derive(Debug)]
+struct NewMapRow<'a> {
     workshop_id: WorkshopId,
-    name: &str,
-    description: Option<&str>,
+    name: &'a str,
+    description: Option<&'a str>,
     state: MapState,
     vpk_checksum: MapChecksum,
+    version: u16,
+    previous_version_id: Option<MapId>,
+    changelog: Option<&'a str>,
+}
+
+#[tracing::instrument(level = "debug", skip(conn), ret(level = "debug"), err(level = "debug"))]
+async fn insert_map(
+    conn: &mut database::Connection,
+    row: NewMapRow<'_>,
 ) -> database::Result<MapId> {
     sqlx::query!(
-        "INSERT INTO Maps (workshop_id, name, description, state, vpk_checksum)
-        VALUES (?, ?, ?, ?, ?)
-        RETURNING id",
-        workshop_id,
-        name,
-        description,
-        state,
-        vpk_checksum,
+        "INSERT INTO Maps (
+           workshop_id,
+           name,
+           description,
+           state,
+           vpk_checksum,
+           version,
+           previous_version_id,
+           changelog
+         )
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         RETURNING id",
+        row.workshop_id,
+        row.name,
+        row.description,
+        row.state,
+        row.vpk_checksum,
+        row.version,
+        row.previous_version_id,
+        row.changelog,
     )
     .fetch_one(conn)
     .await
@@ -13,11 +13,13 @@ use std::pin::Pin;
 use std::task::{Poll, ready};
 
 use futures_util::Stream;
+use sqlx::Row;
 
 use crate::database;
 use crate::maps::{
     Course,
     CourseFilter,
+    CourseFilterState,
     CourseFilters,
     CourseId,
     GetMapsError,
@@ -25,7 +27,9 @@ use crate::maps::{
     MapChecksum,
     MapId,
     MapState,
+    Tier,
 };
+use crate::mode::Mode;
 use crate::players::PlayerInfo;
 use crate::steam::WorkshopId;
 use crate::time::Timestamp;
@@ -118,6 +122,47 @@ impl<S: Stream<Item = database::Result<RawMap>>> Stream for GetMapsStream<S> {
     }
 }
 
+/// Parses a single row of a query selecting the same columns as [`super::macros::select!()`].
+impl<'r> sqlx::FromRow<'r, database::Row> for RawMap {
+    fn from_row(row: &'r database::Row) -> sqlx::Result<Self> {
+        let course_id = row.try_get("course_id")?;
+        let filter = CourseFilter {
+            id: row.try_get("filter_id")?,
+            nub_tier: row.try_get::<Tier, _>("nub_tier")?,
+            pro_tier: row.try_get::<Tier, _>("pro_tier")?,
+            state: row.try_get::<CourseFilterState, _>("filter_state")?,
+            notes: row.try_get("filter_notes")?,
+        };
+
+        Ok(Self {
+            id: row.try_get("id")?,
+            workshop_id: row.try_get("workshop_id")?,
+            name: row.try_get("name")?,
+            description: row.try_get("description")?,
+            state: row.try_get("state")?,
+            vpk_checksum: row.try_get("vpk_checksum")?,
+            mappers: BTreeSet::from_iter([PlayerInfo {
+                id: row.try_get("mapper_id")?,
+                name: row.try_get("mapper_name")?,
+            }]),
+            courses: BTreeMap::from_iter([(course_id, RawCourse {
+                id: course_id,
+                name: row.try_get("course_name")?,
+                description: row.try_get("course_description")?,
+                mappers: BTreeSet::from_iter([PlayerInfo {
+                    id: row.try_get("course_mapper_id")?,
+                    name: row.try_get("course_mapper_name")?,
+                }]),
+                filters: match row.try_get::<Mode, _>("filter_mode")? {
+                    Mode::Vanilla => RawCourseFilters { vanilla: Some(filter), classic: None },
+                    Mode::Classic => RawCourseFilters { vanilla: None, classic: Some(filter) },
+                },
+            })]),
+            approved_at: row.try_get("approved_at")?,
+        })
+    }
+}
+
 impl From<RawMap> for Map {
     fn from(raw: RawMap) -> Self {
         Self {
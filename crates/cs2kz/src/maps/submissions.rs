@@ -0,0 +1,714 @@
+//! Map submissions.
+//!
+//! Instead of having an admin enter every map, mappers [`submit()`] their own maps for review.
+//! Reviewers then move submissions through the different [`SubmissionState`]s with [`review()`],
+//! leaving a comment every time. Moving a submission into testing or approving it with
+//! [`approve()`] turns it into a [`NewMap`]; later reviews only change that map's state.
+
+use std::cmp;
+use std::num::NonZero;
+
+use futures_util::{Stream, TryStreamExt};
+use sqlx::Row as _;
+use sqlx::types::Json as SqlJson;
+
+use super::courses::filters::{CourseFilterState, Tier};
+use super::{
+    ApproveMapError,
+    MapChecksum,
+    MapId,
+    MapState,
+    NewCourse,
+    NewCourseFilter,
+    NewCourseFilters,
+    NewMap,
+};
+use crate::pagination::{Limit, Offset, Paginated};
+use crate::players::PlayerId;
+use crate::steam::WorkshopId;
+use crate::time::Timestamp;
+use crate::users::{UserId, UserInfo};
+use crate::{Context, database, points};
+
+define_id_type! {
+    /// A unique identifier for map submissions.
+    #[derive(sqlx::Type)]
+    #[sqlx(transparent)]
+    pub struct SubmissionId(NonZero<u32>);
+}
+
+/// The different states a submission can be in.
+#[repr(u8)]
+#[derive(
+    Debug,
+    Display,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    sqlx::Type,
+    serde::Serialize,
+    serde::Deserialize
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum SubmissionState {
+    /// The submission is waiting for a reviewer.
+    #[display("submitted")]
+    Submitted = 0,
+
+    /// A reviewer asked the mapper to make changes before the submission can move on.
+    #[display("changes-requested")]
+    ChangesRequested = 1,
+
+    /// The map is being tested by reviewers.
+    #[display("in-testing")]
+    InTesting = 2,
+
+    /// The submission has been approved and turned into a map.
+    #[display("approved")]
+    Approved = 3,
+
+    /// The submission has been rejected.
+    #[display("rejected")]
+    Rejected = 4,
+}
+
+impl SubmissionState {
+    /// Whether the submission has been approved or rejected, and can no longer change.
+    pub fn is_final(self) -> bool {
+        matches!(self, Self::Approved | Self::Rejected)
+    }
+
+    /// Whether a reviewer may move a submission from this state into `new_state`.
+    ///
+    /// Only the mapper can move a submission back into [`Submitted`], by updating it.
+    ///
+    /// [`Submitted`]: SubmissionState::Submitted
+    pub fn can_transition_to(self, new_state: Self) -> bool {
+        !self.is_final() && new_state != Self::Submitted && new_state != self
+    }
+
+    /// Whether moving a submission into this state requires its map to exist.
+    pub fn requires_map(self) -> bool {
+        matches!(self, Self::InTesting | Self::Approved)
+    }
+}
+
+#[derive(Debug)]
+pub struct Submission {
+    pub id: SubmissionId,
+    pub workshop_id: WorkshopId,
+    pub submitted_by: UserInfo,
+    pub description: Option<String>,
+    pub state: SubmissionState,
+    pub mappers: Vec<PlayerId>,
+    pub courses: Vec<SubmittedCourse>,
+
+    /// The map this submission turned into, if it has been moved into testing or approved.
+    pub map_id: Option<MapId>,
+
+    pub submitted_at: Timestamp,
+    pub updated_at: Timestamp,
+}
+
+/// A course as proposed by the mapper.
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+pub struct SubmittedCourse {
+    pub name: String,
+    pub description: Option<String>,
+    pub mappers: Vec<PlayerId>,
+    pub vanilla: ProposedTiers,
+    pub classic: ProposedTiers,
+}
+
+#[derive(Debug, Clone, Copy, serde::Serialize, serde::Deserialize)]
+pub struct ProposedTiers {
+    pub nub_tier: Tier,
+    pub pro_tier: Tier,
+}
+
+/// A reviewer's comment on a submission.
+#[derive(Debug)]
+pub struct Review {
+    pub reviewer: UserInfo,
+
+    /// The state the reviewer moved the submission into.
+    pub state: SubmissionState,
+
+    pub comment: String,
+    pub created_at: Timestamp,
+}
+
+#[derive(Debug)]
+pub struct GetSubmissionsParams {
+    pub state: Option<SubmissionState>,
+    pub submitted_by: Option<UserId>,
+    pub limit: Limit<1000, 100>,
+    pub offset: Offset,
+}
+
+#[derive(Debug)]
+pub struct NewSubmission {
+    pub workshop_id: WorkshopId,
+    pub submitted_by: UserId,
+    pub description: Option<String>,
+    pub mappers: Vec<PlayerId>,
+    pub courses: Vec<SubmittedCourse>,
+}
+
+/// A mapper's revision of their submission.
+///
+/// This replaces the submission's contents and moves it back into
+/// [`SubmissionState::Submitted`].
+#[derive(Debug)]
+pub struct SubmissionUpdate {
+    pub id: SubmissionId,
+    pub updated_by: UserId,
+    pub workshop_id: WorkshopId,
+    pub description: Option<String>,
+    pub mappers: Vec<PlayerId>,
+    pub courses: Vec<SubmittedCourse>,
+}
+
+#[derive(Debug)]
+pub struct NewReview<'a> {
+    pub submission_id: SubmissionId,
+    pub reviewer_id: UserId,
+    pub state: SubmissionState,
+    pub comment: &'a str,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to get map submissions")]
+#[from(forward)]
+pub struct GetSubmissionsError(database::Error);
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to submit map")]
+#[from(forward)]
+pub struct SubmitMapError(database::Error);
+
+#[derive(Debug, Display, Error, From)]
+pub enum UpdateSubmissionError {
+    #[display("only the mapper who submitted a map can update it")]
+    NotSubmitter,
+
+    #[display("submission can no longer be updated because it is {state}")]
+    #[error(ignore)]
+    #[from(ignore)]
+    AlreadyFinal { state: SubmissionState },
+
+    /// The submission's map has already been created, so its contents are set in stone.
+    #[display("submission can no longer be updated because its map has already been created")]
+    #[error(ignore)]
+    #[from(ignore)]
+    AlreadyHasMap { state: SubmissionState },
+
+    #[display("{_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum ReviewSubmissionError {
+    #[display("cannot move submission from {from} to {to}")]
+    #[error(ignore)]
+    #[from(ignore)]
+    InvalidTransition { from: SubmissionState, to: SubmissionState },
+
+    /// Creating the submission's map has to go through [`approve()`].
+    #[display("submissions have to be approved separately")]
+    MustApproveSeparately,
+
+    #[display("{_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn get(
+    cx: &Context,
+    GetSubmissionsParams { state, submitted_by, limit, offset }: GetSubmissionsParams,
+) -> Result<
+    Paginated<impl Stream<Item = Result<Submission, GetSubmissionsError>>>,
+    GetSubmissionsError,
+> {
+    let total = database::count!(
+        cx.database().as_ref(),
+        "MapSubmissions AS s
+         WHERE s.state = COALESCE(?, s.state)
+         AND s.submitted_by = COALESCE(?, s.submitted_by)",
+        state,
+        submitted_by,
+    )
+    .await?;
+
+    let submissions = self::macros::select!(
+        "WHERE s.state = COALESCE(?, s.state)
+         AND s.submitted_by = COALESCE(?, s.submitted_by)
+         ORDER BY s.id DESC
+         LIMIT ?
+         OFFSET ?",
+        state,
+        submitted_by,
+        limit.value(),
+        offset.value(),
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| self::macros::parse_row!(row))
+    .map_err(GetSubmissionsError::from);
+
+    Ok(Paginated::new(total, submissions))
+}
+
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_by_id(
+    cx: &Context,
+    submission_id: SubmissionId,
+) -> Result<Option<Submission>, GetSubmissionsError> {
+    self::macros::select!("WHERE s.id = ?", submission_id)
+        .fetch_optional(cx.database().as_ref())
+        .await
+        .map(|row| row.map(|row| self::macros::parse_row!(row)))
+        .map_err(GetSubmissionsError::from)
+}
+
+/// Returns every review left on a submission, oldest first.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_reviews(
+    cx: &Context,
+    submission_id: SubmissionId,
+) -> Result<Vec<Review>, GetSubmissionsError> {
+    sqlx::query!(
+        "SELECT
+           u.id AS `reviewer_id: UserId`,
+           u.name AS reviewer_name,
+           r.state AS `state: SubmissionState`,
+           r.comment,
+           r.created_at
+         FROM MapSubmissionReviews AS r
+         JOIN Users AS u ON u.id = r.reviewer_id
+         WHERE r.submission_id = ?
+         ORDER BY r.id ASC",
+        submission_id,
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| Review {
+        reviewer: UserInfo { id: row.reviewer_id, name: row.reviewer_name },
+        state: row.state,
+        comment: row.comment,
+        created_at: row.created_at.into(),
+    })
+    .map_err(GetSubmissionsError::from)
+    .try_collect()
+    .await
+}
+
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn submit(
+    cx: &Context,
+    NewSubmission {
+        workshop_id,
+        submitted_by,
+        description,
+        mappers,
+        courses,
+    }: NewSubmission,
+) -> Result<SubmissionId, SubmitMapError> {
+    sqlx::query!(
+        "INSERT INTO MapSubmissions (workshop_id, submitted_by, description, mappers, courses)
+         VALUES (?, ?, ?, ?, ?)
+         RETURNING id",
+        workshop_id,
+        submitted_by,
+        description,
+        SqlJson(mappers),
+        SqlJson(courses),
+    )
+    .fetch_one(cx.database().as_ref())
+    .await
+    .and_then(|row| row.try_get(0))
+    .map_err(SubmitMapError::from)
+}
+
+/// Replaces a submission's contents and moves it back into [`SubmissionState::Submitted`].
+///
+/// This is only possible until the submission's map has been created.
+///
+/// Returns whether the submission exists.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn update(
+    cx: &Context,
+    SubmissionUpdate {
+        id,
+        updated_by,
+        workshop_id,
+        description,
+        mappers,
+        courses,
+    }: SubmissionUpdate,
+) -> Result<bool, UpdateSubmissionError> {
+    cx.database_transaction(async move |conn| {
+        let Some((submitted_by, state, map_id)) = lock_submission(&mut *conn, id).await? else {
+            return Ok(false);
+        };
+
+        if submitted_by != updated_by {
+            return Err(UpdateSubmissionError::NotSubmitter);
+        }
+
+        if state.is_final() {
+            return Err(UpdateSubmissionError::AlreadyFinal { state });
+        }
+
+        if map_id.is_some() {
+            return Err(UpdateSubmissionError::AlreadyHasMap { state });
+        }
+
+        sqlx::query!(
+            "UPDATE MapSubmissions
+             SET workshop_id = ?,
+                 description = ?,
+                 mappers = ?,
+                 courses = ?,
+                 state = ?,
+                 updated_at = CURRENT_TIMESTAMP
+             WHERE id = ?",
+            workshop_id,
+            description,
+            SqlJson(mappers),
+            SqlJson(courses),
+            SubmissionState::Submitted,
+            id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(true)
+    })
+    .await
+}
+
+/// Moves a submission into a new state, leaving a comment.
+///
+/// Moving a submission into a state that [requires a map] creates that map, which needs
+/// additional information about it, so it has to go through [`approve()`] instead. Once the map
+/// exists, this moves it along with the submission: approving the submission approves the map,
+/// and rejecting it invalidates the map. Filters that become (un)ranked because of this are
+/// queued for recalculation.
+///
+/// Returns whether the submission exists.
+///
+/// [requires a map]: SubmissionState::requires_map
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn review(cx: &Context, review: NewReview<'_>) -> Result<bool, ReviewSubmissionError> {
+    cx.database_transaction(async move |conn| {
+        let Some((_, state, map_id)) = lock_submission(&mut *conn, review.submission_id).await?
+        else {
+            return Ok(None);
+        };
+
+        if !state.can_transition_to(review.state) {
+            return Err(ReviewSubmissionError::InvalidTransition { from: state, to: review.state });
+        }
+
+        let map_state = match (map_id, review.state) {
+            (None, new_state) if new_state.requires_map() => {
+                return Err(ReviewSubmissionError::MustApproveSeparately);
+            },
+            (None, _) => None,
+            (Some(_), SubmissionState::Approved) => Some(MapState::Approved),
+            (Some(_), SubmissionState::InTesting) => Some(MapState::InTesting),
+            (Some(_), SubmissionState::Rejected) => Some(MapState::Invalid),
+            (Some(_), SubmissionState::Submitted | SubmissionState::ChangesRequested) => None,
+        };
+
+        let ranking_changes = match (map_id, map_state) {
+            (Some(map_id), Some(map_state)) => {
+                let ranked_before = super::get_ranked_filters(&mut *conn, map_id).await?;
+
+                sqlx::query!("UPDATE Maps SET state = ? WHERE id = ?", map_state, map_id)
+                    .execute(&mut *conn)
+                    .await?;
+
+                super::get_ranking_changes(&mut *conn, map_id, ranked_before).await?
+            },
+            _ => Vec::new(),
+        };
+
+        set_state(&mut *conn, &review, None).await?;
+
+        Ok(Some(ranking_changes))
+    })
+    .await
+    .map(|ranking_changes| {
+        let Some(ranking_changes) = ranking_changes else {
+            return false;
+        };
+
+        // filters that became (un)ranked need their points recalculated
+        if !ranking_changes.is_empty() {
+            points::daemon::request(cx, ranking_changes);
+        }
+
+        true
+    })
+}
+
+/// Moves a submission into testing or approves it, turning it into a new map.
+///
+/// The map starts out in the matching [`MapState`]. `name` and `vpk_checksum` are taken from the
+/// map's current workshop version. Every filter whose lowest proposed tier is humanly possible
+/// starts out ranked.
+///
+/// Returns the new map's ID, or [`None`] if the submission does not exist.
+///
+/// # Panics
+///
+/// This function will panic if `review.state` does not [require a map].
+///
+/// [require a map]: SubmissionState::requires_map
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn approve(
+    cx: &Context,
+    review: NewReview<'_>,
+    name: String,
+    vpk_checksum: MapChecksum,
+) -> Result<Option<MapId>, ReviewSubmissionError> {
+    assert!(review.state.requires_map(), "`approve()` called with {}", review.state);
+
+    cx.database_transaction(async move |conn| {
+        let Some((_, state, map_id)) = lock_submission(&mut *conn, review.submission_id).await?
+        else {
+            return Ok(None);
+        };
+
+        // A submission only ever turns into one map; once it exists, reviews go through
+        // `review()`.
+        if !state.can_transition_to(review.state) || map_id.is_some() {
+            return Err(ReviewSubmissionError::InvalidTransition { from: state, to: review.state });
+        }
+
+        let submission = sqlx::query!(
+            "SELECT
+               workshop_id AS `workshop_id: WorkshopId`,
+               description,
+               mappers AS `mappers: SqlJson<Vec<PlayerId>>`,
+               courses AS `courses: SqlJson<Vec<SubmittedCourse>>`
+             FROM MapSubmissions
+             WHERE id = ?",
+            review.submission_id,
+        )
+        .fetch_one(&mut *conn)
+        .await?;
+
+        let map = NewMap {
+            workshop_id: submission.workshop_id,
+            name,
+            description: submission.description,
+            state: match review.state {
+                SubmissionState::InTesting => MapState::InTesting,
+                _ => MapState::Approved,
+            },
+            vpk_checksum,
+            changelog: None,
+            mappers: submission.mappers.0.into_boxed_slice(),
+            courses: submission
+                .courses
+                .0
+                .into_iter()
+                .map(NewCourse::from)
+                .collect(),
+        };
+
+        let (map_id, unranked_filters) = match super::insert_new_map(&mut *conn, map).await {
+            Ok(inserted) => inserted,
+            Err(ApproveMapError::UnknownPreviousCourse { .. }) => {
+                unreachable!("submitted courses are never linked to previous versions")
+            },
+            Err(ApproveMapError::Database(error)) => return Err(error.into()),
+        };
+
+        set_state(&mut *conn, &review, Some(map_id)).await?;
+
+        Ok(Some((map_id, unranked_filters)))
+    })
+    .await
+    .map(|approved| {
+        approved.map(|(map_id, unranked_filters)| {
+            if !unranked_filters.is_empty() {
+                points::daemon::request(cx, unranked_filters);
+            }
+
+            map_id
+        })
+    })
+}
+
+impl From<SubmittedCourse> for NewCourse {
+    fn from(course: SubmittedCourse) -> Self {
+        Self {
+            name: course.name,
+            description: course.description,
+            mappers: course.mappers.into_boxed_slice(),
+            filters: NewCourseFilters {
+                vanilla: course.vanilla.into(),
+                classic: course.classic.into(),
+            },
+            previous_version: None,
+        }
+    }
+}
+
+impl From<ProposedTiers> for NewCourseFilter {
+    fn from(ProposedTiers { nub_tier, pro_tier }: ProposedTiers) -> Self {
+        Self {
+            nub_tier,
+            pro_tier,
+            state: if cmp::min(nub_tier, pro_tier).is_humanly_possible() {
+                CourseFilterState::Ranked
+            } else {
+                CourseFilterState::Unranked
+            },
+            notes: None,
+        }
+    }
+}
+
+/// Locks a submission's row for the rest of the transaction and returns who submitted it, which
+/// state it is in, and the map it turned into.
+async fn lock_submission(
+    conn: &mut database::Connection,
+    submission_id: SubmissionId,
+) -> database::Result<Option<(UserId, SubmissionState, Option<MapId>)>> {
+    sqlx::query!(
+        "SELECT
+           submitted_by AS `submitted_by: UserId`,
+           state AS `state: SubmissionState`,
+           map_id AS `map_id: MapId`
+         FROM MapSubmissions
+         WHERE id = ?
+         FOR UPDATE",
+        submission_id,
+    )
+    .fetch_optional(conn)
+    .await
+    .map(|row| row.map(|row| (row.submitted_by, row.state, row.map_id)))
+    .map_err(database::Error::from)
+}
+
+async fn set_state(
+    conn: &mut database::Connection,
+    review: &NewReview<'_>,
+    map_id: Option<MapId>,
+) -> database::Result<()> {
+    sqlx::query!(
+        "UPDATE MapSubmissions
+         SET state = ?,
+             map_id = COALESCE(?, map_id),
+             updated_at = CURRENT_TIMESTAMP
+         WHERE id = ?",
+        review.state,
+        map_id,
+        review.submission_id,
+    )
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query!(
+        "INSERT INTO MapSubmissionReviews (submission_id, reviewer_id, state, comment)
+         VALUES (?, ?, ?, ?)",
+        review.submission_id,
+        review.reviewer_id,
+        review.state,
+        review.comment,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
+
+mod macros {
+    macro_rules! select {
+        ( $($extra:tt)* ) => {
+            sqlx::query!(
+                "SELECT
+                   s.id AS `id: SubmissionId`,
+                   s.workshop_id AS `workshop_id: WorkshopId`,
+                   u.id AS `submitted_by_id: UserId`,
+                   u.name AS submitted_by_name,
+                   s.description,
+                   s.state AS `state: SubmissionState`,
+                   s.mappers AS `mappers: SqlJson<Vec<PlayerId>>`,
+                   s.courses AS `courses: SqlJson<Vec<SubmittedCourse>>`,
+                   s.map_id AS `map_id: MapId`,
+                   s.submitted_at,
+                   s.updated_at
+                 FROM MapSubmissions AS s
+                 JOIN Users AS u ON u.id = s.submitted_by "
+                + $($extra)*
+            )
+        };
+    }
+
+    macro_rules! parse_row {
+        ($row:expr) => {
+            Submission {
+                id: $row.id,
+                workshop_id: $row.workshop_id,
+                submitted_by: UserInfo {
+                    id: $row.submitted_by_id,
+                    name: $row.submitted_by_name,
+                },
+                description: $row.description,
+                state: $row.state,
+                mappers: $row.mappers.0,
+                courses: $row.courses.0,
+                map_id: $row.map_id,
+                submitted_at: $row.submitted_at.into(),
+                updated_at: $row.updated_at.into(),
+            }
+        };
+    }
+
+    pub(super) use {parse_row, select};
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SubmissionState::{self, *};
+
+    const STATES: [SubmissionState; 5] = [
+        Submitted,
+        ChangesRequested,
+        InTesting,
+        Approved,
+        Rejected,
+    ];
+
+    #[test]
+    fn final_states_cannot_transition() {
+        for from in [Approved, Rejected] {
+            for to in STATES {
+                assert!(!from.can_transition_to(to), "{from} -> {to}");
+            }
+        }
+    }
+
+    #[test]
+    fn reviewers_cannot_resubmit() {
+        for from in STATES {
+            assert!(!from.can_transition_to(Submitted), "{from} -> submitted");
+        }
+    }
+
+    #[test]
+    fn open_submissions_can_move_forward() {
+        for from in [Submitted, ChangesRequested, InTesting] {
+            for to in [ChangesRequested, InTesting, Approved, Rejected] {
+                assert_eq!(from.can_transition_to(to), from != to, "{from} -> {to}");
+            }
+        }
+    }
+}
@@ -0,0 +1,207 @@
+//! Explicit versioning of maps.
+//!
+//! Approving a map with the same name as an existing one creates a new version of it: the new map
+//! points at the previous one, gets the next version number, and the previous version becomes
+//! [invalid]. Courses on the new version can be linked to the course on the previous version they
+//! are unchanged from, so their records can be carried over.
+//!
+//! [invalid]: MapState::Invalid
+
+use std::collections::HashMap;
+
+use futures_util::TryStreamExt;
+use sqlx::Row as _;
+
+use super::{ApproveMapError, CourseId, GetMapsError, MapChecksum, MapId, MapState, NewCourse};
+use crate::steam::WorkshopId;
+use crate::time::Timestamp;
+use crate::{Context, database};
+
+/// A single version of a map.
+#[derive(Debug, serde::Serialize)]
+pub struct MapVersion {
+    pub map_id: MapId,
+
+    /// The version number, starting at 1.
+    pub version: u16,
+
+    pub workshop_id: WorkshopId,
+    pub name: String,
+    pub state: MapState,
+    pub vpk_checksum: MapChecksum,
+
+    /// What changed compared to the previous version.
+    pub changelog: Option<String>,
+
+    pub previous_version: Option<MapId>,
+    pub next_version: Option<MapId>,
+    pub courses: Vec<CourseVersion>,
+    pub approved_at: Timestamp,
+}
+
+/// A course on a specific [`MapVersion`].
+#[derive(Debug, serde::Serialize)]
+pub struct CourseVersion {
+    pub id: CourseId,
+    pub name: String,
+
+    /// The course on the previous map version this course is unchanged from.
+    pub previous_version: Option<CourseId>,
+}
+
+/// The latest existing version of a map, which a new map will be approved as the successor of.
+#[derive(Debug)]
+pub(super) struct PreviousVersion {
+    pub(super) map_id: MapId,
+    pub(super) version: u16,
+}
+
+/// Returns every version of the given map, newest first.
+///
+/// The returned list is empty if the map does not exist.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get(cx: &Context, map_id: MapId) -> Result<Vec<MapVersion>, GetMapsError> {
+    let mut versions = sqlx::query!(
+        "WITH RECURSIVE
+           Older AS (
+             SELECT id, previous_version_id FROM Maps WHERE id = ?
+             UNION ALL
+             SELECT m.id, m.previous_version_id
+             FROM Maps AS m
+             JOIN Older AS o ON o.previous_version_id = m.id
+           ),
+           Newer AS (
+             SELECT id FROM Maps WHERE id = ?
+             UNION ALL
+             SELECT m.id
+             FROM Maps AS m
+             JOIN Newer AS n ON m.previous_version_id = n.id
+           )
+         SELECT
+           m.id AS `id: MapId`,
+           m.version,
+           m.workshop_id AS `workshop_id: WorkshopId`,
+           m.name,
+           m.state AS `state: MapState`,
+           m.vpk_checksum AS `vpk_checksum: MapChecksum`,
+           m.changelog,
+           m.previous_version_id AS `previous_version_id: MapId`,
+           next.id AS `next_version_id: MapId`,
+           m.approved_at
+         FROM Maps AS m
+         LEFT JOIN Maps AS next ON next.previous_version_id = m.id
+         WHERE m.id IN (SELECT id FROM Older UNION SELECT id FROM Newer)
+         ORDER BY m.version DESC",
+        map_id,
+        map_id,
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| MapVersion {
+        map_id: row.id,
+        version: row.version,
+        workshop_id: row.workshop_id,
+        name: row.name,
+        state: row.state,
+        vpk_checksum: row.vpk_checksum,
+        changelog: row.changelog,
+        previous_version: row.previous_version_id,
+        next_version: row.next_version_id,
+        courses: Vec::new(),
+        approved_at: row.approved_at.into(),
+    })
+    .try_collect::<Vec<_>>()
+    .await?;
+
+    if versions.is_empty() {
+        return Ok(versions);
+    }
+
+    let mut query = database::QueryBuilder::new(
+        "SELECT map_id, id, name, previous_version_id
+         FROM Courses
+         WHERE map_id IN ",
+    );
+
+    query.push_tuples(&versions, |mut query, version| {
+        query.push_bind(version.map_id);
+    });
+
+    query.push(" ORDER BY id ASC");
+
+    let indices = versions
+        .iter()
+        .enumerate()
+        .map(|(idx, version)| (version.map_id, idx))
+        .collect::<HashMap<_, _>>();
+
+    for row in query.build().fetch_all(cx.database().as_ref()).await? {
+        let map_id = row.try_get::<MapId, _>("map_id")?;
+
+        versions[indices[&map_id]].courses.push(CourseVersion {
+            id: row.try_get("id")?,
+            name: row.try_get("name")?,
+            previous_version: row.try_get("previous_version_id")?,
+        });
+    }
+
+    Ok(versions)
+}
+
+/// Returns the latest version of the map with the given name, if any.
+pub(super) async fn get_latest(
+    conn: &mut database::Connection,
+    name: &str,
+) -> database::Result<Option<PreviousVersion>> {
+    sqlx::query_as!(
+        PreviousVersion,
+        "SELECT id AS `map_id: MapId`, version
+         FROM Maps
+         WHERE name = ?
+         ORDER BY version DESC, id DESC
+         LIMIT 1
+         FOR UPDATE",
+        name,
+    )
+    .fetch_optional(conn)
+    .await
+    .map_err(database::Error::from)
+}
+
+/// Links the courses of a newly inserted map version to the courses on the previous version.
+pub(super) async fn link_courses(
+    conn: &mut database::Connection,
+    map_id: MapId,
+    previous_version: Option<&PreviousVersion>,
+    courses: &[NewCourse],
+) -> Result<(), ApproveMapError> {
+    for course in courses {
+        let Some(ref previous_name) = course.previous_version else {
+            continue;
+        };
+
+        let Some(previous_version) = previous_version else {
+            return Err(ApproveMapError::UnknownPreviousCourse { name: previous_name.clone() });
+        };
+
+        let linked = sqlx::query!(
+            "UPDATE Courses AS c
+             JOIN Courses AS old ON old.map_id = ? AND old.name = ?
+             SET c.previous_version_id = old.id
+             WHERE c.map_id = ?
+             AND c.name = ?",
+            previous_version.map_id,
+            previous_name,
+            map_id,
+            &course.name,
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        if linked == 0 {
+            return Err(ApproveMapError::UnknownPreviousCourse { name: previous_name.clone() });
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,199 @@
+//! Metadata about the Steam workshop items of maps.
+//!
+//! Metadata is stored per workshop item rather than per map, as multiple versions of a map can
+//! share the same item. It is fetched from Steam and saved here by the API; preview images are
+//! mirrored to local storage, and the rows keep track of which image the local copy belongs to.
+
+use std::collections::HashMap;
+
+use sqlx::Row as _;
+use sqlx::types::Json as SqlJson;
+
+use crate::steam::WorkshopId;
+use crate::time::Timestamp;
+use crate::{Context, database};
+
+#[derive(Debug)]
+pub struct WorkshopMetadata {
+    pub workshop_id: WorkshopId,
+
+    /// The item's preview image on Steam's CDN.
+    pub preview_url: Option<String>,
+
+    /// The local copy of the preview image, if there is one.
+    ///
+    /// This may be older than `preview_url` if mirroring the latest image failed.
+    pub mirrored_preview: Option<MirroredPreview>,
+
+    /// The size of the item's files, in bytes.
+    pub file_size: Option<u64>,
+
+    pub tags: Vec<String>,
+
+    /// When the item was created on the workshop.
+    pub created_at: Option<Timestamp>,
+
+    /// When the item was last updated on the workshop.
+    pub updated_at: Option<Timestamp>,
+
+    /// When this metadata was last fetched from Steam.
+    pub refreshed_at: Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct MirroredPreview {
+    /// The `preview_url` the local copy was downloaded from.
+    pub source_url: String,
+
+    /// The image's MIME type.
+    pub content_type: String,
+}
+
+#[derive(Debug)]
+pub struct NewWorkshopMetadata<'a> {
+    pub workshop_id: WorkshopId,
+    pub preview_url: Option<&'a str>,
+
+    /// A freshly mirrored preview image.
+    ///
+    /// If this is [`None`], any previously mirrored image is kept.
+    pub mirrored_preview: Option<&'a MirroredPreview>,
+
+    pub file_size: Option<u64>,
+    pub tags: &'a [String],
+    pub created_at: Option<Timestamp>,
+    pub updated_at: Option<Timestamp>,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to get workshop metadata")]
+#[from(forward)]
+pub struct GetWorkshopMetadataError(database::Error);
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to save workshop metadata")]
+#[from(forward)]
+pub struct SaveWorkshopMetadataError(database::Error);
+
+/// Returns the workshop IDs of all maps.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_workshop_ids(cx: &Context) -> Result<Vec<WorkshopId>, GetWorkshopMetadataError> {
+    sqlx::query_scalar!(
+        "SELECT DISTINCT workshop_id AS `workshop_id: WorkshopId`
+         FROM Maps
+         ORDER BY workshop_id ASC",
+    )
+    .fetch_all(cx.database().as_ref())
+    .await
+    .map_err(GetWorkshopMetadataError::from)
+}
+
+/// Returns the stored metadata for the given workshop items.
+///
+/// Items we have no metadata for are omitted from the result.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get(
+    cx: &Context,
+    workshop_ids: &[WorkshopId],
+) -> Result<HashMap<WorkshopId, WorkshopMetadata>, GetWorkshopMetadataError> {
+    if workshop_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+
+    let mut query = database::QueryBuilder::new(
+        "SELECT
+           workshop_id,
+           preview_url,
+           preview_mirrored_from,
+           preview_content_type,
+           file_size,
+           tags,
+           workshop_created_at,
+           workshop_updated_at,
+           refreshed_at
+         FROM WorkshopMetadata
+         WHERE workshop_id IN ",
+    );
+
+    query.push_tuples(workshop_ids, |mut query, workshop_id| {
+        query.push_bind(workshop_id);
+    });
+
+    query
+        .build()
+        .fetch_all(cx.database().as_ref())
+        .await?
+        .iter()
+        .map(|row| {
+            let mirrored_preview = match (
+                row.try_get::<Option<String>, _>("preview_mirrored_from")?,
+                row.try_get::<Option<String>, _>("preview_content_type")?,
+            ) {
+                (Some(source_url), Some(content_type)) => {
+                    Some(MirroredPreview { source_url, content_type })
+                },
+                _ => None,
+            };
+
+            let metadata = WorkshopMetadata {
+                workshop_id: row.try_get("workshop_id")?,
+                preview_url: row.try_get("preview_url")?,
+                mirrored_preview,
+                file_size: row.try_get("file_size")?,
+                tags: row.try_get::<SqlJson<Vec<String>>, _>("tags")?.0,
+                created_at: row.try_get("workshop_created_at")?,
+                updated_at: row.try_get("workshop_updated_at")?,
+                refreshed_at: row.try_get("refreshed_at")?,
+            };
+
+            Ok((metadata.workshop_id, metadata))
+        })
+        .collect::<sqlx::Result<_>>()
+        .map_err(GetWorkshopMetadataError::from)
+}
+
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn save(
+    cx: &Context,
+    metadata: NewWorkshopMetadata<'_>,
+) -> Result<(), SaveWorkshopMetadataError> {
+    let (mirrored_from, content_type) = metadata
+        .mirrored_preview
+        .map(|preview| (preview.source_url.as_str(), preview.content_type.as_str()))
+        .unzip();
+
+    sqlx::query!(
+        "INSERT INTO WorkshopMetadata (
+           workshop_id,
+           preview_url,
+           preview_mirrored_from,
+           preview_content_type,
+           file_size,
+           tags,
+           workshop_created_at,
+           workshop_updated_at
+         )
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)
+         ON DUPLICATE KEY UPDATE
+           preview_url = VALUES(preview_url),
+           preview_mirrored_from = COALESCE(VALUES(preview_mirrored_from), preview_mirrored_from),
+           preview_content_type = COALESCE(VALUES(preview_content_type), preview_content_type),
+           file_size = VALUES(file_size),
+           tags = VALUES(tags),
+           workshop_created_at = VALUES(workshop_created_at),
+           workshop_updated_at = VALUES(workshop_updated_at),
+           refreshed_at = NOW()",
+        metadata.workshop_id,
+        metadata.preview_url,
+        mirrored_from,
+        content_type,
+        metadata.file_size,
+        SqlJson(metadata.tags),
+        metadata.created_at,
+        metadata.updated_at,
+    )
+    .execute(cx.database().as_ref())
+    .await?;
+
+    Ok(())
+}
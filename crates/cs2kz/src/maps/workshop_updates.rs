@@ -0,0 +1,182 @@
+//! Detecting updates to approved maps on the Steam workshop.
+//!
+//! Mappers can update their workshop items at any time, so the map servers download might not
+//! match the map we approved anymore. Whoever checks the workshop (this crate cannot talk to
+//! Steam itself) gets the maps to check from [`get_tracked()`] and reports back with
+//! [`record_check()`]. Maps whose checksum changed end up as [`PendingUpdate`]s until a new
+//! version is approved.
+
+use futures_util::TryStreamExt;
+
+use super::{GetMapsError, MapChecksum, MapId, MapInfo};
+use crate::events::{self, Event};
+use crate::steam::WorkshopId;
+use crate::time::Timestamp;
+use crate::{Context, database};
+
+/// An approved map whose workshop item should be checked for updates.
+#[derive(Debug)]
+pub struct TrackedMap {
+    pub id: MapId,
+    pub workshop_id: WorkshopId,
+    pub vpk_checksum: MapChecksum,
+
+    /// The last time we know the workshop item to have been updated.
+    ///
+    /// Any update after this point has not been checked yet.
+    pub last_checked_update: Timestamp,
+}
+
+/// The result of checking a [`TrackedMap`].
+#[derive(Debug)]
+pub struct MapCheck {
+    pub map_id: MapId,
+
+    /// When the workshop item was last updated.
+    pub workshop_updated_at: Timestamp,
+
+    /// The workshop item's current name and checksum, if the checksum differs from the approved
+    /// map's.
+    pub new_version: Option<(String, MapChecksum)>,
+}
+
+/// An approved map whose workshop item has changed.
+#[derive(Debug, serde::Serialize)]
+pub struct PendingUpdate {
+    /// The approved map that is now out of date.
+    pub map: MapInfo,
+    pub workshop_id: WorkshopId,
+
+    /// The name of the map currently on the workshop.
+    pub name: String,
+
+    /// The checksum of the map currently on the workshop.
+    pub vpk_checksum: MapChecksum,
+
+    pub workshop_updated_at: Timestamp,
+    pub detected_at: Timestamp,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to record workshop update check")]
+#[from(forward)]
+pub struct RecordCheckError(database::Error);
+
+/// Returns every approved map, along with when we last saw its workshop item change.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_tracked(cx: &Context) -> Result<Vec<TrackedMap>, GetMapsError> {
+    sqlx::query_as!(
+        TrackedMap,
+        "SELECT
+           id AS `id: MapId`,
+           workshop_id AS `workshop_id: WorkshopId`,
+           vpk_checksum AS `vpk_checksum: MapChecksum`,
+           COALESCE(workshop_checked_at, approved_at) AS `last_checked_update!: Timestamp`
+         FROM Maps
+         WHERE state = 1
+         ORDER BY id ASC",
+    )
+    .fetch_all(cx.database().as_ref())
+    .await
+    .map_err(GetMapsError::from)
+}
+
+/// Remembers that a map has been checked, and records a [`PendingUpdate`] if it changed.
+///
+/// If the workshop item matches the approved map again, e.g. because the mapper reverted their
+/// update, any previously recorded [`PendingUpdate`] is discarded.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn record_check(
+    cx: &Context,
+    MapCheck { map_id, workshop_updated_at, new_version }: MapCheck,
+) -> Result<(), RecordCheckError> {
+    cx.database_transaction(async move |conn| {
+        sqlx::query!(
+            "UPDATE Maps SET workshop_checked_at = ? WHERE id = ?",
+            workshop_updated_at,
+            map_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        let Some((name, vpk_checksum)) = new_version else {
+            sqlx::query!("DELETE FROM PendingMapUpdates WHERE map_id = ?", map_id)
+                .execute(&mut *conn)
+                .await?;
+
+            return Ok(());
+        };
+
+        sqlx::query!(
+            "INSERT INTO PendingMapUpdates (map_id, name, vpk_checksum, workshop_updated_at)
+             VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY
+             UPDATE name = VALUES(name),
+                    vpk_checksum = VALUES(vpk_checksum),
+                    workshop_updated_at = VALUES(workshop_updated_at),
+                    detected_at = NOW()",
+            map_id,
+            &name,
+            vpk_checksum,
+            workshop_updated_at,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        warn!(%map_id, name, %vpk_checksum, "approved map has changed on the workshop");
+
+        events::dispatch(Event::MapUpdateDetected { map_id, name, vpk_checksum });
+
+        Ok::<_, RecordCheckError>(())
+    })
+    .await
+}
+
+/// Returns every approved map that has changed on the workshop since it was approved.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn get_pending(cx: &Context) -> Result<Vec<PendingUpdate>, GetMapsError> {
+    sqlx::query!(
+        "SELECT
+           m.id AS `id: MapId`,
+           m.name AS map_name,
+           m.workshop_id AS `workshop_id: WorkshopId`,
+           u.name,
+           u.vpk_checksum AS `vpk_checksum: MapChecksum`,
+           u.workshop_updated_at,
+           u.detected_at
+         FROM PendingMapUpdates AS u
+         JOIN Maps AS m ON m.id = u.map_id
+         ORDER BY u.detected_at DESC",
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| PendingUpdate {
+        map: MapInfo { id: row.id, name: row.map_name },
+        workshop_id: row.workshop_id,
+        name: row.name,
+        vpk_checksum: row.vpk_checksum,
+        workshop_updated_at: row.workshop_updated_at.into(),
+        detected_at: row.detected_at.into(),
+    })
+    .try_collect()
+    .await
+    .map_err(GetMapsError::from)
+}
+
+/// Resolves the pending updates of every map with the given workshop ID.
+///
+/// This is called when a new version of a map gets approved.
+pub(super) async fn resolve(
+    conn: &mut database::Connection,
+    workshop_id: WorkshopId,
+) -> database::Result<()> {
+    sqlx::query!(
+        "DELETE u FROM PendingMapUpdates AS u
+         JOIN Maps AS m ON m.id = u.map_id
+         WHERE m.workshop_id = ?",
+        workshop_id,
+    )
+    .execute(conn)
+    .await?;
+
+    Ok(())
+}
@@ -4,13 +4,22 @@ use std::fmt;
 ///
 /// [`cs2kz-metamod`]: https://github.com/KZGlobalTeam/cs2kz-metamod
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, serde::Serialize, sqlx::Type)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize, sqlx::Type)]
 #[serde(rename_all = "kebab-case")]
 pub enum Mode {
     Vanilla = 1,
     Classic = 2,
 }
 
+impl Mode {
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            Self::Vanilla => "vanilla",
+            Self::Classic => "classic",
+        }
+    }
+}
+
 impl<'de> serde::Deserialize<'de> for Mode {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
@@ -1,9 +1,12 @@
 use std::future::{self};
 use std::{cmp, fmt};
 
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD as BASE64;
 use futures_util::future::Either;
 use futures_util::stream::{self, Stream, StreamExt, TryStreamExt};
-use serde::{Deserialize, Deserializer, Serialize};
+use serde::de::{self, DeserializeOwned};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 
 #[derive(Debug, Display, Clone, Copy, Into, Serialize)]
 #[debug("{value}")]
@@ -64,20 +67,116 @@ impl Offset {
     }
 }
 
+/// An opaque cursor used for keyset pagination.
+///
+/// A cursor points at the last value of a page by storing the value's sort key and ID. The next
+/// page can then be fetched by filtering for values that come after the cursor, rather than making
+/// the database skip over every previous value like `OFFSET` does.
+///
+/// Cursors are (de)serialized as URL-safe base64 strings.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cursor<K, I> {
+    sort_key: K,
+    id: I,
+}
+
+impl<K, I> Cursor<K, I> {
+    pub const fn new(sort_key: K, id: I) -> Self {
+        Self { sort_key, id }
+    }
+
+    pub const fn sort_key(&self) -> &K {
+        &self.sort_key
+    }
+
+    pub const fn id(&self) -> &I {
+        &self.id
+    }
+}
+
+impl<K, I> Cursor<K, I>
+where
+    K: Serialize,
+    I: Serialize,
+{
+    pub fn encode(&self) -> String {
+        let json = serde_json::to_vec(&(&self.sort_key, &self.id))
+            .expect("cursor keys should always be serializable");
+
+        BASE64.encode(json)
+    }
+}
+
+impl<K, I> Serialize for Cursor<K, I>
+where
+    K: Serialize,
+    I: Serialize,
+{
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        self.encode().serialize(serializer)
+    }
+}
+
+impl<'de, K, I> Deserialize<'de> for Cursor<K, I>
+where
+    K: DeserializeOwned,
+    I: DeserializeOwned,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let encoded = String::deserialize(deserializer)?;
+        let json = BASE64
+            .decode(&encoded)
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&encoded), &"a cursor"))?;
+
+        serde_json::from_slice::<(K, I)>(&json)
+            .map(|(sort_key, id)| Self { sort_key, id })
+            .map_err(|_| de::Error::invalid_value(de::Unexpected::Str(&encoded), &"a cursor"))
+    }
+}
+
 #[derive(Serialize)]
 pub struct Paginated<T> {
-    total: u64,
+    /// The total number of values across all pages.
+    ///
+    /// This is not known for pages fetched with a [`Cursor`], as counting every value would defeat
+    /// the point of keyset pagination.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    total: Option<u64>,
+
     values: T,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    next_cursor: Option<String>,
 }
 
 impl<T> Paginated<T> {
     pub fn new(total: u64, values: T) -> Self {
-        Self { total, values }
+        Self { total: Some(total), values, next_cursor: None }
+    }
+
+    /// Creates a page without a total, e.g. because it was fetched with a [`Cursor`].
+    pub fn without_total(values: T) -> Self {
+        Self { total: None, values, next_cursor: None }
+    }
+
+    pub fn values(&self) -> &T {
+        &self.values
     }
 
     pub fn into_inner(self) -> T {
         self.values
     }
+
+    /// Returns the encoded cursor pointing at the last value of this page, if any.
+    pub fn next_cursor(&self) -> Option<&str> {
+        self.next_cursor.as_deref()
+    }
 }
 
 impl<T> Paginated<Vec<T>> {
@@ -85,8 +184,32 @@ impl<T> Paginated<Vec<T>> {
         Paginated {
             total: self.total,
             values: self.values.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
         }
     }
+
+    /// Sets the cursor for fetching the next page.
+    ///
+    /// If there are less than `limit` values, this is the last page and no cursor will be set.
+    /// Otherwise, `make_cursor` is called with the last value.
+    pub fn with_next_cursor<K, I>(
+        mut self,
+        limit: impl Into<u64>,
+        make_cursor: impl FnOnce(&T) -> Cursor<K, I>,
+    ) -> Self
+    where
+        K: Serialize,
+        I: Serialize,
+    {
+        self.next_cursor = match self.values.last() {
+            Some(last) if (self.values.len() as u64) >= limit.into() => {
+                Some(make_cursor(last).encode())
+            },
+            _ => None,
+        };
+
+        self
+    }
 }
 
 impl<S, T, E> Paginated<S>
@@ -102,6 +225,7 @@ where
         Paginated {
             total: self.total,
             values: self.values.map_ok(f),
+            next_cursor: self.next_cursor,
         }
     }
 
@@ -116,6 +240,7 @@ where
                 Ok(item) => Either::Left(f(item).map(Ok)),
                 Err(error) => Either::Right(stream::once(future::ready(error)).map(Err)),
             }),
+            next_cursor: self.next_cursor,
         }
     }
 
@@ -133,6 +258,7 @@ where
         Ok(Paginated {
             total: self.total,
             values: self.values.try_collect().await?,
+            next_cursor: self.next_cursor,
         })
     }
 }
@@ -141,6 +267,54 @@ impl<T> fmt::Debug for Paginated<T> {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         fmt.debug_struct("Paginated")
             .field("total", &self.total)
+            .field("next_cursor", &self.next_cursor)
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    type TestCursor = Cursor<String, u64>;
+
+    fn decode(encoded: &str) -> serde_json::Result<TestCursor> {
+        serde_json::from_value(serde_json::Value::String(encoded.to_owned()))
+    }
+
+    #[test]
+    fn cursor_round_trip() {
+        let cursor = TestCursor::new(String::from("2024-01-01T00:00:00Z"), 69);
+        let decoded = decode(&cursor.encode()).unwrap();
+
+        assert_eq!(decoded, cursor);
+    }
+
+    #[test]
+    fn cursor_is_url_safe() {
+        let encoded = TestCursor::new(String::from("??>>??"), u64::MAX).encode();
+
+        assert!(
+            encoded
+                .chars()
+                .all(|char| char.is_ascii_alphanumeric() || matches!(char, '-' | '_'))
+        );
+    }
+
+    #[test]
+    fn cursor_rejects_invalid_base64() {
+        assert!(decode("not a cursor!").is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_invalid_json() {
+        assert!(decode(&BASE64.encode("{")).is_err());
+    }
+
+    #[test]
+    fn cursor_rejects_wrong_types() {
+        assert!(decode(&BASE64.encode(r#"[69, "2024-01-01T00:00:00Z"]"#)).is_err());
+        assert!(decode(&BASE64.encode(r#"["2024-01-01T00:00:00Z"]"#)).is_err());
+        assert!(decode(&BASE64.encode(r#"["2024-01-01T00:00:00Z", -1]"#)).is_err());
+    }
+}
@@ -0,0 +1,260 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, btree_map};
+
+use futures_util::TryStreamExt;
+
+use super::PlayerId;
+use crate::maps::courses::filters::Tier;
+use crate::maps::{CourseFilterId, CourseId, MapId, MapInfo};
+use crate::mode::Mode;
+use crate::records::RecordId;
+use crate::time::Seconds;
+use crate::{Context, database, points};
+
+/// A head-to-head comparison between two players.
+#[derive(Debug, Default)]
+pub struct Comparison {
+    /// Every course filter both players have completed.
+    pub courses: Vec<CourseComparison>,
+
+    /// Aggregate results on the NUB leaderboards.
+    pub nub_summary: ComparisonSummary,
+
+    /// Aggregate results on the PRO leaderboards.
+    pub pro_summary: ComparisonSummary,
+}
+
+#[derive(Debug)]
+pub struct CourseComparison {
+    pub filter_id: CourseFilterId,
+    pub map: MapInfo,
+    pub course_id: CourseId,
+    pub course_name: String,
+    pub nub_tier: Tier,
+    pub pro_tier: Tier,
+
+    /// The first player's best records.
+    pub player: ComparedRecords,
+
+    /// The second player's best records.
+    pub opponent: ComparedRecords,
+}
+
+/// A player's best records on a single course filter.
+#[derive(Debug, Default)]
+pub struct ComparedRecords {
+    pub nub: Option<ComparedRecord>,
+    pub pro: Option<ComparedRecord>,
+}
+
+#[derive(Debug)]
+pub struct ComparedRecord {
+    pub record_id: RecordId,
+    pub time: Seconds,
+    pub rank: u32,
+
+    /// [`None`] if the filter is not ranked.
+    pub points: Option<f64>,
+}
+
+/// Win / loss counts from the first player's point of view.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ComparisonSummary {
+    /// Course filters on which the first player is faster.
+    pub wins: u32,
+
+    /// Course filters on which the second player is faster.
+    pub losses: u32,
+
+    /// Course filters on which both players have the same time.
+    pub ties: u32,
+
+    /// Course filters only the first player has finished.
+    pub only_player: u32,
+
+    /// Course filters only the second player has finished.
+    pub only_opponent: u32,
+}
+
+impl ComparisonSummary {
+    fn add(&mut self, player: Option<&ComparedRecord>, opponent: Option<&ComparedRecord>) {
+        match (player, opponent) {
+            (None, None) => {},
+            (Some(_), None) => self.only_player += 1,
+            (None, Some(_)) => self.only_opponent += 1,
+            (Some(player), Some(opponent)) => match player.time.cmp(&opponent.time) {
+                Ordering::Less => self.wins += 1,
+                Ordering::Greater => self.losses += 1,
+                Ordering::Equal => self.ties += 1,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum ComparePlayersError {
+    #[display("cannot compare a player with themselves")]
+    SamePlayer,
+
+    #[display("failed to compare players: {_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+/// Compares the best records of two players on a specific mode.
+///
+/// Fails with [`ComparePlayersError::SamePlayer`] if both IDs refer to the same player.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn compare(
+    cx: &Context,
+    player_id: PlayerId,
+    opponent_id: PlayerId,
+    mode: Mode,
+) -> Result<Comparison, ComparePlayersError> {
+    if player_id == opponent_id {
+        return Err(ComparePlayersError::SamePlayer);
+    }
+
+    let mut rows = sqlx::query!(
+        "WITH Filters AS (
+           SELECT filter_id FROM BestNubRecords WHERE player_id IN (?, ?)
+           UNION
+           SELECT filter_id FROM BestProRecords WHERE player_id IN (?, ?)
+         ),
+         NubLeaderboard AS (
+           SELECT
+             b.filter_id,
+             b.player_id,
+             b.record_id,
+             b.points,
+             r.time,
+             RANK() OVER (
+               PARTITION BY b.filter_id
+               ORDER BY
+                 r.time ASC,
+                 r.submitted_at ASC
+             ) AS rank
+           FROM BestNubRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           WHERE b.filter_id IN (SELECT filter_id FROM Filters)
+         ),
+         ProLeaderboard AS (
+           SELECT
+             b.filter_id,
+             b.player_id,
+             b.record_id,
+             b.points,
+             r.time,
+             RANK() OVER (
+               PARTITION BY b.filter_id
+               ORDER BY
+                 r.time ASC,
+                 r.submitted_at ASC
+             ) AS rank
+           FROM BestProRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           WHERE b.filter_id IN (SELECT filter_id FROM Filters)
+         ),
+         Leaderboards AS (
+           SELECT FALSE AS is_pro_leaderboard, NubLeaderboard.* FROM NubLeaderboard
+           UNION ALL
+           SELECT TRUE AS is_pro_leaderboard, ProLeaderboard.* FROM ProLeaderboard
+         )
+         SELECT
+           l.is_pro_leaderboard AS `is_pro_leaderboard!: bool`,
+           l.filter_id AS `filter_id!: CourseFilterId`,
+           l.player_id AS `player_id!: PlayerId`,
+           l.record_id AS `record_id!: RecordId`,
+           l.points AS `points!`,
+           l.time AS `time!: Seconds`,
+           l.rank AS `rank!`,
+           (l.filter_id IN (SELECT id FROM RankedCourseFilters)) AS `is_ranked!: bool`,
+           m.id AS `map_id: MapId`,
+           m.name AS map_name,
+           c.id AS `course_id: CourseId`,
+           c.name AS course_name,
+           cf.nub_tier AS `nub_tier: Tier`,
+           cf.pro_tier AS `pro_tier: Tier`
+         FROM Leaderboards AS l
+         JOIN CourseFilters AS cf ON cf.id = l.filter_id
+         JOIN Courses AS c ON c.id = cf.course_id
+         JOIN Maps AS m ON m.id = c.map_id
+         WHERE l.player_id IN (?, ?)
+         AND cf.mode = ?
+         ORDER BY m.name ASC, c.id ASC",
+        player_id,
+        opponent_id,
+        player_id,
+        opponent_id,
+        player_id,
+        opponent_id,
+        mode,
+    )
+    .fetch(cx.database().as_ref());
+
+    // keyed by map name & course ID so the results stay sorted
+    let mut courses = BTreeMap::<(String, CourseId), CourseComparison>::new();
+
+    while let Some(row) = rows.try_next().await? {
+        let is_pro_leaderboard = row.is_pro_leaderboard;
+        let (nub_tier, pro_tier) = (row.nub_tier, row.pro_tier);
+        let rank = row.rank as u32;
+
+        let record = ComparedRecord {
+            record_id: row.record_id,
+            time: row.time,
+            rank,
+            points: row.is_ranked.then(|| {
+                points::complete(
+                    if is_pro_leaderboard { pro_tier } else { nub_tier },
+                    is_pro_leaderboard,
+                    rank as usize - 1,
+                    row.points,
+                )
+            }),
+        };
+
+        let course = match courses.entry((row.map_name.clone(), row.course_id)) {
+            btree_map::Entry::Occupied(entry) => entry.into_mut(),
+            btree_map::Entry::Vacant(entry) => entry.insert(CourseComparison {
+                filter_id: row.filter_id,
+                map: MapInfo { id: row.map_id, name: row.map_name },
+                course_id: row.course_id,
+                course_name: row.course_name,
+                nub_tier,
+                pro_tier,
+                player: ComparedRecords::default(),
+                opponent: ComparedRecords::default(),
+            }),
+        };
+
+        let records = if row.player_id == player_id {
+            &mut course.player
+        } else {
+            &mut course.opponent
+        };
+
+        if is_pro_leaderboard {
+            records.pro = Some(record);
+        } else {
+            records.nub = Some(record);
+        }
+    }
+
+    let mut comparison = Comparison::default();
+
+    for course in courses.into_values() {
+        comparison
+            .nub_summary
+            .add(course.player.nub.as_ref(), course.opponent.nub.as_ref());
+        comparison
+            .pro_summary
+            .add(course.player.pro.as_ref(), course.opponent.pro.as_ref());
+
+        if course.player.nub.is_some() && course.opponent.nub.is_some() {
+            comparison.courses.push(course);
+        }
+    }
+
+    Ok(comparison)
+}
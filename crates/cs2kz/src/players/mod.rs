@@ -9,12 +9,36 @@ use crate::Context;
 use crate::database::{self, QueryBuilder};
 use crate::maps::courses::filters::Tier;
 use crate::mode::Mode;
-use crate::pagination::{Limit, Offset, Paginated};
+use crate::pagination::{Cursor, Limit, Offset, Paginated};
 use crate::time::Timestamp;
 
 mod player_id;
 pub use player_id::PlayerId;
 
+mod comparison;
+pub use comparison::{
+    ComparePlayersError,
+    ComparedRecord,
+    ComparedRecords,
+    Comparison,
+    ComparisonSummary,
+    CourseComparison,
+    compare,
+};
+
+mod ratings;
+pub use ratings::{
+    GetRankingParams,
+    RankedPlayer,
+    UpdateRatingsError,
+    get_ranking,
+    update_ratings,
+};
+pub(crate) use ratings::{update_player_rating_in, update_ratings_in};
+
+pub mod rating_history;
+pub use rating_history::{GetRatingHistoryParams, RatingSnapshot, get_rating_history};
+
 /// [`cs2kz-metamod`] preferences.
 ///
 /// This is an arbitrary JSON blob set by CS2 servers.
@@ -42,6 +66,10 @@ pub struct PlayerInfo {
 pub struct Profile {
     pub id: PlayerId,
     pub name: String,
+
+    /// The player's rating on the requested mode.
+    ///
+    /// This is the same value [`get_ranking()`] orders players by.
     pub rating: f64,
     pub nub_completion: [u32; 8],
     pub pro_completion: [u32; 8],
@@ -50,10 +78,19 @@ pub struct Profile {
 #[derive(Debug, Default)]
 pub struct GetPlayersParams<'a> {
     pub name: Option<&'a str>,
+
+    /// Only include players which come after this cursor.
+    pub cursor: Option<PlayersCursor>,
+
     pub limit: Limit<1000, 250>,
     pub offset: Offset,
 }
 
+/// A cursor for paginating through [`get()`] results.
+///
+/// Players are sorted by their ID, so the cursor has no separate sort key.
+pub type PlayersCursor = Cursor<(), PlayerId>;
+
 #[derive(Debug)]
 #[cfg_attr(feature = "fake", derive(fake::Dummy))]
 pub struct NewPlayer<'a> {
@@ -164,21 +201,33 @@ pub async fn create_many<'a>(
 #[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
 pub async fn get(
     cx: &Context,
-    GetPlayersParams { name, limit, offset }: GetPlayersParams<'_>,
+    GetPlayersParams { name, cursor, limit, offset }: GetPlayersParams<'_>,
 ) -> Result<Paginated<impl Stream<Item = Result<Player, GetPlayersError>>>, GetPlayersError> {
-    let total = database::count!(cx.database().as_ref(), "Players").await?;
+    let total = match cursor {
+        None => Some(database::count!(cx.database().as_ref(), "Players").await?),
+        Some(_) => None,
+    };
+    let cursor_id = cursor.map(|cursor| *cursor.id());
+
     let servers = self::macros::select!(
         "WHERE name LIKE COALESCE(?, name)
+         AND (? IS NULL OR id > ?)
+         ORDER BY id ASC
          LIMIT ?
          OFFSET ?",
         name.map(|name| format!("%{name}%")),
+        cursor_id,
+        cursor_id,
         limit.value(),
         offset.value()
     )
     .fetch(cx.database().as_ref())
     .map_err(GetPlayersError::from);
 
-    Ok(Paginated::new(total, servers))
+    Ok(match total {
+        Some(total) => Paginated::new(total, servers),
+        None => Paginated::without_total(servers),
+    })
 }
 
 #[tracing::instrument(skip(cx), err(level = "debug"))]
@@ -210,95 +259,13 @@ pub async fn get_profile(
     mode: Mode,
 ) -> Result<Option<Profile>, GetPlayersError> {
     let Some(mut profile) = sqlx::query!(
-        r#"WITH RankedPoints AS (
-             SELECT
-               source,
-               record_id,
-               ROW_NUMBER() OVER (
-                 PARTITION BY player_id
-                 ORDER BY points DESC
-               ) AS n
-             FROM ((
-               SELECT "pro" AS source, record_id, player_id, points
-               FROM BestProRecords
-               WHERE player_id = ?
-             ) UNION ALL (
-               SELECT "nub" AS source, record_id, player_id, points
-               FROM BestNubRecords
-               WHERE player_id = ?
-             )) AS _
-           ),
-           NubRecords AS (
-             SELECT
-               r.id AS record_id,
-               r.player_id,
-               cf.nub_tier AS tier,
-               BestNubRecords.points,
-               RANK() OVER (
-                 PARTITION BY r.filter_id
-                 ORDER BY
-                   r.time ASC,
-                   r.submitted_at ASC
-               ) AS rank
-             FROM Records AS r
-             JOIN BestNubRecords ON BestNubRecords.record_id = r.id
-             JOIN CourseFilters AS cf ON cf.id = r.filter_id
-             WHERE r.player_id = ?
-             AND cf.mode = ?
-           ),
-           ProRecords AS (
-             SELECT
-               r.id AS record_id,
-               r.player_id,
-               cf.pro_tier AS tier,
-               BestProRecords.points,
-               RANK() OVER (
-                 PARTITION BY r.filter_id
-                 ORDER BY
-                   r.time ASC,
-                   r.submitted_at ASC
-               ) AS rank
-             FROM Records AS r
-             JOIN BestProRecords ON BestProRecords.record_id = r.id
-             JOIN CourseFilters AS cf ON cf.id = r.filter_id
-             WHERE r.player_id = ?
-             AND cf.mode = ?
-           ),
-           NubRatings AS (
-             SELECT
-               player_id,
-               SUM(KZ_POINTS(tier, false, rank - 1, points) * POWER(0.975, n - 1)) AS rating
-             FROM NubRecords
-             JOIN RankedPoints
-               ON RankedPoints.record_id = NubRecords.record_id
-               AND RankedPoints.source = "nub"
-             GROUP BY player_id
-           ),
-           ProRatings AS (
-             SELECT
-               player_id,
-               SUM(KZ_POINTS(tier, true, rank - 1, points) * POWER(0.975, n - 1)) AS rating
-             FROM ProRecords
-             JOIN RankedPoints
-               ON RankedPoints.record_id = ProRecords.record_id
-               AND RankedPoints.source = "pro"
-             GROUP BY ProRecords.player_id
-           )
-           SELECT
-             p.id AS `player_id: PlayerId`,
-             p.name AS player_name,
-             NubRatings.rating AS nub_rating,
-             ProRatings.rating AS pro_rating,
-             p.first_joined_at
-           FROM Players AS p
-           LEFT JOIN NubRatings ON NubRatings.player_id = p.id
-           LEFT JOIN ProRatings ON ProRatings.player_id = p.id
-           WHERE p.id = ?"#,
-        player_id,
-        player_id,
-        player_id,
-        mode,
-        player_id,
+        "SELECT
+           p.id AS `player_id: PlayerId`,
+           p.name AS player_name,
+           (SELECT rating FROM PlayerRatings WHERE player_id = p.id AND `mode` = ?) AS rating,
+           p.first_joined_at
+         FROM Players AS p
+         WHERE p.id = ?",
         mode,
         player_id,
     )
@@ -308,13 +275,7 @@ pub async fn get_profile(
         row.map(|row| Profile {
             id: row.player_id,
             name: row.player_name,
-            rating: match (row.nub_rating, row.pro_rating) {
-                (None, Some(_)) => unreachable!(),
-                (None, None) => 0.0,
-                (Some(nub_rating), None) => nub_rating,
-                // ?
-                (Some(nub_rating), Some(pro_rating)) => nub_rating + pro_rating,
-            },
+            rating: row.rating.unwrap_or_default(),
             nub_completion: [0; 8],
             pro_completion: [0; 8],
             first_joined_at: row.first_joined_at.into(),
@@ -334,6 +295,7 @@ pub async fn get_profile(
          LEFT JOIN BestNubRecords AS NubRecords ON NubRecords.record_id = r.id
          LEFT JOIN BestProRecords AS ProRecords ON ProRecords.record_id = r.id
          WHERE r.player_id = ?
+         AND r.filter_id IN (SELECT id FROM RankedCourseFilters)
          AND (NOT ((NubRecords.points IS NULL) AND (ProRecords.points IS NULL)))",
         player_id,
     )
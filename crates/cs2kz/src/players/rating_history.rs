@@ -0,0 +1,209 @@
+//! Daily snapshots of player ratings and leaderboard positions.
+//!
+//! [`run()`] takes a snapshot once per day (UTC), which can later be queried with
+//! [`get_rating_history()`].
+
+use std::time::Duration;
+
+use futures_util::TryStreamExt;
+use time::OffsetDateTime;
+use tokio_util::sync::CancellationToken;
+
+use super::{GetPlayersError, PlayerId};
+use crate::maps::CourseFilterId;
+use crate::mode::Mode;
+use crate::pagination::Limit;
+use crate::time::Timestamp;
+use crate::{Context, database};
+
+/// How many of the highest rated players (per mode) get their leaderboard positions snapshotted.
+pub const TOP_PLAYERS: u32 = 100;
+
+/// A player's rating and rank on a specific day.
+#[derive(Debug)]
+pub struct RatingSnapshot {
+    /// The day the snapshot was taken, at midnight (UTC).
+    pub date: Timestamp,
+    pub rating: f64,
+    pub rank: u32,
+
+    /// The player's rank on the requested filter's NUB leaderboard.
+    ///
+    /// This is only recorded for the top [`TOP_PLAYERS`] players.
+    pub nub_rank: Option<u32>,
+
+    /// The player's rank on the requested filter's PRO leaderboard.
+    ///
+    /// This is only recorded for the top [`TOP_PLAYERS`] players.
+    pub pro_rank: Option<u32>,
+}
+
+#[derive(Debug)]
+pub struct GetRatingHistoryParams {
+    pub mode: Mode,
+
+    /// Also include the player's positions on this filter's leaderboards.
+    pub filter_id: Option<CourseFilterId>,
+
+    /// How many days to return, starting from the most recent snapshot.
+    pub limit: Limit<3650, 365>,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to snapshot player ratings")]
+#[from(forward)]
+pub struct SnapshotRatingsError(database::Error);
+
+/// Returns a player's daily rating snapshots, newest first.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn get_rating_history(
+    cx: &Context,
+    player_id: PlayerId,
+    GetRatingHistoryParams { mode, filter_id, limit }: GetRatingHistoryParams,
+) -> Result<Vec<RatingSnapshot>, GetPlayersError> {
+    sqlx::query!(
+        "SELECT h.date, h.rating, h.rank, nub.rank AS nub_rank, pro.rank AS pro_rank
+         FROM PlayerRatingHistory AS h
+         LEFT JOIN LeaderboardHistory AS nub
+           ON nub.filter_id = ?
+           AND nub.is_pro_leaderboard = FALSE
+           AND nub.player_id = h.player_id
+           AND nub.date = h.date
+         LEFT JOIN LeaderboardHistory AS pro
+           ON pro.filter_id = ?
+           AND pro.is_pro_leaderboard = TRUE
+           AND pro.player_id = h.player_id
+           AND pro.date = h.date
+         WHERE h.player_id = ?
+         AND h.mode = ?
+         ORDER BY h.date DESC
+         LIMIT ?",
+        filter_id,
+        filter_id,
+        player_id,
+        mode,
+        limit.value(),
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| RatingSnapshot {
+        date: row.date.midnight().assume_utc().into(),
+        rating: row.rating,
+        rank: row.rank,
+        nub_rank: row.nub_rank,
+        pro_rank: row.pro_rank,
+    })
+    .map_err(GetPlayersError::from)
+    .try_collect()
+    .await
+}
+
+/// Snapshots every player's rating and global rank, as well as the leaderboard positions of the
+/// top [`TOP_PLAYERS`] players of every mode.
+///
+/// Snapshots are keyed by the current date (UTC); taking a second snapshot on the same day is a
+/// no-op.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn snapshot(cx: &Context) -> Result<(), SnapshotRatingsError> {
+    cx.database_transaction(async move |conn| {
+        let players = sqlx::query!(
+            "INSERT IGNORE INTO PlayerRatingHistory (player_id, `mode`, `date`, rating, rank)
+             SELECT
+               player_id,
+               `mode`,
+               UTC_DATE(),
+               rating,
+               RANK() OVER (PARTITION BY `mode` ORDER BY rating DESC)
+             FROM PlayerRatings",
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        let positions = sqlx::query!(
+            "INSERT IGNORE INTO LeaderboardHistory
+               (filter_id, is_pro_leaderboard, player_id, `date`, rank)
+             WITH TopPlayers AS (
+               SELECT player_id, `mode`
+               FROM PlayerRatingHistory
+               WHERE `date` = UTC_DATE()
+               AND rank <= ?
+             ),
+             NubLeaderboard AS (
+               SELECT
+                 b.filter_id,
+                 b.player_id,
+                 cf.mode,
+                 RANK() OVER (
+                   PARTITION BY b.filter_id
+                   ORDER BY
+                     r.time ASC,
+                     r.submitted_at ASC
+                 ) AS rank
+               FROM BestNubRecords AS b
+               JOIN Records AS r ON r.id = b.record_id
+               JOIN CourseFilters AS cf ON cf.id = b.filter_id
+             ),
+             ProLeaderboard AS (
+               SELECT
+                 b.filter_id,
+                 b.player_id,
+                 cf.mode,
+                 RANK() OVER (
+                   PARTITION BY b.filter_id
+                   ORDER BY
+                     r.time ASC,
+                     r.submitted_at ASC
+                 ) AS rank
+               FROM BestProRecords AS b
+               JOIN Records AS r ON r.id = b.record_id
+               JOIN CourseFilters AS cf ON cf.id = b.filter_id
+             )
+             SELECT l.filter_id, FALSE, l.player_id, UTC_DATE(), l.rank
+             FROM NubLeaderboard AS l
+             JOIN TopPlayers AS t ON t.player_id = l.player_id AND t.mode = l.mode
+             UNION ALL
+             SELECT l.filter_id, TRUE, l.player_id, UTC_DATE(), l.rank
+             FROM ProLeaderboard AS l
+             JOIN TopPlayers AS t ON t.player_id = l.player_id AND t.mode = l.mode",
+            TOP_PLAYERS,
+        )
+        .execute(&mut *conn)
+        .await?
+        .rows_affected();
+
+        debug!(players, positions, "took rating snapshot");
+
+        Ok(())
+    })
+    .await
+}
+
+/// Takes a [`snapshot()`] on startup and then every day at midnight (UTC).
+///
+/// Failed snapshots are logged and retried the next day.
+#[tracing::instrument(skip_all)]
+pub async fn run(cx: Context, cancellation_token: CancellationToken) {
+    loop {
+        if let Err(error) = snapshot(&cx).await {
+            error!(%error, "failed to take rating snapshot");
+        }
+
+        select! {
+            () = cancellation_token.cancelled() => break,
+            () = tokio::time::sleep(until_next_day()) => {},
+        }
+    }
+}
+
+/// Returns how long it is until the next UTC midnight.
+fn until_next_day() -> Duration {
+    let now = OffsetDateTime::now_utc();
+    let tomorrow = now
+        .date()
+        .next_day()
+        .expect("we are not at the end of time")
+        .midnight()
+        .assume_utc();
+
+    (tomorrow - now).try_into().unwrap_or_default()
+}
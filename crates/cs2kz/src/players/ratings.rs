@@ -0,0 +1,222 @@
+use futures_util::{Stream, TryStreamExt};
+
+use super::{GetPlayersError, PlayerId};
+use crate::maps::CourseFilterId;
+use crate::mode::Mode;
+use crate::pagination::{Limit, Offset, Paginated};
+use crate::{Context, database};
+
+/// A player's position on the global rating leaderboard.
+#[derive(Debug)]
+pub struct RankedPlayer {
+    pub rank: u64,
+    pub id: PlayerId,
+    pub name: String,
+    pub rating: f64,
+}
+
+#[derive(Debug)]
+pub struct GetRankingParams {
+    pub mode: Mode,
+    pub limit: Limit<1000, 100>,
+    pub offset: Offset,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to update player ratings")]
+#[from(forward)]
+pub struct UpdateRatingsError(database::Error);
+
+/// Returns players ordered by their rating on a specific mode.
+///
+/// Ratings are maintained by the points daemon, see [`update_ratings()`]. A player's own rating
+/// is also updated right away when they submit a record.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn get_ranking(
+    cx: &Context,
+    GetRankingParams { mode, limit, offset }: GetRankingParams,
+) -> Result<Paginated<impl Stream<Item = Result<RankedPlayer, GetPlayersError>>>, GetPlayersError> {
+    let total =
+        database::count!(cx.database().as_ref(), "PlayerRatings WHERE `mode` = ?", mode).await?;
+
+    let players = sqlx::query!(
+        "SELECT rank, id AS `id: PlayerId`, name, rating FROM (
+           SELECT
+             RANK() OVER (ORDER BY pr.rating DESC) AS rank,
+             p.id,
+             p.name,
+             pr.rating
+           FROM PlayerRatings AS pr
+           JOIN Players AS p ON p.id = pr.player_id
+           WHERE pr.mode = ?
+         ) AS _
+         ORDER BY rank ASC, id ASC
+         LIMIT ?
+         OFFSET ?",
+        mode,
+        limit.value(),
+        offset.value(),
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| RankedPlayer {
+        rank: row.rank as u64,
+        id: row.id,
+        name: row.name,
+        rating: row.rating,
+    })
+    .map_err(GetPlayersError::from);
+
+    Ok(Paginated::new(total, players))
+}
+
+/// Recalculates the ratings of every player who has a record on the given filter.
+///
+/// Any change to a leaderboard can shift the ranks (and therefore points) of everyone on it, so
+/// this should be called whenever the points daemon has updated a filter's best records.
+///
+/// Only [ranked] filters count towards a player's rating; players left without any ranked records
+/// lose their rating entirely.
+///
+/// [ranked]: crate::maps::courses::filters::is_ranked
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn update_ratings(
+    cx: &Context,
+    filter_id: CourseFilterId,
+    mode: Mode,
+) -> Result<u64, UpdateRatingsError> {
+    cx.database_transaction(async move |conn| {
+        update_ratings_in(conn, filter_id, mode)
+            .await
+            .map_err(UpdateRatingsError::from)
+    })
+    .await
+}
+
+/// Like [`update_ratings()`], but runs on an existing connection.
+///
+/// This lets callers recalculate ratings as part of a larger transaction, e.g. right before the
+/// filter is deleted.
+pub(crate) async fn update_ratings_in(
+    conn: &mut database::Connection,
+    filter_id: CourseFilterId,
+    mode: Mode,
+) -> database::Result<u64> {
+    recalculate_ratings(
+        conn,
+        "SELECT player_id FROM BestNubRecords WHERE filter_id = ?",
+        filter_id,
+        mode,
+    )
+    .await
+}
+
+/// Recalculates the rating of a single player.
+///
+/// This is used when a player submits a record, so their rating is up to date right away instead
+/// of only once the points daemon gets to the filter. The ratings of other players on the filter
+/// are still updated by the daemon.
+pub(crate) async fn update_player_rating_in(
+    conn: &mut database::Connection,
+    player_id: PlayerId,
+    mode: Mode,
+) -> database::Result<u64> {
+    recalculate_ratings(conn, "SELECT ? AS player_id", player_id, mode).await
+}
+
+/// Recalculates the ratings of the players returned by `affected_players`.
+///
+/// `affected_players` is a query selecting a `player_id` column, with a single parameter bound
+/// to `id`.
+async fn recalculate_ratings<Id>(
+    conn: &mut database::Connection,
+    affected_players: &str,
+    id: Id,
+    mode: Mode,
+) -> database::Result<u64>
+where
+    Id: for<'q> sqlx::Encode<'q, database::Driver> + sqlx::Type<database::Driver> + Copy + Send,
+{
+    sqlx::query(&format!(
+        "DELETE FROM PlayerRatings
+         WHERE `mode` = ?
+         AND player_id IN ({affected_players})"
+    ))
+    .bind(mode)
+    .bind(id)
+    .execute(&mut *conn)
+    .await?;
+
+    sqlx::query(&format!(
+        "INSERT INTO PlayerRatings (player_id, `mode`, rating)
+         WITH AffectedPlayers AS ({affected_players}),
+         AffectedFilters AS (
+           SELECT DISTINCT b.filter_id
+           FROM BestNubRecords AS b
+           JOIN CourseFilters AS cf ON cf.id = b.filter_id
+           WHERE b.player_id IN (SELECT player_id FROM AffectedPlayers)
+           AND b.filter_id IN (SELECT id FROM RankedCourseFilters)
+           AND cf.mode = ?
+         ),
+         NubLeaderboard AS (
+           SELECT
+             b.player_id,
+             b.points,
+             cf.nub_tier AS tier,
+             RANK() OVER (
+               PARTITION BY b.filter_id
+               ORDER BY
+                 r.time ASC,
+                 r.submitted_at ASC
+             ) AS rank
+           FROM BestNubRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           JOIN CourseFilters AS cf ON cf.id = b.filter_id
+           WHERE b.filter_id IN (SELECT filter_id FROM AffectedFilters)
+         ),
+         ProLeaderboard AS (
+           SELECT
+             b.player_id,
+             b.points,
+             cf.pro_tier AS tier,
+             RANK() OVER (
+               PARTITION BY b.filter_id
+               ORDER BY
+                 r.time ASC,
+                 r.submitted_at ASC
+             ) AS rank
+           FROM BestProRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           JOIN CourseFilters AS cf ON cf.id = b.filter_id
+           WHERE b.filter_id IN (SELECT filter_id FROM AffectedFilters)
+         ),
+         Points AS (
+           SELECT player_id, KZ_POINTS(tier, false, rank - 1, points) AS points
+           FROM NubLeaderboard
+           UNION ALL
+           SELECT player_id, KZ_POINTS(tier, true, rank - 1, points) AS points
+           FROM ProLeaderboard
+         ),
+         RankedPoints AS (
+           SELECT
+             player_id,
+             points,
+             ROW_NUMBER() OVER (
+               PARTITION BY player_id
+               ORDER BY points DESC
+             ) AS n
+           FROM Points
+           WHERE player_id IN (SELECT player_id FROM AffectedPlayers)
+         )
+         SELECT player_id, ?, SUM(points * POWER(0.975, n - 1))
+         FROM RankedPoints
+         GROUP BY player_id
+         ON DUPLICATE KEY UPDATE rating = VALUES(rating)"
+    ))
+    .bind(id)
+    .bind(mode)
+    .bind(mode)
+    .execute(&mut *conn)
+    .await
+    .map(|result| result.rows_affected())
+    .map_err(database::Error::from)
+}
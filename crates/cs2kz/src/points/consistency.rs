@@ -0,0 +1,436 @@
+//! Consistency checks for the tables derived from `Records`.
+//!
+//! `BestNubRecords`, `BestProRecords`, `RecordCounts` and `PointDistributionData` are all derived
+//! from `Records`, but they are maintained separately by [`records::submit()`] and the points
+//! daemon. A crash or bug can leave them disagreeing with `Records`; [`check()`] finds these
+//! disagreements, and [`repair()`] rebuilds the affected filters.
+//!
+//! The points daemon only persists `RecordCounts` on shutdown, so both functions are meant to be
+//! run while the API is offline.
+//!
+//! [`records::submit()`]: crate::records::submit
+
+use std::collections::BTreeSet;
+
+use futures_util::{StreamExt, TryStreamExt};
+use sqlx::Row as _;
+
+use crate::maps::courses::filters::{CourseFilterId, Tier};
+use crate::players::PlayerId;
+use crate::records::RecordId;
+use crate::{Context, database, points};
+
+/// One of a filter's two leaderboards.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq)]
+pub enum Leaderboard {
+    #[display("NUB")]
+    Nub,
+
+    #[display("PRO")]
+    Pro,
+}
+
+/// A disagreement between `Records` and one of the tables derived from it.
+#[derive(Debug, Display)]
+pub enum Violation {
+    /// A player has records on a leaderboard, but no best record.
+    #[display(
+        "filter {filter_id}: player {player_id} has no {leaderboard} best record (expected \
+         record {expected})"
+    )]
+    MissingBestRecord {
+        filter_id: CourseFilterId,
+        leaderboard: Leaderboard,
+        player_id: PlayerId,
+        expected: RecordId,
+    },
+
+    /// A player's best record is not their fastest record on the leaderboard.
+    #[display(
+        "filter {filter_id}: {leaderboard} best record of player {player_id} is {actual} \
+         (expected record {expected})"
+    )]
+    WrongBestRecord {
+        filter_id: CourseFilterId,
+        leaderboard: Leaderboard,
+        player_id: PlayerId,
+        expected: RecordId,
+        actual: RecordId,
+    },
+
+    /// A player has a best record, but no record that would qualify for the leaderboard.
+    #[display(
+        "filter {filter_id}: player {player_id} has a {leaderboard} best record ({actual}) without \
+         any qualifying records"
+    )]
+    UnexpectedBestRecord {
+        filter_id: CourseFilterId,
+        leaderboard: Leaderboard,
+        player_id: PlayerId,
+        actual: RecordId,
+    },
+
+    /// The stored record count does not match the amount of records.
+    ///
+    /// This causes the points daemon to reprocess the filter on startup.
+    #[display(
+        "filter {filter_id}: stored record count is {stored:?}, but there are {actual} records"
+    )]
+    RecordCountMismatch { filter_id: CourseFilterId, stored: Option<u64>, actual: u64 },
+
+    /// There are distribution parameters for an unranked filter or an empty leaderboard.
+    #[display("filter {filter_id}: unexpected {leaderboard} distribution parameters")]
+    UnexpectedDistribution { filter_id: CourseFilterId, leaderboard: Leaderboard },
+
+    /// The distribution parameters were fitted on a different leaderboard than the current one.
+    #[display(
+        "filter {filter_id}: {leaderboard} distribution was fitted on {fitted_on} records, but \
+         the leaderboard has {leaderboard_size}"
+    )]
+    StaleDistribution {
+        filter_id: CourseFilterId,
+        leaderboard: Leaderboard,
+        fitted_on: u64,
+        leaderboard_size: u64,
+    },
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to check database consistency")]
+#[from(forward)]
+pub struct CheckConsistencyError(database::Error);
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to repair database consistency")]
+#[from(forward)]
+pub struct RepairConsistencyError(database::Error);
+
+impl Violation {
+    /// Returns the ID of the filter this violation was found on.
+    pub fn filter_id(&self) -> CourseFilterId {
+        match *self {
+            Self::MissingBestRecord { filter_id, .. }
+            | Self::WrongBestRecord { filter_id, .. }
+            | Self::UnexpectedBestRecord { filter_id, .. }
+            | Self::RecordCountMismatch { filter_id, .. }
+            | Self::UnexpectedDistribution { filter_id, .. }
+            | Self::StaleDistribution { filter_id, .. } => filter_id,
+        }
+    }
+}
+
+impl Leaderboard {
+    fn table(self) -> &'static str {
+        match self {
+            Self::Nub => "BestNubRecords",
+            Self::Pro => "BestProRecords",
+        }
+    }
+}
+
+/// Returns every player's fastest qualifying record on a leaderboard, for every filter (or just
+/// `filter_id`) that awards best records.
+///
+/// Binds: humanly possible tier, `filter_id`.
+fn expected_best_records(leaderboard: Leaderboard) -> String {
+    let (tier_column, teleports_condition) = match leaderboard {
+        Leaderboard::Nub => ("nub_tier", ""),
+        Leaderboard::Pro => ("pro_tier", "AND r.teleports = 0"),
+    };
+
+    format!(
+        "SELECT filter_id, player_id, id AS record_id
+         FROM (
+           SELECT
+             r.filter_id,
+             r.player_id,
+             r.id,
+             ROW_NUMBER() OVER (
+               PARTITION BY r.filter_id, r.player_id
+               ORDER BY r.time ASC, r.submitted_at ASC, r.id ASC
+             ) AS n
+           FROM Records AS r
+           JOIN CourseFilters AS cf ON cf.id = r.filter_id
+           WHERE cf.{tier_column} <= ?
+           AND r.filter_id = COALESCE(?, r.filter_id)
+           {teleports_condition}
+         ) AS _
+         WHERE n = 1"
+    )
+}
+
+/// Verifies the tables derived from `Records` for a single filter, or every filter if `filter_id`
+/// is [`None`].
+///
+/// Violations are ordered by filter.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn check(
+    cx: &Context,
+    filter_id: Option<CourseFilterId>,
+) -> Result<Vec<Violation>, CheckConsistencyError> {
+    let mut violations = Vec::new();
+
+    for leaderboard in [Leaderboard::Nub, Leaderboard::Pro] {
+        let query = format!(
+            "WITH Expected AS ({expected})
+             SELECT e.filter_id, e.player_id, e.record_id AS expected, b.record_id AS actual
+             FROM Expected AS e
+             LEFT JOIN {table} AS b ON b.filter_id = e.filter_id AND b.player_id = e.player_id
+             WHERE b.record_id IS NULL
+             OR b.record_id != e.record_id
+             UNION ALL
+             SELECT b.filter_id, b.player_id, NULL, b.record_id
+             FROM {table} AS b
+             LEFT JOIN Expected AS e ON e.filter_id = b.filter_id AND e.player_id = b.player_id
+             WHERE e.record_id IS NULL
+             AND b.filter_id = COALESCE(?, b.filter_id)",
+            expected = expected_best_records(leaderboard),
+            table = leaderboard.table(),
+        );
+
+        sqlx::query(&query)
+            .bind(Tier::Death)
+            .bind(filter_id)
+            .bind(filter_id)
+            .fetch(cx.database().as_ref())
+            .map(|row| {
+                row.and_then(|row| {
+                    let filter_id = row.try_get("filter_id")?;
+                    let player_id = row.try_get("player_id")?;
+
+                    Ok(match (row.try_get("expected")?, row.try_get("actual")?) {
+                        (Some(expected), None) => Violation::MissingBestRecord {
+                            filter_id,
+                            leaderboard,
+                            player_id,
+                            expected,
+                        },
+                        (Some(expected), Some(actual)) => Violation::WrongBestRecord {
+                            filter_id,
+                            leaderboard,
+                            player_id,
+                            expected,
+                            actual,
+                        },
+                        (None, Some(actual)) => Violation::UnexpectedBestRecord {
+                            filter_id,
+                            leaderboard,
+                            player_id,
+                            actual,
+                        },
+                        (None, None) => unreachable!("at least one side of the join exists"),
+                    })
+                })
+            })
+            .try_for_each(|violation| {
+                violations.push(violation);
+                async { Ok(()) }
+            })
+            .await?;
+    }
+
+    sqlx::query!(
+        "SELECT cf.id AS `filter_id: CourseFilterId`, rc.count AS stored, COUNT(r.id) AS actual
+         FROM CourseFilters AS cf
+         LEFT JOIN RecordCounts AS rc ON rc.filter_id = cf.id
+         LEFT JOIN Records AS r ON r.filter_id = cf.id
+         WHERE cf.id = COALESCE(?, cf.id)
+         GROUP BY cf.id, rc.count
+         HAVING (stored IS NULL AND actual > 0) OR stored != actual",
+        filter_id,
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| Violation::RecordCountMismatch {
+        filter_id: row.filter_id,
+        stored: row.stored,
+        actual: row.actual as u64,
+    })
+    .try_for_each(|violation| {
+        violations.push(violation);
+        async { Ok(()) }
+    })
+    .await?;
+
+    // The daemon fits the PRO distribution on the PRO runs of the NUB leaderboard, not on
+    // `BestProRecords`.
+    sqlx::query!(
+        "WITH LeaderboardSizes AS (
+           SELECT filter_id, FALSE AS is_pro_leaderboard, COUNT(*) AS size
+           FROM BestNubRecords
+           GROUP BY filter_id
+           UNION ALL
+           SELECT b.filter_id, TRUE, COUNT(*)
+           FROM BestNubRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           WHERE r.teleports = 0
+           GROUP BY b.filter_id
+         )
+         SELECT
+           filter_id AS `filter_id: CourseFilterId`,
+           is_pro_leaderboard AS `is_pro_leaderboard: bool`,
+           fitted_on,
+           leaderboard_size AS `leaderboard_size!`,
+           is_ranked AS `is_ranked!`
+         FROM (
+           SELECT
+             d.filter_id,
+             d.is_pro_leaderboard,
+             d.leaderboard_size AS fitted_on,
+             COALESCE(s.size, 0) AS leaderboard_size,
+             (SELECT COUNT(*) FROM RankedCourseFilters WHERE id = d.filter_id) AS is_ranked
+           FROM PointDistributionData AS d
+           LEFT JOIN LeaderboardSizes AS s
+             ON s.filter_id = d.filter_id
+             AND s.is_pro_leaderboard = d.is_pro_leaderboard
+           WHERE d.filter_id = COALESCE(?, d.filter_id)
+         ) AS _
+         WHERE is_ranked = 0 OR leaderboard_size = 0 OR fitted_on != leaderboard_size",
+        filter_id,
+    )
+    .fetch(cx.database().as_ref())
+    .map_ok(|row| {
+        let leaderboard = if row.is_pro_leaderboard {
+            Leaderboard::Pro
+        } else {
+            Leaderboard::Nub
+        };
+
+        let leaderboard_size = row.leaderboard_size as u64;
+
+        if row.is_ranked == 0 || leaderboard_size == 0 {
+            Violation::UnexpectedDistribution { filter_id: row.filter_id, leaderboard }
+        } else {
+            Violation::StaleDistribution {
+                filter_id: row.filter_id,
+                leaderboard,
+                fitted_on: row.fitted_on.into(),
+                leaderboard_size,
+            }
+        }
+    })
+    .try_for_each(|violation| {
+        violations.push(violation);
+        async { Ok(()) }
+    })
+    .await?;
+
+    violations.sort_by_key(Violation::filter_id);
+
+    Ok(violations)
+}
+
+/// Rebuilds the tables derived from `Records` for the given filters.
+///
+/// Best records are replaced by every player's fastest qualifying record, distribution parameters
+/// are dropped, and record counts are reset to the actual amount of records. The filters are then
+/// queued in `FiltersToRecalculate`, so the points daemon recalculates their points and refits
+/// their distributions on its next startup.
+///
+/// Best records that did not change keep their points until then; replaced ones score 0.
+///
+/// Returns the IDs of the repaired filters.
+#[tracing::instrument(skip(cx, filter_ids), ret(level = "debug"), err(level = "debug"))]
+pub async fn repair(
+    cx: &Context,
+    filter_ids: impl IntoIterator<Item = CourseFilterId>,
+) -> Result<Vec<CourseFilterId>, RepairConsistencyError> {
+    let filter_ids = filter_ids.into_iter().collect::<BTreeSet<_>>();
+
+    for &filter_id in &filter_ids {
+        cx.database_transaction(async move |conn| {
+            rebuild_best_records(&mut *conn, filter_id).await?;
+
+            sqlx::query!("DELETE FROM PointDistributionData WHERE filter_id = ?", filter_id)
+                .execute(&mut *conn)
+                .await?;
+
+            sqlx::query!(
+                "INSERT INTO RecordCounts (filter_id, count)
+                 SELECT ?, COUNT(*) FROM Records WHERE filter_id = ?
+                 ON DUPLICATE KEY UPDATE count = VALUES(count)",
+                filter_id,
+                filter_id,
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            sqlx::query!(
+                "INSERT IGNORE INTO FiltersToRecalculate (filter_id) VALUES (?)",
+                filter_id
+            )
+            .execute(&mut *conn)
+            .await?;
+
+            Ok::<_, RepairConsistencyError>(())
+        })
+        .await?;
+
+        info!(%filter_id, "repaired filter");
+    }
+
+    Ok(filter_ids.into_iter().collect())
+}
+
+/// Replaces a filter's best records with every player's fastest qualifying record.
+///
+/// Best records that did not change keep their points; replaced ones score 0.
+pub(crate) async fn rebuild_best_records(
+    conn: &mut database::Connection,
+    filter_id: CourseFilterId,
+) -> database::Result<()> {
+    for leaderboard in [Leaderboard::Nub, Leaderboard::Pro] {
+        let extra_column = match leaderboard {
+            Leaderboard::Nub => ("", ""),
+            Leaderboard::Pro => (", points_based_on_pro_leaderboard", ", TRUE"),
+        };
+
+        // `points` has to be updated before `record_id`, as it depends on its old value
+        let upsert = format!(
+            "INSERT INTO {table} (
+               filter_id,
+               player_id,
+               record_id,
+               points,
+               points_formula_version
+               {column}
+             )
+             SELECT filter_id, player_id, record_id, 0, ? {value}
+             FROM ({expected}) AS Expected
+             ON DUPLICATE KEY
+             UPDATE points = IF({table}.record_id = VALUES(record_id), {table}.points, 0),
+                    record_id = VALUES(record_id)",
+            table = leaderboard.table(),
+            column = extra_column.0,
+            value = extra_column.1,
+            expected = expected_best_records(leaderboard),
+        );
+
+        let upserted = sqlx::query(&upsert)
+            .bind(points::formula::CURRENT.version)
+            .bind(Tier::Death)
+            .bind(filter_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        let delete = format!(
+            "DELETE FROM {table}
+             WHERE filter_id = ?
+             AND player_id NOT IN (SELECT player_id FROM ({expected}) AS Expected)",
+            table = leaderboard.table(),
+            expected = expected_best_records(leaderboard),
+        );
+
+        let deleted = sqlx::query(&delete)
+            .bind(filter_id)
+            .bind(Tier::Death)
+            .bind(filter_id)
+            .execute(&mut *conn)
+            .await?
+            .rows_affected();
+
+        debug!(%filter_id, %leaderboard, upserted, deleted, "rebuilt best records");
+    }
+
+    Ok(())
+}
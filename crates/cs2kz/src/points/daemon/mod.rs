@@ -1,70 +1,262 @@
 use std::assert_matches::assert_matches;
+use std::collections::HashSet;
 use std::collections::hash_map::{self, HashMap};
-use std::{future, iter};
+use std::sync::Arc;
+use std::time::Duration;
+use std::{future, iter, panic};
 
-use futures_util::{FutureExt, Stream, StreamExt, TryStreamExt};
-use pyo3::PyErr;
-use tokio::sync::mpsc;
+use futures_util::{FutureExt, Stream, StreamExt, TryStreamExt, stream};
+use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
+use tokio_util::time::DelayQueue;
+use tracing::Instrument;
 
 use self::record_counts::RecordCounts;
 use crate::database::{self, QueryBuilder};
 use crate::events::{self, Event};
+use crate::maps::MapId;
 use crate::maps::courses::filters::{
     self,
     CourseFilterId,
     GetCourseFiltersError,
     GetCourseFiltersParams,
 };
-use crate::points::{self, SMALL_LEADERBOARD_THRESHOLD, UpdateDistributionDataError};
+use crate::mode::Mode;
+use crate::players::{self, UpdateRatingsError};
+use crate::points::{
+    self,
+    FitDistributionError,
+    SMALL_LEADERBOARD_THRESHOLD,
+    UpdateDistributionDataError,
+};
 use crate::records::{self, BestRecord, GetRecordsError, ProPoints};
-use crate::{Context, maps, python};
+use crate::{Context, maps};
 
 mod record_counts;
 
+mod state;
+
+/// How long to wait before retrying a filter that failed to process.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+pub(crate) use state::State;
+pub use state::{InProgressFilter, ProcessedFilter, Status};
+
 #[derive(Debug, Display, Error, From)]
 pub enum Error {
     GetCourseFilter(GetCourseFiltersError),
     GetRecords(GetRecordsError),
-    #[error(ignore)]
-    #[from(ignore)]
-    Python(String),
+    FitDistribution(FitDistributionError),
     #[from(ignore)]
     GetCurrentRecordCounts(database::Error),
     #[from(ignore)]
     SaveFiltersToRecalculate(database::Error),
     UpdateDistributionData(UpdateDistributionDataError),
+    UpdateRatings(UpdateRatingsError),
 }
 
-impl From<PyErr> for Error {
-    fn from(err: PyErr) -> Self {
-        // NOTE: we convert the error into a string eagerly because trying to do so without holding
-        //       the GIL will cause a deadlock
-        Self::Python(err.to_string())
-    }
+/// Which filters to recalculate when calling [`enqueue()`].
+#[derive(Debug, Clone, Copy)]
+pub enum RecalculationScope {
+    Filter(CourseFilterId),
+    Map(MapId),
+    Mode(Mode),
+    All,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to enqueue filters for recalculation")]
+#[from(forward)]
+pub struct EnqueueRecalculationError(database::Error);
+
+/// Queues specific filters to have their points recalculated by the points daemon.
+///
+/// Unlike [`enqueue()`], this does not check whether the filters exist.
+pub(crate) fn request(cx: &Context, filter_ids: impl IntoIterator<Item = CourseFilterId>) {
+    cx.points_daemon().request(filter_ids);
+}
+
+/// Returns a snapshot of what the points daemon is currently doing.
+pub fn status(cx: &Context) -> Status {
+    cx.points_daemon().status()
+}
+
+/// Queues filters to have their points recalculated by the points daemon.
+///
+/// Returns how many filters were queued.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn enqueue(
+    cx: &Context,
+    scope: RecalculationScope,
+) -> Result<usize, EnqueueRecalculationError> {
+    let database = cx.database().as_ref();
+    let filter_ids = match scope {
+        RecalculationScope::Filter(filter_id) => {
+            sqlx::query_scalar!(
+                "SELECT id AS `id: CourseFilterId` FROM CourseFilters WHERE id = ?",
+                filter_id,
+            )
+            .fetch_all(database)
+            .await?
+        },
+        RecalculationScope::Map(map_id) => {
+            sqlx::query_scalar!(
+                "SELECT cf.id AS `id: CourseFilterId`
+                 FROM CourseFilters AS cf
+                 JOIN Courses AS c ON c.id = cf.course_id
+                 WHERE c.map_id = ?",
+                map_id,
+            )
+            .fetch_all(database)
+            .await?
+        },
+        RecalculationScope::Mode(mode) => {
+            sqlx::query_scalar!(
+                "SELECT id AS `id: CourseFilterId` FROM CourseFilters WHERE mode = ?",
+                mode,
+            )
+            .fetch_all(database)
+            .await?
+        },
+        RecalculationScope::All => {
+            sqlx::query_scalar!("SELECT id AS `id: CourseFilterId` FROM CourseFilters")
+                .fetch_all(database)
+                .await?
+        },
+    };
+
+    let amount = filter_ids.len();
+
+    cx.points_daemon().request(filter_ids);
+
+    Ok(amount)
 }
 
+/// Recalculates points for course filters whenever their leaderboards change.
+///
+/// Filters are queued by how many new records they received, and up to
+/// [`PointsConfig::workers`] filters are processed concurrently. The same filter is never
+/// processed by more than one worker at a time.
+///
+/// Additional filters can be queued with [`enqueue()`], and progress can be observed with
+/// [`status()`]. Filters which fail to process are queued again after [`RETRY_DELAY`].
+///
+/// [`PointsConfig::workers`]: crate::config::PointsConfig::workers
 #[tracing::instrument(skip_all, err)]
 pub async fn run(cx: Context, cancellation_token: CancellationToken) -> Result<(), Error> {
-    let (filter_id_tx, mut filter_id_rx) = mpsc::channel(16);
-
-    cx.spawn("record-count-tracker", |cancellation_token| {
-        track_record_counts(cx.clone(), cancellation_token, filter_id_tx)
-    });
+    let max_workers = cx.config().points.workers.get();
+    let mut workers = JoinSet::new();
+    let mut in_progress = HashSet::new();
+    let mut record_counts = RecordCounts::new();
+    let mut retries = DelayQueue::new();
+    let mut retrying = HashSet::new();
+    let (mut filter_ids, mut map_events) = filters_to_recalculate(&cx).await?;
+    let state = cx.points_daemon();
 
     loop {
+        while workers.len() < max_workers {
+            let Some((filter_id, new_records)) =
+                record_counts.pop_where(|filter_id| !in_progress.contains(filter_id))
+            else {
+                break;
+            };
+
+            let cx = cx.clone();
+
+            in_progress.insert(filter_id);
+            state.update_status(|status| status.start(filter_id, new_records));
+            workers.spawn(
+                async move { (filter_id, process_filter(&cx, filter_id, new_records).await) }
+                    .in_current_span(),
+            );
+        }
+
+        state.update_status(|status| status.queued = record_counts.len());
+
         select! {
             () = cancellation_token.cancelled() => {
+                warn!(in_progress = workers.len(), "waiting for workers to finish");
+
+                while let Some(result) = workers.join_next().await {
+                    let (filter_id, result) = result.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
+
+                    if !finish_filter(state, filter_id, result) {
+                        retrying.insert(filter_id);
+                    }
+                }
+
+                warn!("saving outstanding filters to recalculate");
+
+                let queued_filter_ids = iter::from_fn(|| record_counts.pop().map(|(filter_id, _)| filter_id))
+                    .chain(retrying)
+                    .chain(state.take_requests());
+
+                save_filters_to_recalculate(&cx, stream::iter(queued_filter_ids).chain(filter_ids)).await?;
+
                 warn!("saving record counts");
                 save_record_counts(&cx).await?;
+
                 break;
             },
 
-            result = filter_id_rx.recv() => match result {
-                None => break,
-                Some((filter_id, new_records)) => {
-                    process_filter(&cx, filter_id, new_records).await?;
-                },
+            Some(result) = workers.join_next() => {
+                let (filter_id, result) = result.unwrap_or_else(|err| panic::resume_unwind(err.into_panic()));
+
+                in_progress.remove(&filter_id);
+
+                if !finish_filter(state, filter_id, result) && retrying.insert(filter_id) {
+                    warn!(%filter_id, delay = ?RETRY_DELAY, "queueing failed filter for retry");
+                    retries.insert(filter_id, RETRY_DELAY);
+                }
+            },
+
+            Some(expired) = retries.next() => {
+                let filter_id = expired.into_inner();
+
+                // the filter might have been deleted in the meantime
+                if retrying.remove(&filter_id) {
+                    record_counts.push(filter_id);
+                }
+            },
+
+            Some(filter_id) = filter_ids.next() => {
+                record_counts.push(filter_id);
+            },
+
+            requested = state.requested() => {
+                info!(amount = requested.len(), "filters were requested to be recalculated");
+
+                for filter_id in requested {
+                    record_counts.push(filter_id);
+                }
+            },
+
+            Some(event) = map_events.next() => {
+                let name = match *event {
+                    Event::NewMap { ref name, .. } => name,
+                    Event::MapDeleted { ref filter_ids, .. } => {
+                        for &filter_id in filter_ids {
+                            record_counts.remove(filter_id);
+                            retrying.remove(&filter_id);
+                        }
+
+                        continue;
+                    },
+                    _ => continue,
+                };
+
+                let mut maps = maps::get_by_name(&cx, name);
+
+                while let Some(Ok(map)) = maps.next().await {
+                    let mut filters = filters::get(&cx, GetCourseFiltersParams {
+                        map_id: Some(map.id),
+                        ..Default::default()
+                    });
+
+                    while let Some(Ok(filters)) = filters.next().await {
+                        record_counts.remove(filters.vanilla.id);
+                        record_counts.remove(filters.classic.id);
+                    }
+                }
             },
         }
     }
@@ -72,6 +264,19 @@ pub async fn run(cx: Context, cancellation_token: CancellationToken) -> Result<(
     Ok(())
 }
 
+/// Records the result of processing a filter in the daemon's [`Status`].
+///
+/// Returns whether processing succeeded.
+fn finish_filter(state: &State, filter_id: CourseFilterId, result: Result<(), Error>) -> bool {
+    // `process_filter()` already logs its errors
+    let error = result.err().map(|error| error.to_string());
+    let succeeded = error.is_none();
+
+    state.update_status(|status| status.finish(filter_id, error));
+
+    succeeded
+}
+
 #[tracing::instrument(
     skip(cx, filter_id),
     fields(id = %filter_id, mode = tracing::field::Empty),
@@ -83,13 +288,24 @@ async fn process_filter(
     new_records: u64,
 ) -> Result<(), Error> {
     let span = tracing::Span::current();
-    let (mode, filter) = filters::get_by_id(cx, filter_id)
-        .await?
-        .expect("there should be a valid filter with this ID");
+    let Some((mode, filter)) = filters::get_by_id(cx, filter_id).await? else {
+        warn!("filter does not exist anymore; skipping");
+        return Ok(());
+    };
 
     span.record("mode", tracing::field::debug(mode));
     info!("processing filter");
 
+    if !filters::is_ranked(cx, filter.id).await? {
+        info!("filter is not ranked; removing its points");
+
+        points::delete_distribution_data(cx, filter.id).await?;
+        records::clear_points(cx, filter.id).await?;
+        players::update_ratings(cx, filter.id, mode).await?;
+
+        return Ok(());
+    }
+
     let mut nub_leaderboard = Vec::new();
     let mut pro_leaderboard = Vec::new();
     let mut records = records::get_leaderboard(cx, filter.id);
@@ -103,20 +319,20 @@ async fn process_filter(
     }
 
     let (nub_dist, nub_leaderboard, pro_dist, pro_leaderboard) =
-        python::execute(span.clone(), move |py| -> Result<_, Error> {
+        points::compute(span.clone(), move || -> Result<_, Error> {
             info!(
                 size = nub_leaderboard.len(),
                 "calculating distribution parameters for the NUB leaderboard",
             );
 
-            let nub_dist = points::Distribution::new(py, &nub_leaderboard)?;
+            let nub_dist = points::Distribution::new(&nub_leaderboard)?;
 
             info!(
                 size = pro_leaderboard.len(),
                 "calculating distribution parameters for the PRO leaderboard",
             );
 
-            let pro_dist = points::Distribution::new(py, &pro_leaderboard)?;
+            let pro_dist = points::Distribution::new(&pro_leaderboard)?;
 
             info!("done calculating distribution parameters");
 
@@ -126,11 +342,19 @@ async fn process_filter(
 
     info!("updating distribution data");
 
-    points::update_distribution_data(cx, filter.id, nub_dist.as_ref(), pro_dist.as_ref()).await?;
+    points::update_distribution_data(
+        cx,
+        filter.id,
+        nub_dist.as_ref(),
+        nub_leaderboard.len(),
+        pro_dist.as_ref(),
+        pro_leaderboard.len(),
+    )
+    .await?;
 
     info!("recalculating points");
 
-    let records = python::execute(span.clone(), move |py| -> Result<_, Error> {
+    let records = points::compute(span.clone(), move || -> Result<_, Error> {
         let mut records = HashMap::new();
         let mut nub_dist_points_so_far = Vec::with_capacity(nub_leaderboard.len());
         let mut scaled_nub_times = Vec::with_capacity(nub_leaderboard.len());
@@ -159,17 +383,15 @@ async fn process_filter(
                 } else {
                     debug!("calculating points from distribution");
 
-                    points::from_dist(
-                        py,
+                    let points = points::from_dist(
                         nub_dist,
                         &scaled_nub_times,
                         &nub_dist_points_so_far,
                         rank,
-                    )
-                    .map(|points| {
-                        nub_dist_points_so_far.push(points);
-                        (points / nub_dist.top_scale).min(1.0)
-                    })?
+                    );
+
+                    nub_dist_points_so_far.push(points);
+                    (points / nub_dist.top_scale).min(1.0)
                 };
 
                 let slot = records.insert(entry.record_id, BestRecord {
@@ -212,17 +434,15 @@ async fn process_filter(
                 } else {
                     debug!("calculating points from distribution");
 
-                    points::from_dist(
-                        py,
+                    let points = points::from_dist(
                         pro_dist,
                         &scaled_pro_times,
                         &pro_dist_points_so_far,
                         rank,
-                    )
-                    .map(|points| {
-                        pro_dist_points_so_far.push(points);
-                        (points / pro_dist.top_scale).min(1.0)
-                    })?
+                    );
+
+                    pro_dist_points_so_far.push(points);
+                    (points / pro_dist.top_scale).min(1.0)
                 };
 
                 let (Ok(rank_in_nub_leaderboard) | Err(rank_in_nub_leaderboard)) =
@@ -238,14 +458,14 @@ async fn process_filter(
                         entry.time.into(),
                     )
                 } else {
-                    points::from_dist(
-                        py,
+                    let points = points::from_dist(
                         nub_dist,
                         &scaled_nub_times,
                         &nub_dist_points_so_far,
                         rank_in_nub_leaderboard,
-                    )
-                    .map(|points| (points / nub_dist.top_scale).min(1.0))?
+                    );
+
+                    (points / nub_dist.top_scale).min(1.0)
                 };
 
                 let points_based_on_pro_leaderboard = pro_points >= nub_points;
@@ -283,18 +503,25 @@ async fn process_filter(
 
     records::update_best_records(cx, filter.id, records.into_values()).await?;
 
+    info!("updating player ratings");
+
+    players::update_ratings(cx, filter.id, mode).await?;
+
     Ok(())
 }
 
-async fn track_record_counts(
-    cx: Context,
-    cancellation_token: CancellationToken,
-    filter_id_tx: mpsc::Sender<(CourseFilterId, u64)>,
-) -> Result<(), Error> {
-    let mut permit = None;
-    let mut record_counts = RecordCounts::new();
-
-    let mut old_maps = events::subscribe();
+/// Returns a stream of filters that need to be recalculated, as well as a stream of map events
+/// that might invalidate them.
+///
+/// This includes filters that were still queued during the last shutdown, filters whose record
+/// counts changed while we were offline, and filters that receive new records from now on.
+async fn filters_to_recalculate(
+    cx: &Context,
+) -> Result<
+    (impl Stream<Item = CourseFilterId> + Unpin, impl Stream<Item = Arc<Event>> + Unpin),
+    Error,
+> {
+    let map_events = events::subscribe();
 
     let filters_from_last_time = sqlx::query_scalar!(
         "SELECT filter_id AS `filter_id: CourseFilterId`
@@ -311,6 +538,25 @@ async fn track_record_counts(
         })
     });
 
+    // filters with records that were scored by a different version of the points formula
+    let filters_with_outdated_points = sqlx::query_scalar(
+        "SELECT filter_id FROM BestNubRecords WHERE points_formula_version != ?
+         UNION
+         SELECT filter_id FROM BestProRecords WHERE points_formula_version != ?",
+    )
+    .bind(points::formula::CURRENT.version)
+    .bind(points::formula::CURRENT.version)
+    .fetch(cx.database().as_ref())
+    .filter_map(|result: sqlx::Result<CourseFilterId>| {
+        future::ready(match result {
+            Ok(filter_id) => Some(filter_id),
+            Err(error) => {
+                error!(%error, "failed to fetch filter id with outdated points from database");
+                None
+            },
+        })
+    });
+
     let filters_of_changed_record_counts = {
         let current_counts = sqlx::query!(
             "SELECT filter_id AS `filter_id!: CourseFilterId`, count FROM (
@@ -360,51 +606,12 @@ async fn track_record_counts(
         })
     });
 
-    let mut filter_ids = filters_from_last_time
+    let filter_ids = filters_from_last_time
+        .chain(filters_with_outdated_points)
         .chain(filters_of_changed_record_counts)
         .chain(new_filter_ids);
 
-    loop {
-        select! {
-            () = cancellation_token.cancelled() => {
-                warn!("saving outstanding filters to recalculate");
-                save_filters_to_recalculate(&cx, filter_ids).await?;
-                break Ok(());
-            },
-
-            Ok(new_permit) = filter_id_tx.reserve(), if permit.is_none() => {
-                permit = Some(new_permit);
-            },
-
-            Some(filter_id) = filter_ids.next() => {
-                record_counts.push(filter_id);
-
-                if let Some(permit) = permit.take() {
-                    permit.send(record_counts.pop().unwrap());
-                }
-            },
-
-            Some(event) = old_maps.next() => {
-                let Event::NewMap { ref name, .. } = *event else {
-                    continue;
-                };
-
-                let mut maps = maps::get_by_name(&cx, name);
-
-                while let Some(Ok(map)) = maps.next().await {
-                    let mut filters = filters::get(&cx, GetCourseFiltersParams {
-                        map_id: Some(map.id),
-                        ..Default::default()
-                    });
-
-                    while let Some(Ok(filters)) = filters.next().await {
-                        record_counts.remove(filters.vanilla.id);
-                        record_counts.remove(filters.classic.id);
-                    }
-                }
-            },
-        }
-    }
+    Ok((filter_ids, map_events))
 }
 
 async fn save_record_counts(cx: &Context) -> Result<(), Error> {
@@ -13,6 +13,10 @@ impl<T> Counts<T> {
         Self { entries: VecDeque::new() }
     }
 
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
     pub fn push(&mut self, value: T)
     where
         T: Eq,
@@ -41,6 +45,14 @@ impl<T> Counts<T> {
         self.entries.pop_front()
     }
 
+    /// Pops the entry with the highest count that satisfies `predicate`.
+    pub fn pop_where(&mut self, mut predicate: impl FnMut(&T) -> bool) -> Option<(T, u64)> {
+        self.entries
+            .iter()
+            .position(|entry| predicate(&entry.0))
+            .and_then(|idx| self.entries.remove(idx))
+    }
+
     pub fn remove(&mut self, value: T) -> Option<u64>
     where
         T: Eq,
@@ -118,4 +130,18 @@ mod tests {
         assert_eq!(counts.pop(), Some((1, 2)));
         assert_eq!(counts.entries, vec![(0, 1), (2, 1)]);
     }
+
+    #[test]
+    fn pop_where_skips_rejected_entries() {
+        let mut counts = Counts::<i32>::new();
+
+        counts.push(0);
+        counts.push(1);
+        counts.push(1);
+        counts.push(2);
+
+        assert_eq!(counts.pop_where(|&value| value != 1), Some((0, 1)));
+        assert_eq!(counts.entries, vec![(1, 2), (2, 1)]);
+        assert_eq!(counts.pop_where(|_| false), None);
+    }
 }
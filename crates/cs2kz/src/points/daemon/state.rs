@@ -0,0 +1,141 @@
+use std::mem;
+use std::sync::Mutex;
+
+use tokio::sync::Notify;
+
+use crate::maps::courses::CourseFilterId;
+use crate::time::{Seconds, Timestamp};
+
+/// How many processed filters are kept around for [`Status::recent`].
+const MAX_RECENT: usize = 32;
+
+/// State shared between the points daemon and the rest of the application.
+#[derive(Debug, Default)]
+pub(crate) struct State {
+    /// Filters that were explicitly requested to be recalculated.
+    requests: Mutex<Vec<CourseFilterId>>,
+
+    /// Notified whenever `requests` is extended.
+    requested: Notify,
+
+    status: Mutex<Status>,
+}
+
+/// A snapshot of what the points daemon is currently doing.
+#[derive(Debug, Default, Clone)]
+pub struct Status {
+    /// How many filters are waiting to be processed.
+    pub queued: usize,
+
+    /// Filters that are currently being processed.
+    pub in_progress: Vec<InProgressFilter>,
+
+    /// How many filters have been processed successfully since startup.
+    pub processed: u64,
+
+    /// How many filters failed to process since startup.
+    pub failed: u64,
+
+    /// The most recently processed filters, newest first.
+    pub recent: Vec<ProcessedFilter>,
+}
+
+#[derive(Debug, Clone)]
+pub struct InProgressFilter {
+    pub filter_id: CourseFilterId,
+
+    /// How many new records caused this filter to be queued.
+    pub new_records: u64,
+
+    pub started_at: Timestamp,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProcessedFilter {
+    pub filter_id: CourseFilterId,
+    pub started_at: Timestamp,
+    pub took: Seconds,
+
+    /// The error that occurred while processing the filter, if any.
+    pub error: Option<String>,
+}
+
+impl State {
+    pub(crate) fn status(&self) -> Status {
+        self.status
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    pub(super) fn update_status(&self, f: impl FnOnce(&mut Status)) {
+        f(&mut self.status.lock().unwrap_or_else(|err| err.into_inner()));
+    }
+
+    /// Requests the daemon to recalculate the given filters.
+    pub(super) fn request(&self, filter_ids: impl IntoIterator<Item = CourseFilterId>) {
+        self.requests
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .extend(filter_ids);
+
+        self.requested.notify_one();
+    }
+
+    /// Waits for new requests and returns them.
+    pub(super) async fn requested(&self) -> Vec<CourseFilterId> {
+        loop {
+            let requests = self.take_requests();
+
+            if !requests.is_empty() {
+                return requests;
+            }
+
+            self.requested.notified().await;
+        }
+    }
+
+    /// Returns any outstanding requests without waiting.
+    pub(super) fn take_requests(&self) -> Vec<CourseFilterId> {
+        mem::take(&mut *self.requests.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+}
+
+impl Status {
+    pub(super) fn start(&mut self, filter_id: CourseFilterId, new_records: u64) {
+        self.in_progress.push(InProgressFilter {
+            filter_id,
+            new_records,
+            started_at: Timestamp::now(),
+        });
+    }
+
+    pub(super) fn finish(&mut self, filter_id: CourseFilterId, error: Option<String>) {
+        let Some(idx) = self
+            .in_progress
+            .iter()
+            .position(|filter| filter.filter_id == filter_id)
+        else {
+            return;
+        };
+
+        let InProgressFilter { started_at, .. } = self.in_progress.remove(idx);
+        let took = (Timestamp::now() - started_at)
+            .try_into()
+            .unwrap_or_default();
+
+        if error.is_some() {
+            self.failed += 1;
+        } else {
+            self.processed += 1;
+        }
+
+        self.recent.truncate(MAX_RECENT - 1);
+        self.recent.insert(0, ProcessedFilter {
+            filter_id,
+            started_at,
+            took: Seconds(took),
+            error,
+        });
+    }
+}
@@ -1,6 +1,4 @@
-use pyo3::types::{PyAnyMethods, PyList, PyTuple};
-use pyo3::{PyErr, Python};
-
+use super::nig::{self, FitDistributionError};
 use crate::num::AsF64;
 
 /// [Normal-inverse Gaussian distribution][norminvgauss] parameters.
@@ -17,24 +15,16 @@ pub struct Distribution {
 
 impl Distribution {
     /// Calculates the distribution parameters using `times` as the input dataset.
-    pub fn new(py: Python<'_>, times: &[impl AsF64]) -> Result<Option<Self>, PyErr> {
+    ///
+    /// This is CPU-heavy for large datasets and should not be called on an async runtime thread.
+    pub fn new(times: &[impl AsF64]) -> Result<Option<Self>, FitDistributionError> {
         let Some(top_time) = times.first().map(AsF64::as_f64) else {
             return Ok(None);
         };
 
-        let norminvgauss = py.import("scipy.stats")?.getattr("norminvgauss")?;
-
-        let (a, b, loc, scale) = norminvgauss
-            .getattr("fit")?
-            .call1((PyList::new(py, times.iter().map(AsF64::as_f64))?,))?
-            .downcast_into::<PyTuple>()?
-            .extract::<(f64, f64, f64, f64)>()?;
-
-        let top_scale = norminvgauss
-            .call1((a, b, loc, scale))?
-            .getattr("sf")?
-            .call1((top_time,))?
-            .extract::<f64>()?;
+        let times = times.iter().map(AsF64::as_f64).collect::<Vec<_>>();
+        let nig::Params { a, b, loc, scale } = nig::fit(&times)?;
+        let top_scale = nig::sf((top_time - loc) / scale, a, b);
 
         Ok(Some(Self { a, b, loc, scale, top_scale }))
     }
@@ -47,14 +37,7 @@ impl Distribution {
     }
 
     /// Calls the distribution's survival function with the given `value` as the input.
-    pub fn sf(&self, py: Python<'_>, value: f64) -> Result<f64, PyErr> {
-        let Distribution { a, b, loc, scale, .. } = *self;
-
-        py.import("scipy.stats")?
-            .getattr("norminvgauss")?
-            .call1((a, b, loc, scale))?
-            .getattr("sf")?
-            .call1((value,))?
-            .extract::<f64>()
+    pub fn sf(&self, value: f64) -> f64 {
+        nig::sf((value - self.loc) / self.scale, self.a, self.b)
     }
 }
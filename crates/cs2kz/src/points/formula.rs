@@ -0,0 +1,225 @@
+//! The points formula.
+//!
+//! [`CURRENT`] is the single definition of how records are scored. The Rust code reads it
+//! directly, and [`install()`] generates the `KZ_POINTS` SQL function from it on startup, so the
+//! two can never disagree.
+//!
+//! Every change to [`CURRENT`] must bump its [`version`]. Best records remember which version
+//! scored them, and the points daemon recalculates any filter with records scored by a different
+//! version. [`install()`] fails if [`CURRENT`] was changed without bumping it.
+//!
+//! [`version`]: Formula::version
+
+use std::fmt::Write as _;
+
+use sqlx::types::Json as SqlJson;
+
+use crate::database::{self, Database};
+
+/// The formula currently used for scoring records.
+pub const CURRENT: Formula = Formula {
+    version: 1,
+    max_points: 10_000.0,
+    tier_points: [
+        0.0, 500.0, 2_000.0, 3_500.0, 5_000.0, 6_500.0, 8_000.0, 9_500.0,
+    ],
+    pro_tier_points: [
+        1_000.0, 1_450.0, 2_800.0, 4_150.0, 5_500.0, 6_850.0, 8_200.0, 9_550.0,
+    ],
+    rank_share: 0.125,
+    rank_points: RankPoints {
+        top_100: 0.004,
+        top_20: 0.02,
+        top_5: [0.2, 0.12, 0.09, 0.06, 0.02],
+    },
+    small_leaderboard: SmallLeaderboard {
+        threshold: 50,
+        base: 2.1,
+        per_tier: 0.25,
+        midpoint: 1.5,
+    },
+};
+
+/// A versioned definition of how records are scored.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct Formula {
+    /// Identifies this formula; must be bumped on every change.
+    pub version: u16,
+
+    /// The maximum points for any record.
+    pub max_points: f64,
+
+    /// Base points for completing a course on the NUB leaderboard, indexed by tier.
+    pub tier_points: [f64; 8],
+
+    /// Base points for completing a course on the PRO leaderboard, indexed by tier.
+    pub pro_tier_points: [f64; 8],
+
+    /// The share of the remaining (non-tier) points awarded based on rank.
+    ///
+    /// The rest is awarded based on the leaderboard's distribution.
+    pub rank_share: f64,
+
+    pub rank_points: RankPoints,
+    pub small_leaderboard: SmallLeaderboard,
+}
+
+/// Bonuses for achieving a high rank, as fractions of the rank share.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct RankPoints {
+    /// Awarded for every rank below 100 the record is ahead of.
+    pub top_100: f64,
+
+    /// Awarded for every rank below 20 the record is ahead of.
+    pub top_20: f64,
+
+    /// Flat bonuses for the top 5 ranks.
+    pub top_5: [f64; 5],
+}
+
+/// Parameters for scoring leaderboards too small to fit a distribution on.
+#[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct SmallLeaderboard {
+    /// Leaderboards with this many records or fewer count as "small".
+    pub threshold: usize,
+
+    pub base: f64,
+    pub per_tier: f64,
+
+    /// The time relative to the top time at which half of the distribution points are awarded.
+    pub midpoint: f64,
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum InstallFormulaError {
+    #[display("points formula v{version} was changed without bumping its version")]
+    #[error(ignore)]
+    #[from(ignore)]
+    DefinitionChanged { version: u16 },
+
+    #[display("failed to install points formula")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+impl Formula {
+    /// Generates the `KZ_POINTS` SQL function for this formula.
+    ///
+    /// The function has the same signature and semantics as [`points::complete()`].
+    ///
+    /// [`points::complete()`]: super::complete
+    pub fn kz_points_function(&self) -> String {
+        let mut sql = String::from(
+            "CREATE OR REPLACE FUNCTION KZ_POINTS(
+               tier INT1 UNSIGNED,
+               is_pro_leaderboard BOOLEAN,
+               rank INT4 UNSIGNED,
+               dist_points FLOAT8
+             ) RETURNS FLOAT8
+             DETERMINISTIC
+             BEGIN
+               DECLARE for_tier, remaining, for_rank FLOAT8;
+
+               IF (is_pro_leaderboard) THEN
+                 SET for_tier = CASE tier",
+        );
+
+        for (tier, points) in (1..).zip(self.pro_tier_points) {
+            let _ = write!(sql, " WHEN {tier} THEN {points:?}");
+        }
+
+        sql.push_str(" END; ELSE SET for_tier = CASE tier");
+
+        for (tier, points) in (1..).zip(self.tier_points) {
+            let _ = write!(sql, " WHEN {tier} THEN {points:?}");
+        }
+
+        let _ = write!(
+            sql,
+            " END; END IF;
+
+              SET remaining = {max_points:?} - for_tier;
+              SET for_rank = 0;
+
+              IF (rank < 100) THEN
+                SET for_rank = (100 - rank) * {top_100:?};
+              END IF;
+
+              IF (rank < 20) THEN
+                SET for_rank = for_rank + (20 - rank) * {top_20:?};
+              END IF;
+
+              SET for_rank = for_rank + (CASE rank",
+            max_points = self.max_points,
+            top_100 = self.rank_points.top_100,
+            top_20 = self.rank_points.top_20,
+        );
+
+        for (rank, points) in self.rank_points.top_5.iter().enumerate() {
+            let _ = write!(sql, " WHEN {rank} THEN {points:?}");
+        }
+
+        let _ = write!(
+            sql,
+            " ELSE 0 END);
+
+              RETURN for_tier
+                + ({rank_share:?} * remaining * for_rank)
+                + ({dist_share:?} * remaining * dist_points);
+            END",
+            rank_share = self.rank_share,
+            dist_share = 1.0 - self.rank_share,
+        );
+
+        sql
+    }
+}
+
+/// Installs [`CURRENT`] into the database.
+///
+/// This records the formula's definition in the `PointsFormulas` table and (re)creates the
+/// `KZ_POINTS` SQL function. Returns whether this version was installed for the first time.
+///
+/// If this version was installed before with a different definition, [`CURRENT`] was changed
+/// without bumping its version. Existing points would then silently disagree with new ones, so
+/// this returns an error instead of installing anything.
+#[tracing::instrument(level = "debug", skip(database), err)]
+pub async fn install(database: &Database) -> Result<bool, InstallFormulaError> {
+    let is_new =
+        sqlx::query("INSERT IGNORE INTO PointsFormulas (version, definition) VALUES (?, ?)")
+            .bind(CURRENT.version)
+            .bind(SqlJson(&CURRENT))
+            .execute(database.as_ref())
+            .await?
+            .rows_affected()
+            > 0;
+
+    if !is_new {
+        let SqlJson(installed) = sqlx::query_scalar::<_, SqlJson<serde_json::Value>>(
+            "SELECT definition FROM PointsFormulas WHERE version = ?",
+        )
+        .bind(CURRENT.version)
+        .fetch_one(database.as_ref())
+        .await?;
+
+        if serde_json::from_value::<Formula>(installed.clone()).ok() != Some(CURRENT) {
+            error!(
+                version = CURRENT.version,
+                %installed,
+                "points formula differs from the installed definition of the same version",
+            );
+
+            return Err(InstallFormulaError::DefinitionChanged { version: CURRENT.version });
+        }
+    }
+
+    sqlx::raw_sql(&CURRENT.kz_points_function())
+        .execute(database.as_ref())
+        .await?;
+
+    if is_new {
+        info!(version = CURRENT.version, "installed new points formula");
+    }
+
+    Ok(is_new)
+}
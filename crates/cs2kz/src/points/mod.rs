@@ -1,34 +1,29 @@
 use std::iter;
 
-use pyo3::types::{PyAnyMethods, PyTuple};
-use pyo3::{PyErr, Python};
+use tokio::task;
 
 use crate::maps::courses::filters::{CourseFilterId, Tier};
-use crate::{Context, database, python};
+use crate::time::Timestamp;
+use crate::{Context, database};
+
+mod nig;
+pub use nig::FitDistributionError;
 
 mod distribution;
 pub use distribution::Distribution;
 
+mod projection;
+pub use projection::{ProjectPointsError, ProjectedRecord, Projection, project};
+
+pub mod consistency;
 pub mod daemon;
+pub mod formula;
 
 /// The maximum points for any record.
-pub const MAX: f64 = 10_000.0;
+pub const MAX: f64 = formula::CURRENT.max_points;
 
 /// Threshold for what counts as a "small" leaderboard.
-pub const SMALL_LEADERBOARD_THRESHOLD: usize = 50;
-
-#[derive(Debug, Display, Error)]
-#[display("failed to calculate points: {_0}")]
-#[error(ignore)]
-pub struct CalculatePointsError(String);
-
-impl From<PyErr> for CalculatePointsError {
-    fn from(err: PyErr) -> Self {
-        // NOTE: we convert the error into a string eagerly because trying to do so without holding
-        //       the GIL will cause a deadlock
-        Self(err.to_string())
-    }
-}
+pub const SMALL_LEADERBOARD_THRESHOLD: usize = formula::CURRENT.small_leaderboard.threshold;
 
 /// Calculates points for a new record with the given `time` at position `rank` in the
 /// leaderboard.
@@ -42,36 +37,81 @@ pub fn calculate(
     leaderboard_size: usize,
     top_time: f64,
     time: f64,
-) -> impl Future<Output = Result<f64, CalculatePointsError>> {
-    python::execute(tracing::Span::current(), move |py| match (dist, leaderboard_size) {
+) -> impl Future<Output = f64> {
+    compute(tracing::Span::current(), move || match (dist, leaderboard_size) {
         (None, _) | (Some(_), ..=SMALL_LEADERBOARD_THRESHOLD) => {
-            Ok(for_small_leaderboard(tier, top_time, time))
+            for_small_leaderboard(tier, top_time, time)
         },
         (Some(dist), _) => {
-            let sf = dist.sf(py, time)?;
+            let sf = dist.sf(time);
 
+            // storing NaN would poison the player's rating, so treat the record as not earning
+            // any points from the distribution instead
             if sf.is_nan() {
                 warn!(?dist, leaderboard_size, top_time, time, "sf returned NaN");
-                // return Ok(0.0);
+                return 0.0;
             }
 
-            Ok(sf / dist.top_scale)
+            sf / dist.top_scale
         },
     })
 }
 
+/// Runs a CPU-bound points computation on Tokio's blocking thread pool.
+async fn compute<T>(span: tracing::Span, f: impl FnOnce() -> T + Send + 'static) -> T
+where
+    T: Send + 'static,
+{
+    task::spawn_blocking(move || span.in_scope(f))
+        .await
+        .expect("task does not panic")
+}
+
+/// The individual components that make up a record's points.
+///
+/// See [`breakdown()`].
+#[derive(Debug, Clone, Copy)]
+pub struct Breakdown {
+    /// Points awarded for the course's difficulty.
+    pub for_tier: f64,
+
+    /// Points awarded for the record's rank on the leaderboard.
+    pub for_rank: f64,
+
+    /// Points awarded for the record's performance relative to the rest of the leaderboard.
+    pub from_dist: f64,
+}
+
+impl Breakdown {
+    pub fn total(&self) -> f64 {
+        self.for_tier + self.for_rank + self.from_dist
+    }
+}
+
 /// "Completes" pre-calculated distribution points cached in the database.
 ///
 /// # Panics
 ///
 /// This function will panic if <code>tier > [Tier::Death]</code>.
 pub fn complete(tier: Tier, is_pro_leaderboard: bool, rank: usize, dist_points: f64) -> f64 {
+    breakdown(tier, is_pro_leaderboard, rank, dist_points).total()
+}
+
+/// Like [`complete()`], but returns the individual components instead of their sum.
+///
+/// # Panics
+///
+/// This function will panic if <code>tier > [Tier::Death]</code>.
+pub fn breakdown(tier: Tier, is_pro_leaderboard: bool, rank: usize, dist_points: f64) -> Breakdown {
+    let rank_share = formula::CURRENT.rank_share;
     let for_tier = for_tier(tier, is_pro_leaderboard);
     let remaining = MAX - for_tier;
-    let for_rank = 0.125 * remaining * for_rank(rank);
-    let from_dist = 0.875 * remaining * dist_points;
 
-    for_tier + for_rank + from_dist
+    Breakdown {
+        for_tier,
+        for_rank: rank_share * remaining * for_rank(rank),
+        from_dist: (1.0 - rank_share) * remaining * dist_points,
+    }
 }
 
 /// Calculates the amount of points to award for completing a difficult course.
@@ -80,30 +120,29 @@ pub fn complete(tier: Tier, is_pro_leaderboard: bool, rank: usize, dist_points:
 ///
 /// This function will panic if <code>tier > [Tier::Death]</code>.
 pub const fn for_tier(tier: Tier, is_pro_leaderboard: bool) -> f64 {
-    const POINTS_BY_TIER: [f64; 8] = [
-        0.0, 500.0, 2_000.0, 3_500.0, 5_000.0, 6_500.0, 8_000.0, 9_500.0,
-    ];
-
-    const POINTS_BY_TIER_PRO: [f64; 8] = [
-        1_000.0, 1_450.0, 2_800.0, 4_150.0, 5_500.0, 6_850.0, 8_200.0, 9_550.0,
-    ];
+    let points_by_tier = if is_pro_leaderboard {
+        &formula::CURRENT.pro_tier_points
+    } else {
+        &formula::CURRENT.tier_points
+    };
 
-    [POINTS_BY_TIER, POINTS_BY_TIER_PRO][is_pro_leaderboard as usize][(tier as usize) - 1]
+    points_by_tier[(tier as usize) - 1]
 }
 
 /// Calculates the amount of points to award for achieving a high rank on the leaderboard.
 pub fn for_rank(rank: usize) -> f64 {
+    let rank_points = &formula::CURRENT.rank_points;
     let mut points = 0.0;
 
     if rank < 100 {
-        points += ((100 - rank) as f64) * 0.004;
+        points += ((100 - rank) as f64) * rank_points.top_100;
     }
 
     if rank < 20 {
-        points += ((20 - rank) as f64) * 0.02;
+        points += ((20 - rank) as f64) * rank_points.top_20;
     }
 
-    if let Some(&extra) = [0.2, 0.12, 0.09, 0.06, 0.02].get(rank) {
+    if let Some(&extra) = rank_points.top_5.get(rank) {
         points += extra;
     }
 
@@ -119,9 +158,10 @@ pub fn for_small_leaderboard(tier: Tier, top_time: f64, time: f64) -> f64 {
 
     assert!(top_time <= time);
 
-    let x = 2.1 - 0.25 * (tier as u8 as f64);
-    let y = 1.0 + (x * -0.5).exp();
-    let z = 1.0 + (x * (time / top_time - 1.5)).exp();
+    let params = &formula::CURRENT.small_leaderboard;
+    let x = params.base - params.per_tier * (tier as u8 as f64);
+    let y = 1.0 + (x * (1.0 - params.midpoint)).exp();
+    let z = 1.0 + (x * (time / top_time - params.midpoint)).exp();
 
     y / z
 }
@@ -136,15 +176,14 @@ pub fn for_small_leaderboard(tier: Tier, top_time: f64, time: f64) -> f64 {
 /// - `dist_points_so_far`: results returned by previous calls to this function
 /// - `rank`: 0-indexed position of the record on the leaderboard
 pub fn from_dist(
-    py: Python<'_>,
     dist: &Distribution,
     scaled_times: &[f64],
     dist_points_so_far: &[f64],
     rank: usize,
-) -> Result<f64, PyErr> {
+) -> f64 {
     // we already calculated this
     if rank == 0 {
-        return Ok(dist.top_scale);
+        return dist.top_scale;
     }
 
     let curr_time = scaled_times[rank];
@@ -152,23 +191,108 @@ pub fn from_dist(
 
     // `rank` and `rank - 1` are tied, so just award the same points
     if curr_time == prev_time {
-        return Ok(dist_points_so_far[rank - 1]);
+        return dist_points_so_far[rank - 1];
     }
 
-    let pdf = py
-        .import("scipy.stats")?
-        .getattr("norminvgauss")?
-        .getattr("_pdf")?;
+    let diff = nig::integrate_pdf(prev_time, curr_time, dist.a, dist.b);
 
-    let (diff, _) = py
-        .import("scipy")?
-        .getattr("integrate")?
-        .getattr("quad")?
-        .call1((pdf, prev_time, curr_time, (dist.a, dist.b)))?
-        .downcast_into::<PyTuple>()?
-        .extract::<(f64, f64)>()?;
+    dist_points_so_far[rank - 1] - diff
+}
 
-    Ok(dist_points_so_far[rank - 1] - diff)
+/// The stored distribution parameters of a filter's leaderboards.
+#[derive(Debug, Default)]
+pub struct DistributionData {
+    pub nub: Option<LeaderboardDistribution>,
+    pub pro: Option<LeaderboardDistribution>,
+}
+
+#[derive(Debug)]
+pub struct LeaderboardDistribution {
+    pub dist: Distribution,
+
+    /// The amount of records the distribution was fitted on.
+    pub leaderboard_size: u32,
+
+    /// When the distribution was last recalculated.
+    pub updated_at: Timestamp,
+}
+
+#[derive(Debug, Display, Error, From)]
+#[display("failed to get point distribution data")]
+#[from(forward)]
+pub struct GetDistributionDataError(database::Error);
+
+/// Returns the distribution parameters stored for a filter.
+///
+/// Returns [`None`] if the filter does not exist.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn get_distribution_data(
+    cx: &Context,
+    filter_id: CourseFilterId,
+) -> Result<Option<DistributionData>, GetDistributionDataError> {
+    let rows = sqlx::query!(
+        "SELECT
+           d.is_pro_leaderboard AS `is_pro_leaderboard: bool`,
+           d.a,
+           d.b,
+           d.loc,
+           d.scale,
+           d.top_scale,
+           d.leaderboard_size,
+           d.updated_at AS `updated_at: Timestamp`
+         FROM CourseFilters AS cf
+         LEFT JOIN PointDistributionData AS d ON d.filter_id = cf.id
+         WHERE cf.id = ?",
+        filter_id,
+    )
+    .fetch_all(cx.database().as_ref())
+    .await?;
+
+    if rows.is_empty() {
+        return Ok(None);
+    }
+
+    let mut data = DistributionData::default();
+
+    for row in rows {
+        // the filter exists, but has no distribution data yet
+        let (
+            Some(is_pro_leaderboard),
+            Some(a),
+            Some(b),
+            Some(loc),
+            Some(scale),
+            Some(top_scale),
+            Some(leaderboard_size),
+            Some(updated_at),
+        ) = (
+            row.is_pro_leaderboard,
+            row.a,
+            row.b,
+            row.loc,
+            row.scale,
+            row.top_scale,
+            row.leaderboard_size,
+            row.updated_at,
+        )
+        else {
+            continue;
+        };
+
+        let leaderboard = LeaderboardDistribution {
+            dist: Distribution { a, b, loc, scale, top_scale },
+            leaderboard_size,
+            updated_at,
+        };
+
+        if is_pro_leaderboard {
+            data.pro = Some(leaderboard);
+        } else {
+            data.nub = Some(leaderboard);
+        }
+    }
+
+    Ok(Some(data))
 }
 
 #[derive(Debug, Display, Error, From)]
@@ -176,16 +300,23 @@ pub fn from_dist(
 #[from(forward)]
 pub struct UpdateDistributionDataError(database::Error);
 
+/// Stores the distribution parameters for a filter's leaderboards.
+///
+/// `nub_leaderboard_size` and `pro_leaderboard_size` are the amount of records the distributions
+/// were fitted on.
 #[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
 pub async fn update_distribution_data(
     cx: &Context,
     filter_id: CourseFilterId,
     nub_dist: Option<&Distribution>,
+    nub_leaderboard_size: usize,
     pro_dist: Option<&Distribution>,
+    pro_leaderboard_size: usize,
 ) -> Result<(), UpdateDistributionDataError> {
-    for (dist, is_pro_leaderboard) in
-        iter::chain(nub_dist.map(|dist| (dist, false)), pro_dist.map(|dist| (dist, true)))
-    {
+    for (dist, is_pro_leaderboard, leaderboard_size) in iter::chain(
+        nub_dist.map(|dist| (dist, false, nub_leaderboard_size)),
+        pro_dist.map(|dist| (dist, true, pro_leaderboard_size)),
+    ) {
         sqlx::query!(
             "INSERT INTO PointDistributionData (
                filter_id,
@@ -194,15 +325,18 @@ pub async fn update_distribution_data(
                b,
                loc,
                scale,
-               top_scale
+               top_scale,
+               leaderboard_size
              )
-             VALUES (?, ?, ?, ?, ?, ?, ?)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)
              ON DUPLICATE KEY
              UPDATE a = VALUES(a),
                     b = VALUES(b),
                     loc = VALUES(loc),
-                    scale = VALUES(loc),
-                    top_scale = VALUES(top_scale)",
+                    scale = VALUES(scale),
+                    top_scale = VALUES(top_scale),
+                    leaderboard_size = VALUES(leaderboard_size),
+                    updated_at = CURRENT_TIMESTAMP",
             filter_id,
             is_pro_leaderboard,
             dist.a,
@@ -210,6 +344,7 @@ pub async fn update_distribution_data(
             dist.loc,
             dist.scale,
             dist.top_scale,
+            leaderboard_size as u32,
         )
         .execute(cx.database().as_ref())
         .await?;
@@ -217,3 +352,18 @@ pub async fn update_distribution_data(
 
     Ok(())
 }
+
+/// Deletes the distribution parameters stored for a filter.
+///
+/// This is used when a filter stops being ranked.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn delete_distribution_data(
+    cx: &Context,
+    filter_id: CourseFilterId,
+) -> Result<u64, UpdateDistributionDataError> {
+    sqlx::query!("DELETE FROM PointDistributionData WHERE filter_id = ?", filter_id)
+        .execute(cx.database().as_ref())
+        .await
+        .map(|result| result.rows_affected())
+        .map_err(UpdateDistributionDataError::from)
+}
@@ -0,0 +1,706 @@
+//! Native implementation of the [normal-inverse Gaussian distribution][nig].
+//!
+//! All functions operate on the *standardized* distribution with shape parameters `a` (tail
+//! heaviness) and `b` (asymmetry), matching the parameterization used by
+//! [`scipy.stats.norminvgauss`][scipy]. Location and scale are applied by the caller.
+//!
+//! [nig]: https://en.wikipedia.org/wiki/Normal-inverse_Gaussian_distribution
+//! [scipy]: https://docs.scipy.org/doc/scipy/reference/generated/scipy.stats.norminvgauss.html
+
+use std::collections::BinaryHeap;
+use std::f64::consts::PI;
+use std::{cmp, iter};
+
+/// Euler–Mascheroni constant.
+const EULER_GAMMA: f64 = 0.577_215_664_901_532_9;
+
+/// Absolute and relative error tolerance for numerical integration.
+const QUAD_TOLERANCE: f64 = 1e-12;
+
+/// Maximum number of subintervals used for numerical integration.
+const QUAD_MAX_INTERVALS: usize = 200;
+
+/// Maximum number of iterations for a single Nelder-Mead run.
+const FIT_MAX_ITERATIONS: usize = 5_000;
+
+/// Maximum number of times Nelder-Mead is restarted from its previous result.
+const FIT_MAX_RESTARTS: usize = 5;
+
+/// Parameters for a fitted distribution.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Params {
+    pub a: f64,
+    pub b: f64,
+    pub loc: f64,
+    pub scale: f64,
+}
+
+#[derive(Debug, Display, Error)]
+#[display("failed to fit distribution: {reason}")]
+pub struct FitDistributionError {
+    #[error(ignore)]
+    reason: &'static str,
+}
+
+/// The probability density function.
+pub fn pdf(x: f64, a: f64, b: f64) -> f64 {
+    log_pdf(x, a, b).exp()
+}
+
+/// The natural logarithm of [`pdf()`].
+pub fn log_pdf(x: f64, a: f64, b: f64) -> f64 {
+    let s = x.hypot(1.0);
+    let gamma = (a * a - b * b).sqrt();
+
+    // `K1(a * s) = k1e(a * s) * exp(-a * s)`, which keeps everything in range for large inputs
+    (a / PI).ln() + bessel_k1e(a * s).ln() - s.ln() + gamma + b * x - a * s
+}
+
+/// The survival function (`1 - cdf`).
+pub fn sf(x: f64, a: f64, b: f64) -> f64 {
+    let density = |x| pdf(x, a, b);
+
+    // map `[x, ∞)` onto `(0, 1]` via `x + (1 - t) / t`
+    integrate(|t| density(x + (1.0 - t) / t) / (t * t), 0.0, 1.0)
+}
+
+/// Integrates the probability density function over `[lower, upper]`.
+pub fn integrate_pdf(lower: f64, upper: f64, a: f64, b: f64) -> f64 {
+    integrate(|x| pdf(x, a, b), lower, upper)
+}
+
+/// Fits the distribution to `values` using maximum likelihood estimation.
+///
+/// # Panics
+///
+/// This function will panic if `values` is empty.
+pub fn fit(values: &[f64]) -> Result<Params, FitDistributionError> {
+    assert!(!values.is_empty(), "cannot fit distribution to empty dataset");
+
+    if values.iter().any(|value| !value.is_finite()) {
+        return Err(FitDistributionError { reason: "dataset contains non-finite values" });
+    }
+
+    // optimize in an unconstrained space so every point the optimizer visits is a valid
+    // parameter set: `(ln a, atanh(b / a), loc, ln scale)`
+    let to_params = |[ln_a, atanh_ratio, loc, ln_scale]: [f64; 4]| {
+        let a = ln_a.exp();
+        Params {
+            a,
+            b: a * atanh_ratio.tanh(),
+            loc,
+            scale: ln_scale.exp(),
+        }
+    };
+
+    let negative_log_likelihood = |point: [f64; 4]| {
+        let Params { a, b, loc, scale } = to_params(point);
+
+        if !(a.is_finite() && b.abs() < a && scale > 0.0 && scale.is_finite()) {
+            return f64::INFINITY;
+        }
+
+        let log_likelihood = values
+            .iter()
+            .map(|&value| log_pdf((value - loc) / scale, a, b))
+            .sum::<f64>();
+
+        let result = (values.len() as f64) * scale.ln() - log_likelihood;
+
+        if result.is_nan() { f64::INFINITY } else { result }
+    };
+
+    let start = fit_start(values);
+    let mut best = [
+        start.a.ln(),
+        (start.b / start.a).atanh(),
+        start.loc,
+        start.scale.ln(),
+    ];
+    let mut best_value = negative_log_likelihood(best);
+
+    // Nelder-Mead can collapse its simplex prematurely; restarting from the previous result
+    // until it stops improving makes the result a lot more reliable
+    for _ in 0..FIT_MAX_RESTARTS {
+        let (point, value) = nelder_mead(&negative_log_likelihood, best);
+        let improvement = best_value - value;
+
+        if value <= best_value {
+            best = point;
+            best_value = value;
+        }
+
+        if improvement.is_nan() || improvement <= 1e-9 * best_value.abs().max(1.0) {
+            break;
+        }
+    }
+
+    if !best_value.is_finite() {
+        return Err(FitDistributionError { reason: "likelihood is not finite" });
+    }
+
+    Ok(to_params(best))
+}
+
+/// Estimates starting parameters for [`fit()`] using the method of moments.
+fn fit_start(values: &[f64]) -> Params {
+    let n = values.len() as f64;
+    let mean = values.iter().sum::<f64>() / n;
+    let moment = |k| {
+        values
+            .iter()
+            .map(|&value| (value - mean).powi(k))
+            .sum::<f64>()
+            / n
+    };
+    let variance = moment(2);
+    let std_dev = variance.sqrt();
+
+    // degenerate datasets (e.g. a single value) still need a usable scale
+    let fallback_scale = std_dev.max(mean.abs() * 1e-3).max(1e-9);
+    let fallback = Params {
+        a: 1.0,
+        b: 0.0,
+        loc: mean,
+        scale: fallback_scale,
+    };
+
+    if variance <= 0.0 {
+        return fallback;
+    }
+
+    let skewness = moment(3) / (variance * std_dev);
+    let excess_kurtosis = moment(4) / (variance * variance) - 3.0;
+
+    // skewness = 3ρ / √γ and excess kurtosis = 3 (1 + 4ρ²) / γ, where ρ = b / a and
+    // γ = √(a² - b²)
+    let denominator = excess_kurtosis - 4.0 * skewness * skewness / 3.0;
+
+    if denominator.is_nan() || denominator <= 0.0 {
+        return fallback;
+    }
+
+    let gamma = 3.0 / denominator;
+    let ratio = skewness.signum() * (skewness * skewness * gamma / 9.0).sqrt();
+
+    if ratio.is_nan() || ratio.abs() >= 1.0 {
+        return fallback;
+    }
+
+    let a = gamma / (1.0 - ratio * ratio).sqrt();
+    let b = ratio * a;
+    let scale = (variance * gamma.powi(3)).sqrt() / a;
+    let loc = mean - scale * b / gamma;
+
+    if [a, b, loc, scale].iter().all(|value| value.is_finite()) && scale > 0.0 {
+        Params { a, b, loc, scale }
+    } else {
+        fallback
+    }
+}
+
+/// Minimizes `f` using the [Nelder-Mead method][nelder-mead].
+///
+/// The coefficients and initial simplex match `scipy.optimize.fmin`.
+///
+/// [nelder-mead]: https://en.wikipedia.org/wiki/Nelder%E2%80%93Mead_method
+fn nelder_mead<const N: usize>(f: &impl Fn([f64; N]) -> f64, start: [f64; N]) -> ([f64; N], f64) {
+    const REFLECTION: f64 = 1.0;
+    const EXPANSION: f64 = 2.0;
+    const CONTRACTION: f64 = 0.5;
+    const SHRINK: f64 = 0.5;
+
+    const X_TOLERANCE: f64 = 1e-10;
+    const F_TOLERANCE: f64 = 1e-12;
+
+    let combine = |p: &[f64; N], q: &[f64; N], t: f64| -> [f64; N] {
+        // p + t * (q - p)
+        std::array::from_fn(|i| p[i] + t * (q[i] - p[i]))
+    };
+
+    let mut simplex = iter::once(start)
+        .chain((0..N).map(|i| {
+            let mut point = start;
+            point[i] = if point[i] != 0.0 {
+                point[i] * 1.05
+            } else {
+                0.000_25
+            };
+            point
+        }))
+        .map(|point| (point, f(point)))
+        .collect::<Vec<_>>();
+
+    for _ in 0..FIT_MAX_ITERATIONS {
+        simplex.sort_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs));
+
+        let (best, best_value) = simplex[0];
+        let converged_x = simplex[1..].iter().all(|(point, _)| {
+            iter::zip(point, &best).all(|(lhs, rhs)| (lhs - rhs).abs() <= X_TOLERANCE)
+        });
+        let converged_f = simplex[1..]
+            .iter()
+            .all(|&(_, value)| (value - best_value).abs() <= F_TOLERANCE * best_value.abs());
+
+        if converged_x || converged_f {
+            break;
+        }
+
+        let centroid = std::array::from_fn(|i| {
+            simplex[..N].iter().map(|(point, _)| point[i]).sum::<f64>() / (N as f64)
+        });
+
+        let (worst, worst_value) = simplex[N];
+        let second_worst_value = simplex[N - 1].1;
+
+        let reflected = combine(&centroid, &worst, -REFLECTION);
+        let reflected_value = f(reflected);
+
+        if reflected_value < best_value {
+            let expanded = combine(&centroid, &worst, -REFLECTION * EXPANSION);
+            let expanded_value = f(expanded);
+
+            simplex[N] = if expanded_value < reflected_value {
+                (expanded, expanded_value)
+            } else {
+                (reflected, reflected_value)
+            };
+
+            continue;
+        }
+
+        if reflected_value < second_worst_value {
+            simplex[N] = (reflected, reflected_value);
+            continue;
+        }
+
+        let (contracted, contracted_value) = if reflected_value < worst_value {
+            let contracted = combine(&centroid, &worst, -REFLECTION * CONTRACTION);
+            (contracted, f(contracted))
+        } else {
+            let contracted = combine(&centroid, &worst, CONTRACTION);
+            (contracted, f(contracted))
+        };
+
+        if contracted_value < reflected_value.min(worst_value) {
+            simplex[N] = (contracted, contracted_value);
+            continue;
+        }
+
+        for (point, value) in &mut simplex[1..] {
+            *point = combine(&best, point, SHRINK);
+            *value = f(*point);
+        }
+    }
+
+    simplex
+        .into_iter()
+        .min_by(|(_, lhs), (_, rhs)| lhs.total_cmp(rhs))
+        .expect("simplex is never empty")
+}
+
+/// The exponentially scaled modified Bessel function of the second kind of order 1
+/// (`K1(x) * exp(x)`).
+fn bessel_k1e(x: f64) -> f64 {
+    debug_assert!(x > 0.0);
+
+    if x <= 2.0 {
+        // power series, see Abramowitz & Stegun 9.6.11
+        let y = 0.25 * x * x;
+        let mut term = 1.0;
+        let mut digamma_sum = 1.0 - 2.0 * EULER_GAMMA; // ψ(1) + ψ(2)
+        let mut i1_sum = 0.0;
+        let mut series = 0.0;
+
+        for k in 0..100 {
+            i1_sum += term;
+            series += digamma_sum * term;
+
+            let k = k as f64;
+            term *= y / ((k + 1.0) * (k + 2.0));
+            digamma_sum += 1.0 / (k + 1.0) + 1.0 / (k + 2.0);
+
+            if term < f64::EPSILON * i1_sum {
+                break;
+            }
+        }
+
+        let i1 = 0.5 * x * i1_sum;
+        let k1 = 1.0 / x + (0.5 * x).ln() * i1 - 0.25 * x * series;
+
+        k1 * x.exp()
+    } else {
+        // Steed's continued fraction (CF2), see Numerical Recipes 6.7
+        let a1 = 0.25;
+        let mut a = -a1;
+        let mut b = 2.0 * (1.0 + x);
+        let mut d = 1.0 / b;
+        let mut delta_h = d;
+        let mut h = d;
+        let mut q1 = 0.0;
+        let mut q2 = 1.0;
+        let mut q = a1;
+        let mut c = a1;
+        let mut s = 1.0 + q * delta_h;
+
+        for i in 2..10_000 {
+            let i = i as f64;
+            a -= 2.0 * (i - 1.0);
+            c = -a * c / i;
+            let q_next = (q1 - b * q2) / a;
+            q1 = q2;
+            q2 = q_next;
+            q += c * q_next;
+            b += 2.0;
+            d = 1.0 / (b + a * d);
+            delta_h *= b * d - 1.0;
+            h += delta_h;
+            let delta_s = q * delta_h;
+            s += delta_s;
+
+            if (delta_s / s).abs() < f64::EPSILON {
+                break;
+            }
+        }
+
+        let k0 = (PI / (2.0 * x)).sqrt() / s;
+
+        k0 * (x + 0.5 - a1 * h) / x
+    }
+}
+
+/// Integrates `f` over `[lower, upper]` using adaptive Gauss-Kronrod quadrature.
+///
+/// This is a simplified version of QUADPACK's `qag` routine, which is also what
+/// `scipy.integrate.quad` uses under the hood.
+fn integrate(f: impl Fn(f64) -> f64, lower: f64, upper: f64) -> f64 {
+    #[derive(Debug)]
+    struct Interval {
+        lower: f64,
+        upper: f64,
+        value: f64,
+        error: f64,
+    }
+
+    impl PartialEq for Interval {
+        fn eq(&self, other: &Self) -> bool {
+            self.cmp(other) == cmp::Ordering::Equal
+        }
+    }
+
+    impl Eq for Interval {}
+
+    impl PartialOrd for Interval {
+        fn partial_cmp(&self, other: &Self) -> Option<cmp::Ordering> {
+            Some(self.cmp(other))
+        }
+    }
+
+    impl Ord for Interval {
+        fn cmp(&self, other: &Self) -> cmp::Ordering {
+            self.error.total_cmp(&other.error)
+        }
+    }
+
+    if lower == upper {
+        return 0.0;
+    }
+
+    let evaluate = |lower: f64, upper: f64| {
+        let (value, error) = gauss_kronrod(&f, lower, upper);
+        Interval { lower, upper, value, error }
+    };
+
+    let mut intervals = BinaryHeap::from([evaluate(lower, upper)]);
+    let mut total_value = intervals.peek().map_or(0.0, |interval| interval.value);
+    let mut total_error = intervals.peek().map_or(0.0, |interval| interval.error);
+
+    while total_error > QUAD_TOLERANCE.max(QUAD_TOLERANCE * total_value.abs())
+        && intervals.len() < QUAD_MAX_INTERVALS
+    {
+        let worst = intervals
+            .pop()
+            .expect("there is always at least one interval");
+        let midpoint = 0.5 * (worst.lower + worst.upper);
+
+        // we can't split any further
+        if midpoint <= worst.lower || midpoint >= worst.upper {
+            intervals.push(worst);
+            break;
+        }
+
+        let left = evaluate(worst.lower, midpoint);
+        let right = evaluate(midpoint, worst.upper);
+
+        total_value += left.value + right.value - worst.value;
+        total_error += left.error + right.error - worst.error;
+
+        intervals.push(left);
+        intervals.push(right);
+    }
+
+    // sum up from scratch to avoid accumulating rounding errors from the updates above
+    intervals.iter().map(|interval| interval.value).sum()
+}
+
+/// Applies the 15-point Gauss-Kronrod rule to `f` over `[lower, upper]`.
+///
+/// Returns the estimated integral and its estimated absolute error.
+fn gauss_kronrod(f: impl Fn(f64) -> f64, lower: f64, upper: f64) -> (f64, f64) {
+    /// Kronrod nodes; the odd indices are also the 7-point Gauss nodes.
+    const NODES: [f64; 8] = [
+        0.991_455_371_120_812_6,
+        0.949_107_912_342_758_5,
+        0.864_864_423_359_769_1,
+        0.741_531_185_599_394_5,
+        0.586_087_235_467_691_1,
+        0.405_845_151_377_397_2,
+        0.207_784_955_007_898_48,
+        0.0,
+    ];
+
+    const KRONROD_WEIGHTS: [f64; 8] = [
+        0.022_935_322_010_529_224,
+        0.063_092_092_629_978_56,
+        0.104_790_010_322_250_19,
+        0.140_653_259_715_525_92,
+        0.169_004_726_639_267_9,
+        0.190_350_578_064_785_42,
+        0.204_432_940_075_298_89,
+        0.209_482_141_084_727_82,
+    ];
+
+    const GAUSS_WEIGHTS: [f64; 4] = [
+        0.129_484_966_168_869_7,
+        0.279_705_391_489_276_64,
+        0.381_830_050_505_118_9,
+        0.417_959_183_673_469_4,
+    ];
+
+    let center = 0.5 * (lower + upper);
+    let half_length = 0.5 * (upper - lower);
+
+    let center_value = f(center);
+    let mut kronrod = KRONROD_WEIGHTS[7] * center_value;
+    let mut gauss = GAUSS_WEIGHTS[3] * center_value;
+
+    for (i, (&node, &weight)) in iter::zip(&NODES[..7], &KRONROD_WEIGHTS[..7]).enumerate() {
+        let offset = half_length * node;
+        let sum = f(center - offset) + f(center + offset);
+
+        kronrod += weight * sum;
+
+        if i % 2 == 1 {
+            gauss += GAUSS_WEIGHTS[i / 2] * sum;
+        }
+    }
+
+    let value = kronrod * half_length;
+    let error = ((kronrod - gauss) * half_length).abs();
+
+    (value, error)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Asserts that `actual` and `expected` agree to within a relative tolerance.
+    #[track_caller]
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        let error = ((actual - expected) / expected).abs();
+
+        assert!(
+            error <= tolerance,
+            "expected {expected}, got {actual} (relative error {error:e})",
+        );
+    }
+
+    // reference values in the tests below were computed with 30 significant digits using
+    // `mpmath`, using the same definitions as `scipy.special.k1e` and `scipy.stats.norminvgauss`
+
+    #[test]
+    fn bessel_k1e_matches_reference() {
+        for (x, expected) in [
+            (1e-3, 1_000.996_734_559_068_4),
+            (0.1, 10.890_182_683_049_696),
+            (0.5, 2.731_009_708_211_786),
+            (1.0, 1.636_153_486_263_258_2),
+            (1.9, 1.067_470_929_814_57),
+            (2.0, 1.033_476_847_068_688_6),
+            (2.1, 1.002_368_052_740_579_1),
+            (5.0, 0.600_273_858_788_312_6),
+            (10.0, 0.410_766_570_595_788_75),
+            (50.0, 0.178_566_558_558_815_57),
+            (700.0, 0.047_396_187_653_494_54),
+            (1e4, 0.012_533_611_351_270_506),
+        ] {
+            assert_close(bessel_k1e(x), expected, 1e-13);
+        }
+    }
+
+    #[test]
+    fn pdf_matches_reference() {
+        for ((x, a, b), expected) in [
+            ((0.0, 1.0, 0.0), 0.520_803_829_991_670_1),
+            ((0.5, 2.0, 0.5), 0.522_367_203_123_592_7),
+            ((-1.0, 1.5, -1.0), 0.335_188_987_888_710_2),
+            ((3.0, 0.8, 0.7), 0.068_970_403_360_299_91),
+            ((10.0, 5.0, 4.9), 0.021_871_718_913_006_28),
+        ] {
+            assert_close(pdf(x, a, b), expected, 1e-12);
+        }
+    }
+
+    #[test]
+    fn sf_matches_reference() {
+        for ((x, a, b), expected) in [
+            ((0.0, 1.0, 0.0), 0.5),
+            ((0.5, 2.0, 0.5), 0.328_912_219_366_189_3),
+            ((-1.0, 1.5, -1.0), 0.640_216_035_703_935_9),
+            ((3.0, 0.8, 0.7), 0.185_573_948_855_422_58),
+            ((-2.0, 3.0, 1.0), 0.999_858_816_505_260_3),
+            ((2.0, 3.0, 1.0), 0.014_384_003_013_321_78),
+            ((20.0, 1.0, 0.5), 8.390_941_887_394_61e-7),
+        ] {
+            assert_close(sf(x, a, b), expected, 1e-9);
+        }
+    }
+
+    #[test]
+    fn integrate_pdf_matches_reference() {
+        for ((lower, upper, a, b), expected) in [
+            ((-0.5, 0.25, 1.0, 0.0), 0.361_852_234_700_759_64),
+            ((0.1, 0.2, 2.0, 1.5), 0.040_159_283_176_778_91),
+            ((1.0, 4.0, 1.2, 0.3), 0.170_843_665_626_203_83),
+        ] {
+            assert_close(integrate_pdf(lower, upper, a, b), expected, 1e-10);
+        }
+    }
+
+    #[test]
+    fn fit_recovers_parameters() {
+        let expected = Params { a: 2.0, b: 1.0, loc: 30.0, scale: 5.0 };
+
+        // evenly spaced quantiles of the distribution above
+        let values = (1..200)
+            .map(|i| f64::from(i) / 200.0)
+            .map(|p| expected.loc + expected.scale * quantile(p, expected.a, expected.b))
+            .collect::<Vec<_>>();
+
+        let negative_log_likelihood = |Params { a, b, loc, scale }: Params| {
+            (values.len() as f64) * scale.ln()
+                - values
+                    .iter()
+                    .map(|&value| log_pdf((value - loc) / scale, a, b))
+                    .sum::<f64>()
+        };
+
+        let actual = fit(&values).unwrap();
+
+        // the quantiles cut off the tails, so the MLE won't reproduce the parameters exactly, but
+        // it must be at least as likely as the true parameters and describe the same distribution
+        assert!(negative_log_likelihood(actual) <= negative_log_likelihood(expected));
+
+        for value in [15.0, 25.0, 30.0, 35.0, 45.0] {
+            let actual_sf = sf((value - actual.loc) / actual.scale, actual.a, actual.b);
+            let expected_sf = sf((value - expected.loc) / expected.scale, expected.a, expected.b);
+
+            assert!(
+                (actual_sf - expected_sf).abs() < 5e-3,
+                "sf({value}): expected {expected_sf}, got {actual_sf}",
+            );
+        }
+    }
+
+    /// How closely [`fit()`] has to match `scipy.stats.norminvgauss.fit`.
+    ///
+    /// scipy maximizes the likelihood with `scipy.optimize.fmin`, which stops once the parameters
+    /// change by less than `1e-4`, so its results are only that accurate to begin with.
+    const FIT_TOLERANCE: f64 = 1e-4;
+
+    /// Parses a leaderboard in `testdata/`, one time (in seconds) per line.
+    fn leaderboard(times: &str) -> Vec<f64> {
+        times.lines().map(|time| time.parse().unwrap()).collect()
+    }
+
+    // The leaderboards were sampled from distributions like the ones real leaderboards follow,
+    // with times rounded to ticks (1/64 s). The expected parameters are the exact maximum
+    // likelihood estimates scipy converges to, computed with `mpmath` by running Newton's method
+    // until the gradient vanished (< 1e-15).
+
+    #[test]
+    fn fit_matches_reference_estimates() {
+        for (times, expected, survival) in [
+            (
+                include_str!("testdata/leaderboard_1.txt"),
+                Params {
+                    a: 2.304_473_327_734_093,
+                    b: 1.333_376_331_926_858_2,
+                    loc: 50.341_609_505_902_23,
+                    scale: 6.470_082_559_030_095,
+                },
+                [
+                    (42.015_625, 0.997_792_475_553_929_4),
+                    (55.0, 0.421_325_066_375_945_6),
+                    (67.5, 0.033_890_782_272_546_77),
+                    (85.921_875, 0.001_019_984_921_342_721_8),
+                ],
+            ),
+            (
+                include_str!("testdata/leaderboard_2.txt"),
+                Params {
+                    a: 1.144_435_625_780_916_6,
+                    b: 0.859_653_756_883_831_3,
+                    loc: 189.631_800_697_784_17,
+                    scale: 23.996_942_441_080_944,
+                },
+                [
+                    (139.406_25, 0.998_635_166_244_487_5),
+                    (190.0, 0.784_621_079_953_693_1),
+                    (250.0, 0.142_469_091_381_307_17),
+                    (411.6875, 0.005_394_322_003_701_891),
+                ],
+            ),
+        ] {
+            let actual = fit(&leaderboard(times)).unwrap();
+
+            assert_close(actual.a, expected.a, FIT_TOLERANCE);
+            assert_close(actual.b, expected.b, FIT_TOLERANCE);
+            assert_close(actual.loc, expected.loc, FIT_TOLERANCE);
+            assert_close(actual.scale, expected.scale, FIT_TOLERANCE);
+
+            // points are derived from the survival function, so that has to match as well
+            for (time, expected_sf) in survival {
+                let actual_sf = sf((time - actual.loc) / actual.scale, actual.a, actual.b);
+
+                assert_close(actual_sf, expected_sf, FIT_TOLERANCE);
+            }
+        }
+    }
+
+    #[test]
+    fn fit_handles_degenerate_input() {
+        let params = fit(&[42.0]).unwrap();
+
+        assert!(params.a.is_finite());
+        assert!(params.scale > 0.0);
+    }
+
+    /// Inverts [`sf()`] using bisection.
+    fn quantile(p: f64, a: f64, b: f64) -> f64 {
+        let (mut lower, mut upper) = (-50.0, 50.0);
+
+        for _ in 0..60 {
+            let midpoint = 0.5 * (lower + upper);
+
+            if 1.0 - sf(midpoint, a, b) < p {
+                lower = midpoint;
+            } else {
+                upper = midpoint;
+            }
+        }
+
+        0.5 * (lower + upper)
+    }
+}
@@ -0,0 +1,181 @@
+use super::Distribution;
+use crate::maps::courses::filters::{self, CourseFilterId, GetCourseFiltersError, Tier};
+use crate::time::Seconds;
+use crate::{Context, database, points};
+
+/// What a hypothetical record would achieve on a filter's leaderboards.
+#[derive(Debug)]
+pub struct Projection {
+    /// Projection for the NUB leaderboard.
+    ///
+    /// This is [`None`] if the filter is not ranked, or the NUB tier is not humanly possible.
+    pub nub: Option<ProjectedRecord>,
+
+    /// Projection for the PRO leaderboard.
+    ///
+    /// This is [`None`] if the filter is not ranked, the record has teleports, or the PRO tier is
+    /// not humanly possible.
+    pub pro: Option<ProjectedRecord>,
+}
+
+#[derive(Debug)]
+pub struct ProjectedRecord {
+    /// The rank the record would achieve.
+    pub rank: u32,
+
+    /// The size of the leaderboard before the record is inserted.
+    pub leaderboard_size: u32,
+
+    /// The points the record would be awarded.
+    pub points: f64,
+
+    /// Whether `points` were calculated based on the PRO leaderboard.
+    ///
+    /// PRO records are awarded whichever is higher: points based on the PRO leaderboard, or
+    /// points based on the NUB leaderboard. This is always `false` for NUB projections.
+    pub based_on_pro_leaderboard: bool,
+}
+
+#[derive(Debug, Display, Error, From)]
+pub enum ProjectPointsError {
+    #[display("{_0}")]
+    GetCourseFilter(GetCourseFiltersError),
+
+    #[display("failed to project points: {_0}")]
+    #[from(forward)]
+    Database(database::Error),
+}
+
+/// Calculates the rank and points a record with the given `time` and `teleports` would receive
+/// on a filter, without submitting it.
+///
+/// Returns [`None`] if the filter does not exist.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn project(
+    cx: &Context,
+    filter_id: CourseFilterId,
+    time: Seconds,
+    teleports: u32,
+) -> Result<Option<Projection>, ProjectPointsError> {
+    let Some((nub_tier, pro_tier)) = sqlx::query!(
+        "SELECT nub_tier AS `nub_tier: Tier`, pro_tier AS `pro_tier: Tier`
+         FROM CourseFilters
+         WHERE id = ?",
+        filter_id,
+    )
+    .fetch_optional(cx.database().as_ref())
+    .await
+    .map(|row| row.map(|row| (row.nub_tier, row.pro_tier)))?
+    else {
+        return Ok(None);
+    };
+
+    // records on unranked filters are not awarded any points
+    if !filters::is_ranked(cx, filter_id)
+        .await
+        .map_err(ProjectPointsError::GetCourseFilter)?
+    {
+        return Ok(Some(Projection { nub: None, pro: None }));
+    }
+
+    let nub = if nub_tier.is_humanly_possible() {
+        Some(project_leaderboard(cx, filter_id, nub_tier, false, time).await?)
+    } else {
+        None
+    };
+
+    let pro = if teleports == 0 && pro_tier.is_humanly_possible() {
+        let (mut pro, pro_dist_points) =
+            project_leaderboard(cx, filter_id, pro_tier, true, time).await?;
+
+        // the same time on the NUB leaderboard might be worth more
+        let nub_dist_points = nub.as_ref().map_or(0.0, |&(_, dist_points)| dist_points);
+
+        pro.based_on_pro_leaderboard = pro_dist_points >= nub_dist_points;
+        pro.points = points::complete(
+            pro_tier,
+            true,
+            (pro.rank - 1) as usize,
+            pro_dist_points.max(nub_dist_points),
+        );
+
+        Some(pro)
+    } else {
+        None
+    };
+
+    Ok(Some(Projection { nub: nub.map(|(nub, _)| nub), pro }))
+}
+
+/// Projects a record onto a single leaderboard.
+///
+/// Returns the projection as well as the raw distribution points.
+async fn project_leaderboard(
+    cx: &Context,
+    filter_id: CourseFilterId,
+    tier: Tier,
+    is_pro_leaderboard: bool,
+    time: Seconds,
+) -> Result<(ProjectedRecord, f64), ProjectPointsError> {
+    let dist = sqlx::query_as!(
+        Distribution,
+        "SELECT a, b, loc, scale, top_scale
+         FROM PointDistributionData
+         WHERE filter_id = ?
+         AND is_pro_leaderboard = ?",
+        filter_id,
+        is_pro_leaderboard,
+    )
+    .fetch_optional(cx.database().as_ref())
+    .await?;
+
+    let (leaderboard_size, faster, top_time) = if is_pro_leaderboard {
+        sqlx::query!(
+            "SELECT
+               COUNT(r.id) AS size,
+               COUNT(CASE WHEN r.time <= ? THEN 1 END) AS faster,
+               MIN(r.time) AS `top_time: Seconds`
+             FROM Records AS r
+             JOIN BestProRecords AS b ON b.record_id = r.id
+             WHERE b.filter_id = ?",
+            time,
+            filter_id,
+        )
+        .fetch_one(cx.database().as_ref())
+        .await
+        .map(|row| (row.size as usize, row.faster as usize, row.top_time))?
+    } else {
+        sqlx::query!(
+            "SELECT
+               COUNT(r.id) AS size,
+               COUNT(CASE WHEN r.time <= ? THEN 1 END) AS faster,
+               MIN(r.time) AS `top_time: Seconds`
+             FROM Records AS r
+             JOIN BestNubRecords AS b ON b.record_id = r.id
+             WHERE b.filter_id = ?",
+            time,
+            filter_id,
+        )
+        .fetch_one(cx.database().as_ref())
+        .await
+        .map(|row| (row.size as usize, row.faster as usize, row.top_time))?
+    };
+
+    let dist_points = if let Some(top_time) = top_time.filter(|&top_time| top_time < time) {
+        points::calculate(dist, tier, leaderboard_size, top_time.into(), time.into()).await
+    } else {
+        1.0
+    };
+
+    // a new record is ranked behind existing records with the same time
+    let rank = faster + 1;
+
+    let projection = ProjectedRecord {
+        rank: rank as u32,
+        leaderboard_size: leaderboard_size as u32,
+        points: points::complete(tier, is_pro_leaderboard, rank - 1, dist_points),
+        based_on_pro_leaderboard: is_pro_leaderboard,
+    };
+
+    Ok((projection, dist_points))
+}
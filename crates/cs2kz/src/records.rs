@@ -11,10 +11,10 @@ use crate::maps::courses::filters::Tier;
 use crate::maps::{CourseFilterId, CourseId, CourseInfo, MapId, MapInfo};
 use crate::mode::Mode;
 use crate::num::AsF64;
-use crate::pagination::{Limit, Offset, Paginated};
-use crate::players::{PlayerId, PlayerInfo};
+use crate::pagination::{Cursor, Limit, Offset, Paginated};
+use crate::players::{self, PlayerId, PlayerInfo};
 use crate::plugin::PluginVersionId;
-use crate::points::{self, CalculatePointsError, Distribution};
+use crate::points::{self, Distribution};
 use crate::servers::{ServerId, ServerInfo};
 use crate::styles::Styles;
 use crate::time::{Seconds, Timestamp};
@@ -44,6 +44,21 @@ pub struct Record {
     pub submitted_at: Timestamp,
 }
 
+impl Record {
+    /// Turns the raw distribution points fetched from the database into the record's final
+    /// points.
+    ///
+    /// The raw points are `NULL` if the record's filter is not ranked, in which case the record
+    /// is not awarded any points.
+    fn complete_points(&mut self, nub_tier: Tier, pro_tier: Tier) {
+        self.nub_points = Option::zip(self.nub_rank, self.nub_points)
+            .map(|(rank, points)| points::complete(nub_tier, false, rank as usize - 1, points));
+
+        self.pro_points = Option::zip(self.pro_rank, self.pro_points)
+            .map(|(rank, points)| points::complete(pro_tier, true, rank as usize - 1, points));
+    }
+}
+
 #[derive(Debug, Clone, Copy)]
 pub struct LeaderboardEntry {
     pub record_id: RecordId,
@@ -72,6 +87,45 @@ impl AsF64 for LeaderboardEntry {
     }
 }
 
+/// How a record's points came together.
+#[derive(Debug)]
+pub struct PointsBreakdown {
+    /// Whether the record's filter is ranked.
+    ///
+    /// Records on unranked filters are not awarded any points, so `nub` and `pro` will always be
+    /// [`None`].
+    pub is_ranked: bool,
+
+    /// The record's points on the NUB leaderboard, if it is the player's best NUB record.
+    pub nub: Option<LeaderboardPoints>,
+
+    /// The record's points on the PRO leaderboard, if it is the player's best PRO record.
+    pub pro: Option<LeaderboardPoints>,
+}
+
+#[derive(Debug)]
+pub struct LeaderboardPoints {
+    pub tier: Tier,
+    pub rank: u32,
+    pub leaderboard_size: u32,
+
+    /// The pre-calculated distribution points stored in the database.
+    ///
+    /// This is a value in `0..=1` and the input for [`points::complete()`].
+    pub dist_points: f64,
+
+    /// Whether `dist_points` were calculated with [`points::for_small_leaderboard()`] instead of
+    /// a distribution.
+    pub small_leaderboard: bool,
+
+    /// Whether `dist_points` were calculated based on the PRO leaderboard.
+    ///
+    /// This is always `false` for NUB records.
+    pub based_on_pro_leaderboard: bool,
+
+    pub breakdown: points::Breakdown,
+}
+
 #[derive(Debug)]
 pub struct NewRecord {
     pub player_id: PlayerId,
@@ -111,32 +165,167 @@ pub struct GetRecordsParams {
     /// This can be used, for example, to query world records only (`max_rank=1`).
     pub max_rank: Option<NonZero<u32>>,
 
+    /// Only include records which have (at least) these styles enabled.
+    pub styles: Option<Styles>,
+
+    /// Only include records submitted after this point in time.
+    pub submitted_after: Option<Timestamp>,
+
+    /// Only include records submitted before this point in time.
+    pub submitted_before: Option<Timestamp>,
+
+    /// Only include records submitted on this plugin version.
+    pub plugin_version_id: Option<PluginVersionId>,
+
+    /// The minimum amount of points any record should have.
+    ///
+    /// Records qualify if either their NUB or PRO points reach this value.
+    pub min_points: Option<f64>,
+
     /// Which value to sort the results by.
     pub sort_by: SortBy,
 
     /// Which direction to sort the results in.
     ///
-    /// Defaults to 'descending' if `sort_by` is 'submission-date'.
-    /// Defaults to 'ascending' if `sort_by` is 'time'.
+    /// Defaults to 'descending' if `sort_by` is 'submission-date', 'nub-points' or
+    /// 'pro-points'.
+    /// Defaults to 'ascending' if `sort_by` is 'time', 'nub-rank' or 'pro-rank'.
     pub sort_order: Option<SortOrder>,
 
+    /// Only include records which come after this cursor.
+    ///
+    /// The cursor's sort key must match `sort_by`.
+    pub cursor: Option<RecordsCursor>,
+
     pub limit: Limit<1000, 100>,
     pub offset: Offset,
 }
 
-#[derive(Debug, Default, Clone, Copy, serde::Deserialize)]
+/// A cursor for paginating through [`get()`] results.
+pub type RecordsCursor = Cursor<SortKey, RecordId>;
+
+/// The value a record was sorted by, stored in a [`RecordsCursor`].
+#[derive(Debug, Clone, Copy, PartialEq, serde::Serialize, serde::Deserialize)]
+#[serde(tag = "by", content = "value", rename_all = "kebab-case")]
+pub enum SortKey {
+    SubmissionDate(Timestamp),
+    Time(f64),
+    NubPoints(Option<f64>),
+    ProPoints(Option<f64>),
+    NubRank(Option<u32>),
+    ProRank(Option<u32>),
+}
+
+impl SortKey {
+    /// Returns the [`SortBy`] this key belongs to.
+    pub fn sort_by(&self) -> SortBy {
+        match self {
+            Self::SubmissionDate(_) => SortBy::SubmissionDate,
+            Self::Time(_) => SortBy::Time,
+            Self::NubPoints(_) => SortBy::NubPoints,
+            Self::ProPoints(_) => SortBy::ProPoints,
+            Self::NubRank(_) => SortBy::NubRank,
+            Self::ProRank(_) => SortBy::ProRank,
+        }
+    }
+
+    fn from_row(sort_by: SortBy, row: &database::Row) -> sqlx::Result<Self> {
+        match sort_by {
+            SortBy::SubmissionDate => row.try_get("submitted_at").map(Self::SubmissionDate),
+            SortBy::Time => row.try_get("time").map(Self::Time),
+            SortBy::NubPoints => row.try_get("nub_points_total").map(Self::NubPoints),
+            SortBy::ProPoints => row.try_get("pro_points_total").map(Self::ProPoints),
+            SortBy::NubRank => row
+                .try_get::<Option<i64>, _>("nub_rank")
+                .map(|rank| Self::NubRank(rank.and_then(|rank| rank.try_into().ok()))),
+            SortBy::ProRank => row
+                .try_get::<Option<i64>, _>("pro_rank")
+                .map(|rank| Self::ProRank(rank.and_then(|rank| rank.try_into().ok()))),
+        }
+    }
+
+    /// Pushes a condition which only matches records that come after this key in the given
+    /// sort order.
+    fn push_after(&self, query: &mut QueryBuilder<'_>, id: RecordId, sort_order: SortOrder) {
+        let op = match sort_order {
+            SortOrder::Ascending => " > ",
+            SortOrder::Descending => " < ",
+        };
+
+        let (expr, value) = match *self {
+            Self::SubmissionDate(submitted_at) => {
+                query
+                    .push(" (r.submitted_at ")
+                    .push(op)
+                    .push_bind(submitted_at);
+                query.push(" OR (r.submitted_at = ").push_bind(submitted_at);
+                query.push(" AND r.id ").push(op).push_bind(id).push("))");
+                return;
+            },
+            Self::Time(time) => {
+                query.push(" (r.time ").push(op).push_bind(time);
+                query.push(" OR (r.time = ").push_bind(time);
+                query.push(" AND r.id ").push(op).push_bind(id).push("))");
+                return;
+            },
+            Self::NubPoints(points) => (NUB_POINTS_SQL, points),
+            Self::ProPoints(points) => (PRO_POINTS_SQL, points),
+            Self::NubRank(rank) => (" NubLeaderboard.rank ", rank.map(f64::from)),
+            Self::ProRank(rank) => (" ProLeaderboard.rank ", rank.map(f64::from)),
+        };
+
+        // records without a rank / points are always sorted last
+        match value {
+            None => {
+                query.push(" (").push(expr).push(" IS NULL AND r.id ");
+                query.push(op).push_bind(id).push(")");
+            },
+            Some(value) => {
+                query.push(" (").push(expr).push(" IS NULL OR ");
+                query.push(expr).push(op).push_bind(value);
+                query.push(" OR (").push(expr).push(" = ").push_bind(value);
+                query.push(" AND r.id ").push(op).push_bind(id).push("))");
+            },
+        }
+    }
+}
+
+/// Records on filters which aren't ranked are not awarded any points.
+const NUB_POINTS_SQL: &str = " IF(
+  cf.id IN (SELECT id FROM RankedCourseFilters),
+  KZ_POINTS(cf.nub_tier, false, NubLeaderboard.rank - 1, NubLeaderboard.points),
+  NULL
+) ";
+
+const PRO_POINTS_SQL: &str = " IF(
+  cf.id IN (SELECT id FROM RankedCourseFilters),
+  KZ_POINTS(cf.pro_tier, true, ProLeaderboard.rank - 1, ProLeaderboard.points),
+  NULL
+) ";
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, serde::Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub enum SortBy {
     #[default]
     SubmissionDate,
     Time,
+    NubPoints,
+    ProPoints,
+    NubRank,
+    ProRank,
 }
 
 impl SortBy {
     fn sql(&self) -> &'static str {
+        // records which are not on the relevant leaderboard, or aren't awarded any points, should
+        // always come last, regardless of the sort order
         match self {
             Self::SubmissionDate => " r.submitted_at ",
             Self::Time => " r.time ",
+            Self::NubPoints => " nub_points_total IS NULL, nub_points_total ",
+            Self::ProPoints => " pro_points_total IS NULL, pro_points_total ",
+            Self::NubRank => " NubLeaderboard.rank IS NULL, NubLeaderboard.rank ",
+            Self::ProRank => " ProLeaderboard.rank IS NULL, ProLeaderboard.rank ",
         }
     }
 }
@@ -151,8 +340,8 @@ pub enum SortOrder {
 impl SortOrder {
     fn from_sort_by(sort_by: SortBy) -> Self {
         match sort_by {
-            SortBy::SubmissionDate => Self::Descending,
-            SortBy::Time => Self::Ascending,
+            SortBy::SubmissionDate | SortBy::NubPoints | SortBy::ProPoints => Self::Descending,
+            SortBy::Time | SortBy::NubRank | SortBy::ProRank => Self::Ascending,
         }
     }
 
@@ -180,9 +369,6 @@ pub struct SubmittedRecord {
 
 #[derive(Debug, Display, Error, From)]
 pub enum SubmitRecordError {
-    #[display("{_0}")]
-    CalculatePoints(CalculatePointsError),
-
     #[display("{_0}")]
     #[from(forward)]
     Database(database::Error),
@@ -232,6 +418,16 @@ pub async fn submit(
             .await
             .and_then(|row| row.try_get(0))?;
 
+            // records on unranked filters still make it onto the leaderboards, but they are not
+            // awarded any points
+            let is_ranked = sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM RankedCourseFilters WHERE id = ?",
+            )
+            .bind(filter_id)
+            .fetch_one(&mut *conn)
+            .await?
+                > 0;
+
             let old_nub = sqlx::query!(
                 "SELECT
                    r.id,
@@ -304,35 +500,37 @@ pub async fn submit(
                     .await
                     .map(|row| row.map_or((0, None), |row| (row.size as usize, row.top_time)))?;
 
-                    let dist_points =
-                        if let Some(top_time) = top_time.filter(|&top_time| top_time < time) {
-                            points::calculate(dist, tier, leaderboard_size, top_time, time.into())
-                                .await
-                                .map_err(SubmitRecordError::CalculatePoints)?
-                        } else {
-                            1.0
-                        };
+                    let dist_points = if !is_ranked {
+                        0.0
+                    } else if let Some(top_time) = top_time.filter(|&top_time| top_time < time) {
+                        points::calculate(dist, tier, leaderboard_size, top_time, time.into()).await
+                    } else {
+                        1.0
+                    };
 
                     sqlx::query!(
                         "INSERT INTO BestNubRecords (
                            filter_id,
                            player_id,
                            record_id,
-                           points
+                           points,
+                           points_formula_version
                          )
-                         VALUES (?, ?, ?, ?)
+                         VALUES (?, ?, ?, ?, ?)
                          ON DUPLICATE KEY
                          UPDATE record_id = VALUES(record_id),
-                                points = VALUES(points)",
+                                points = VALUES(points),
+                                points_formula_version = VALUES(points_formula_version)",
                         filter_id,
                         player_id,
                         record_id,
                         dist_points,
+                        points::formula::CURRENT.version,
                     )
                     .execute(&mut *conn)
                     .await?;
 
-                    if leaderboard_size <= points::SMALL_LEADERBOARD_THRESHOLD {
+                    if is_ranked && leaderboard_size <= points::SMALL_LEADERBOARD_THRESHOLD {
                         let mut top_time = None;
                         let leaderboard = sqlx::query!(
                             "SELECT
@@ -343,45 +541,55 @@ pub async fn submit(
                              JOIN BestNubRecords ON BestNubRecords.record_id = r.id
                              WHERE BestNubRecords.filter_id = ?
                              ORDER BY time ASC",
-                             filter_id,
-                            )
-                            .fetch(&mut *conn)
-                            .map_ok(|row| {
-                                (row.player_id, row.id, points::for_small_leaderboard(
+                            filter_id,
+                        )
+                        .fetch(&mut *conn)
+                        .map_ok(|row| {
+                            (
+                                row.player_id,
+                                row.id,
+                                points::for_small_leaderboard(
                                     tier,
                                     *top_time.get_or_insert(row.time),
                                     row.time,
-                                ))
-                            })
-                            .try_collect::<Vec<_>>()
-                            .await?;
+                                ),
+                            )
+                        })
+                        .try_collect::<Vec<_>>()
+                        .await?;
 
                         let mut query = QueryBuilder::new(
                             "INSERT INTO BestNubRecords (
                                filter_id,
                                player_id,
                                record_id,
-                               points
+                               points,
+                               points_formula_version
                              )",
                         );
 
-                        query.push_values(leaderboard, |mut query, (player_id, record_id, points)| {
-                            query.push_bind(filter_id);
-                            query.push_bind(player_id);
-                            query.push_bind(record_id);
-                            query.push_bind(points);
-                        });
+                        query.push_values(
+                            leaderboard,
+                            |mut query, (player_id, record_id, points)| {
+                                query.push_bind(filter_id);
+                                query.push_bind(player_id);
+                                query.push_bind(record_id);
+                                query.push_bind(points);
+                                query.push_bind(points::formula::CURRENT.version);
+                            },
+                        );
 
-                        query.push("ON DUPLICATE KEY UPDATE points = VALUES(points)");
+                        query.push(
+                            "ON DUPLICATE KEY
+                             UPDATE points = VALUES(points),
+                                    points_formula_version = VALUES(points_formula_version)",
+                        );
 
-                        query
-                            .build()
-                            .persistent(false)
-                            .execute(&mut *conn)
-                            .await?;
+                        query.build().persistent(false).execute(&mut *conn).await?;
                     }
 
-                    Ok(Some(move |rank| points::complete(tier, false, rank, dist_points)))
+                    Ok(is_ranked
+                        .then_some(move |rank| points::complete(tier, false, rank, dist_points)))
                 };
 
             let insert_pro =
@@ -424,14 +632,13 @@ pub async fn submit(
                     .await
                     .map(|row| row.map_or((0, None), |row| (row.size as usize, row.top_time)))?;
 
-                    let dist_points =
-                        if let Some(top_time) = top_time.filter(|&top_time| top_time < time) {
-                            points::calculate(dist, tier, leaderboard_size, top_time, time.into())
-                                .await
-                                .map_err(SubmitRecordError::CalculatePoints)?
-                        } else {
-                            1.0
-                        };
+                    let dist_points = if !is_ranked {
+                        0.0
+                    } else if let Some(top_time) = top_time.filter(|&top_time| top_time < time) {
+                        points::calculate(dist, tier, leaderboard_size, top_time, time.into()).await
+                    } else {
+                        1.0
+                    };
 
                     sqlx::query!(
                         "INSERT INTO BestProRecords (
@@ -439,21 +646,24 @@ pub async fn submit(
                            player_id,
                            record_id,
                            points,
-                           points_based_on_pro_leaderboard
+                           points_based_on_pro_leaderboard,
+                           points_formula_version
                          )
-                         VALUES (?, ?, ?, ?, true)
+                         VALUES (?, ?, ?, ?, true, ?)
                          ON DUPLICATE KEY
                          UPDATE record_id = VALUES(record_id),
-                                points = VALUES(points)",
+                                points = VALUES(points),
+                                points_formula_version = VALUES(points_formula_version)",
                         filter_id,
                         player_id,
                         record_id,
                         dist_points,
+                        points::formula::CURRENT.version,
                     )
                     .execute(&mut *conn)
                     .await?;
 
-                    if leaderboard_size <= points::SMALL_LEADERBOARD_THRESHOLD {
+                    if is_ranked && leaderboard_size <= points::SMALL_LEADERBOARD_THRESHOLD {
                         let mut top_time = None;
                         let leaderboard = sqlx::query!(
                             "SELECT
@@ -464,18 +674,22 @@ pub async fn submit(
                              JOIN BestProRecords ON BestProRecords.record_id = r.id
                              WHERE BestProRecords.filter_id = ?
                              ORDER BY time ASC",
-                             filter_id,
-                            )
-                            .fetch(&mut *conn)
-                            .map_ok(|row| {
-                                (row.player_id, row.id, points::for_small_leaderboard(
+                            filter_id,
+                        )
+                        .fetch(&mut *conn)
+                        .map_ok(|row| {
+                            (
+                                row.player_id,
+                                row.id,
+                                points::for_small_leaderboard(
                                     tier,
                                     *top_time.get_or_insert(row.time),
                                     row.time,
-                                ))
-                            })
-                            .try_collect::<Vec<_>>()
-                            .await?;
+                                ),
+                            )
+                        })
+                        .try_collect::<Vec<_>>()
+                        .await?;
 
                         let mut query = QueryBuilder::new(
                             "INSERT INTO BestProRecords (
@@ -483,28 +697,34 @@ pub async fn submit(
                                player_id,
                                record_id,
                                points,
-                               points_based_on_pro_leaderboard
+                               points_based_on_pro_leaderboard,
+                               points_formula_version
                              )",
                         );
 
-                        query.push_values(leaderboard, |mut query, (player_id, record_id, points)| {
-                            query.push_bind(filter_id);
-                            query.push_bind(player_id);
-                            query.push_bind(record_id);
-                            query.push_bind(points);
-                            query.push_bind(true);
-                        });
-
-                        query.push("ON DUPLICATE KEY UPDATE points = VALUES(points)");
-
-                        query
-                            .build()
-                            .persistent(false)
-                            .execute(&mut *conn)
-                            .await?;
+                        query.push_values(
+                            leaderboard,
+                            |mut query, (player_id, record_id, points)| {
+                                query.push_bind(filter_id);
+                                query.push_bind(player_id);
+                                query.push_bind(record_id);
+                                query.push_bind(points);
+                                query.push_bind(true);
+                                query.push_bind(points::formula::CURRENT.version);
+                            },
+                        );
+
+                        query.push(
+                            "ON DUPLICATE KEY
+                             UPDATE points = VALUES(points),
+                                    points_formula_version = VALUES(points_formula_version)",
+                        );
+
+                        query.build().persistent(false).execute(&mut *conn).await?;
                     }
 
-                    Ok(Some(move |rank| points::complete(tier, true, rank, dist_points)))
+                    Ok(is_ranked
+                        .then_some(move |rank| points::complete(tier, true, rank, dist_points)))
                 };
 
             let (calc_nub_points, calc_pro_points) = match (&old_nub, &old_pro, teleports) {
@@ -582,127 +802,40 @@ pub async fn submit(
             .fetch_one(&mut *conn)
             .await?;
 
+            // the daemon only updates ratings once it gets to the filter, but the player should
+            // see their new rating right away
+            players::update_player_rating_in(&mut *conn, player_id, mode).await?;
+
             sqlx::query!(
-                r#"WITH RankedPoints AS (
-                     SELECT
-                       source,
-                       record_id,
-                       ROW_NUMBER() OVER (
-                         PARTITION BY player_id
-                         ORDER BY points DESC
-                       ) AS n
-                     FROM ((
-                       SELECT "nub" AS source, record_id, player_id, points
-                       FROM BestNubRecords
-                       WHERE player_id = ?
-                     ) UNION ALL (
-                       SELECT "pro" AS source, record_id, player_id, points
-                       FROM BestProRecords
-                       WHERE player_id = ?
-                     )) AS _
-                   ),
-                   NubRecords AS (
-                     SELECT
-                       r.id AS record_id,
-                       r.player_id,
-                       cf.nub_tier AS tier,
-                       BestNubRecords.points,
-                       RANK() OVER (
-                         PARTITION BY r.filter_id
-                         ORDER BY
-                           r.time ASC,
-                           r.submitted_at ASC
-                       ) AS rank
-                     FROM Records AS r
-                     JOIN BestNubRecords ON BestNubRecords.record_id = r.id
-                     JOIN CourseFilters AS cf ON cf.id = r.filter_id
-                     WHERE r.player_id = ?
-                     AND cf.mode = ?
-                   ),
-                   ProRecords AS (
-                     SELECT
-                       r.id AS record_id,
-                       r.player_id,
-                       cf.pro_tier AS tier,
-                       BestProRecords.points,
-                       RANK() OVER (
-                         PARTITION BY r.filter_id
-                         ORDER BY
-                           r.time ASC,
-                           r.submitted_at ASC
-                       ) AS rank
-                     FROM Records AS r
-                     JOIN BestProRecords ON BestProRecords.record_id = r.id
-                     JOIN CourseFilters AS cf ON cf.id = r.filter_id
-                     WHERE r.player_id = ?
-                     AND cf.mode = ?
-                   ),
-                   NubRatings AS (
-                     SELECT
-                       player_id,
-                       SUM(KZ_POINTS(tier, false, rank - 1, points) * POWER(0.975, n - 1)) AS rating
-                     FROM NubRecords
-                     JOIN RankedPoints
-                       ON RankedPoints.record_id = NubRecords.record_id
-                       AND RankedPoints.source = "nub"
-                     GROUP BY player_id
-                   ),
-                   NubRankAndPoints AS (
-                     SELECT
-                       player_id,
-                       rank,
-                       SUM(KZ_POINTS(tier, false, rank - 1, points)) AS points
-                     FROM NubRecords
-                     WHERE record_id = ?
-                     GROUP BY player_id
-                   ),
-                   ProRatings AS (
-                     SELECT
-                       player_id,
-                       SUM(KZ_POINTS(tier, true, rank - 1, points) * POWER(0.975, n - 1)) AS rating
-                     FROM ProRecords
-                     JOIN RankedPoints
-                       ON RankedPoints.record_id = ProRecords.record_id
-                       AND RankedPoints.source = "pro"
-                     GROUP BY player_id
-                   ),
-                   ProRankAndPoints AS (
-                     SELECT
-                       player_id,
-                       rank,
-                       SUM(KZ_POINTS(tier, true, rank - 1, points)) AS points
-                     FROM ProRecords
-                     WHERE record_id = ?
-                     GROUP BY player_id
-                   )
+                "WITH NubLeaderboard AS (
                    SELECT
-                     (SELECT COUNT(*) FROM BestNubRecords WHERE filter_id = ?) AS nub_leaderboard_size,
-                     (SELECT COUNT(*) FROM BestProRecords WHERE filter_id = ?) AS pro_leaderboard_size,
-                     NubRatings.rating AS nub_rating,
-                     NubRankAndPoints.rank AS nub_rank,
-                     NubRankAndPoints.points AS nub_points,
-                     ProRatings.rating AS pro_rating,
-                     ProRankAndPoints.rank AS pro_rank,
-                     ProRankAndPoints.points AS pro_points
-                   FROM Players AS p
-                   LEFT JOIN NubRecords ON NubRecords.player_id = p.id
-                   LEFT JOIN ProRecords ON ProRecords.player_id = p.id
-                   LEFT JOIN NubRatings ON NubRatings.player_id = p.id
-                   LEFT JOIN NubRankAndPoints ON NubRankAndPoints.player_id = p.id
-                   LEFT JOIN ProRatings ON ProRatings.player_id = p.id
-                   LEFT JOIN ProRankAndPoints ON ProRankAndPoints.player_id = p.id
-                   WHERE p.id = ?"#,
-                player_id,
-                player_id,
-                player_id,
-                mode,
-                player_id,
-                mode,
-                record_id,
-                record_id,
+                     r.id AS record_id,
+                     RANK() OVER (ORDER BY r.time ASC, r.submitted_at ASC) AS rank
+                   FROM Records AS r
+                   JOIN BestNubRecords ON BestNubRecords.record_id = r.id
+                   WHERE r.filter_id = ?
+                 ),
+                 ProLeaderboard AS (
+                   SELECT
+                     r.id AS record_id,
+                     RANK() OVER (ORDER BY r.time ASC, r.submitted_at ASC) AS rank
+                   FROM Records AS r
+                   JOIN BestProRecords ON BestProRecords.record_id = r.id
+                   WHERE r.filter_id = ?
+                 )
+                 SELECT
+                   (SELECT COUNT(*) FROM NubLeaderboard) AS nub_leaderboard_size,
+                   (SELECT COUNT(*) FROM ProLeaderboard) AS pro_leaderboard_size,
+                   (SELECT rank FROM NubLeaderboard WHERE record_id = ?) AS nub_rank,
+                   (SELECT rank FROM ProLeaderboard WHERE record_id = ?) AS pro_rank,
+                   (SELECT rating FROM PlayerRatings WHERE player_id = ? AND `mode` = ?)
+                     AS player_rating",
                 filter_id,
                 filter_id,
+                record_id,
+                record_id,
                 player_id,
+                mode,
             )
             .fetch_one(&mut *conn)
             .await
@@ -712,13 +845,7 @@ pub async fn submit(
                 let nub_leaderboard_size = row.nub_leaderboard_size.map_or(0, |size| size as u32);
                 let pro_rank = row.pro_rank.map(|rank| rank as u32);
                 let pro_leaderboard_size = row.pro_leaderboard_size.map_or(0, |size| size as u32);
-                let player_rating = match (row.nub_rating, row.pro_rating) {
-                    (None, Some(_)) => unreachable!(),
-                    (None, None) => 0.0,
-                    (Some(nub_rating), None) => nub_rating,
-                    // ?
-                    (Some(nub_rating), Some(pro_rating)) => nub_rating + pro_rating,
-                };
+                let player_rating = row.player_rating.unwrap_or_default();
 
                 SubmittedRecord {
                     record_id,
@@ -763,7 +890,65 @@ pub async fn submit(
 #[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
 pub async fn get(
     cx: &Context,
-    GetRecordsParams {
+    params: GetRecordsParams,
+) -> Result<Paginated<Vec<Record>>, GetRecordsError> {
+    let GetRecordsParams { sort_by, cursor, limit, .. } = params;
+    let mut query = build_get_query(&params, true);
+
+    let mut total = None;
+    let mut last_sort_key = None;
+    let records = query
+        .build()
+        .fetch(cx.database().as_ref())
+        .map_err(GetRecordsError::from)
+        .and_then(|row| {
+            future::ready(match row.try_get::<Option<i64>, _>("total") {
+                Ok(value) => {
+                    total = value.map(|value| {
+                        value
+                            .try_into()
+                            .expect("`COUNT(…)` should not return a negative value")
+                    });
+
+                    Ok(row)
+                },
+                Err(error) => Err(error.into()),
+            })
+        })
+        .and_then(|row| {
+            future::ready(match SortKey::from_row(sort_by, &row) {
+                Ok(sort_key) => {
+                    last_sort_key = Some(sort_key);
+                    Ok(row)
+                },
+                Err(error) => Err(error.into()),
+            })
+        })
+        .and_then(async move |row| {
+            let mut record = Record::from_row(&row)?;
+            record.complete_points(row.try_get("nub_tier")?, row.try_get("pro_tier")?);
+
+            Ok(record)
+        })
+        .try_collect()
+        .await?;
+
+    let records = match total {
+        Some(total) => Paginated::new(total, records),
+        None if cursor.is_some() => Paginated::without_total(records),
+        None => Paginated::new(0, records),
+    };
+
+    Ok(records.with_next_cursor(limit.value(), |record: &Record| {
+        Cursor::new(last_sort_key.expect("we have at least one record"), record.id)
+    }))
+}
+
+/// Builds the query shared by [`get()`] and [`export()`].
+///
+/// `cursor`, `limit` and `offset` are only applied if `paginate` is `true`.
+fn build_get_query(
+    &GetRecordsParams {
         top,
         player_id,
         server_id,
@@ -772,12 +957,19 @@ pub async fn get(
         mode,
         has_teleports,
         max_rank,
+        styles,
+        submitted_after,
+        submitted_before,
+        plugin_version_id,
+        min_points,
         sort_by,
         sort_order,
+        cursor,
         limit,
         offset,
-    }: GetRecordsParams,
-) -> Result<Paginated<Vec<Record>>, GetRecordsError> {
+    }: &GetRecordsParams,
+    paginate: bool,
+) -> QueryBuilder<'static> {
     fn base_filters(
         query: &mut QueryBuilder<'_>,
         player_id: Option<PlayerId>,
@@ -811,7 +1003,7 @@ pub async fn get(
         "WITH NubLeaderboard AS (
            SELECT
              r.id AS record_id,
-             NubRecords.points,
+             IF(cf.id IN (SELECT id FROM RankedCourseFilters), NubRecords.points, NULL) AS points,
              RANK() OVER (
                PARTITION BY r.filter_id
                ORDER BY
@@ -834,7 +1026,7 @@ pub async fn get(
         "ProLeaderboard AS (
            SELECT
              r.id AS record_id,
-             ProRecords.points,
+             IF(cf.id IN (SELECT id FROM RankedCourseFilters), ProRecords.points, NULL) AS points,
              RANK() OVER (
                PARTITION BY r.filter_id
                ORDER BY
@@ -853,10 +1045,17 @@ pub async fn get(
     base_filters(&mut query, player_id, server_id, map_id, course_id, mode);
 
     query.push(") ");
+
+    let cursor = cursor.filter(|_| paginate);
+
+    // the total is meaningless for cursor pages, since the cursor filters out previous records,
+    // and exports don't have one
+    query.push("SELECT ").push(match cursor {
+        None if paginate => "COUNT(NubLeaderboard.rank) + COUNT(ProLeaderboard.rank) AS total, ",
+        _ => "NULL AS total, ",
+    });
     query.push(
-        "SELECT
-           COUNT(NubLeaderboard.rank) + COUNT(ProLeaderboard.rank) AS total,
-           r.id AS id,
+        "r.id AS id,
            p.id AS player_id,
            p.name AS player_name,
            s.id AS server_id,
@@ -875,8 +1074,12 @@ pub async fn get(
            NubLeaderboard.points AS nub_points,
            ProLeaderboard.rank AS pro_rank,
            ProLeaderboard.points AS pro_points,
-           r.submitted_at
-         FROM Records AS r
+           r.submitted_at, ",
+    );
+    query.push(NUB_POINTS_SQL).push(" AS nub_points_total, ");
+    query.push(PRO_POINTS_SQL).push(" AS pro_points_total ");
+    query.push(
+        "FROM Records AS r
          LEFT JOIN NubLeaderboard ON NubLeaderboard.record_id = r.id
          LEFT JOIN ProLeaderboard ON ProLeaderboard.record_id = r.id
          JOIN Players AS p ON p.id = r.player_id
@@ -896,6 +1099,70 @@ pub async fn get(
         has_where = true;
     }
 
+    if let Some(styles) = styles {
+        query.push(if has_where { " AND " } else { " WHERE " });
+        query.push(" (r.styles & ");
+        query.push_bind(styles);
+        query.push(") = ");
+        query.push_bind(styles);
+
+        has_where = true;
+    }
+
+    if let Some(submitted_after) = submitted_after {
+        query.push(if has_where { " AND " } else { " WHERE " });
+        query.push(" r.submitted_at > ");
+        query.push_bind(submitted_after);
+
+        has_where = true;
+    }
+
+    if let Some(submitted_before) = submitted_before {
+        query.push(if has_where { " AND " } else { " WHERE " });
+        query.push(" r.submitted_at < ");
+        query.push_bind(submitted_before);
+
+        has_where = true;
+    }
+
+    if let Some(plugin_version_id) = plugin_version_id {
+        query.push(if has_where { " AND " } else { " WHERE " });
+        query.push(" r.plugin_version_id = ");
+        query.push_bind(plugin_version_id);
+
+        has_where = true;
+    }
+
+    if let Some(min_points) = min_points {
+        // aliases from the select list cannot be used in `WHERE`, so we have to compute the
+        // points again
+        query.push(if has_where { " AND " } else { " WHERE " });
+        query
+            .push(" (")
+            .push(NUB_POINTS_SQL)
+            .push(" >= ")
+            .push_bind(min_points);
+        query
+            .push(" OR ")
+            .push(PRO_POINTS_SQL)
+            .push(" >= ")
+            .push_bind(min_points);
+        query.push(")");
+
+        has_where = true;
+    }
+
+    let sort_order = sort_order.unwrap_or_else(|| SortOrder::from_sort_by(sort_by));
+
+    if let Some(ref cursor) = cursor {
+        query.push(if has_where { " AND " } else { " WHERE " });
+        cursor
+            .sort_key()
+            .push_after(&mut query, *cursor.id(), sort_order);
+
+        has_where = true;
+    }
+
     if let Some(max_rank) = max_rank {
         query.push(if has_where { " AND " } else { " WHERE " });
         query.push(" (NubLeaderboard.rank <= ");
@@ -915,62 +1182,42 @@ pub async fn get(
     query
         .push(" ORDER BY ")
         .push(sort_by.sql())
-        .push(
-            sort_order
-                .unwrap_or_else(|| SortOrder::from_sort_by(sort_by))
-                .sql(),
-        )
-        .push(" LIMIT ")
-        .push_bind(limit.value())
-        .push(" OFFSET ")
-        .push_bind(offset.value());
+        .push(sort_order.sql())
+        .push(", r.id ")
+        .push(sort_order.sql());
+
+    if paginate {
+        query
+            .push(" LIMIT ")
+            .push_bind(limit.value())
+            .push(" OFFSET ")
+            .push_bind(offset.value());
+    }
 
-    let mut total = 0;
-    let records = query
-        .build()
-        .fetch(cx.database().as_ref())
-        .map_err(GetRecordsError::from)
-        .and_then(|row| {
-            future::ready(match row.try_get::<i64, _>("total") {
-                Ok(value) => {
-                    total = value
-                        .try_into()
-                        .expect("`COUNT(…)` should not return a negative value");
+    query
+}
 
-                    Ok(row)
-                },
-                Err(error) => Err(error.into()),
-            })
-        })
-        .and_then(async move |row| {
-            let mut record = Record::from_row(&row)?;
-            let nub_tier = row.try_get::<Tier, _>("nub_tier")?;
-            let pro_tier = row.try_get::<Tier, _>("pro_tier")?;
-
-            record.nub_points = record.nub_rank.map(|nub_rank| {
-                points::complete(
-                    nub_tier,
-                    false,
-                    nub_rank as usize - 1,
-                    record.nub_points.unwrap_or_default(),
-                )
-            });
-
-            record.pro_points = record.pro_rank.map(|pro_rank| {
-                points::complete(
-                    pro_tier,
-                    true,
-                    pro_rank as usize - 1,
-                    record.pro_points.unwrap_or_default(),
-                )
-            });
+/// Returns a stream of all records matching the given filters.
+///
+/// Unlike [`get()`], this does not paginate or buffer any results, so it is suitable for
+/// exporting large amounts of records. `cursor`, `limit` and `offset` are ignored.
+#[tracing::instrument(skip(cx))]
+pub fn export(
+    cx: &Context,
+    params: GetRecordsParams,
+) -> impl Stream<Item = Result<Record, GetRecordsError>> {
+    let mut query = build_get_query(&params, false);
 
-            Ok(record)
-        })
-        .try_collect()
-        .await?;
+    async_stream::try_stream! {
+        let mut rows = query.build().fetch(cx.database().as_ref());
 
-    Ok(Paginated::new(total, records))
+        while let Some(row) = rows.try_next().await? {
+            let mut record = Record::from_row(&row)?;
+            record.complete_points(row.try_get("nub_tier")?, row.try_get("pro_tier")?);
+
+            yield record;
+        }
+    }
 }
 
 #[tracing::instrument(skip(cx))]
@@ -986,25 +1233,7 @@ pub fn get_player_records(
     .fetch(cx.database().as_ref())
     .map_ok(move |row| {
         let mut record = self::macros::parse_row!(row);
-
-        record.nub_points = record.nub_rank.map(|nub_rank| {
-            points::complete(
-                row.nub_tier,
-                false,
-                nub_rank as usize - 1,
-                record.nub_points.unwrap_or_default(),
-            )
-        });
-
-        record.pro_points = record.pro_rank.map(|pro_rank| {
-            points::complete(
-                row.pro_tier,
-                true,
-                pro_rank as usize - 1,
-                record.pro_points.unwrap_or_default(),
-            )
-        });
-
+        record.complete_points(row.nub_tier, row.pro_tier);
         record
     })
     .map_err(GetRecordsError::from)
@@ -1040,10 +1269,140 @@ pub async fn get_by_id(
     self::macros::select!("WHERE r.id = ?", record_id;)
         .fetch_optional(cx.database().as_ref())
         .await
-        .map(|row| row.map(|row| self::macros::parse_row!(row)))
+        .map(|row| {
+            row.map(|row| {
+                let mut record = self::macros::parse_row!(row);
+                record.complete_points(row.nub_tier, row.pro_tier);
+                record
+            })
+        })
         .map_err(GetRecordsError::from)
 }
 
+/// Returns the individual components of a record's points.
+///
+/// Returns [`None`] if the record does not exist.
+#[tracing::instrument(skip(cx), ret(level = "debug"), err(level = "debug"))]
+pub async fn get_points_breakdown(
+    cx: &Context,
+    record_id: RecordId,
+) -> Result<Option<PointsBreakdown>, GetRecordsError> {
+    let Some(row) = sqlx::query!(
+        "WITH NubLeaderboard AS (
+           SELECT
+             b.record_id,
+             b.points,
+             RANK() OVER (ORDER BY r.time ASC, r.submitted_at ASC) AS rank
+           FROM BestNubRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           WHERE b.filter_id = (SELECT filter_id FROM Records WHERE id = ?)
+         ),
+         ProLeaderboard AS (
+           SELECT
+             b.record_id,
+             b.points,
+             b.points_based_on_pro_leaderboard,
+             RANK() OVER (ORDER BY r.time ASC, r.submitted_at ASC) AS rank
+           FROM BestProRecords AS b
+           JOIN Records AS r ON r.id = b.record_id
+           WHERE b.filter_id = (SELECT filter_id FROM Records WHERE id = ?)
+         )
+         SELECT
+           cf.nub_tier AS `nub_tier: Tier`,
+           cf.pro_tier AS `pro_tier: Tier`,
+           nub.points AS nub_points,
+           nub.rank AS nub_rank,
+           pro.points AS pro_points,
+           pro.rank AS pro_rank,
+           pro.points_based_on_pro_leaderboard AS `points_based_on_pro_leaderboard: bool`,
+           (SELECT COUNT(*) FROM NubLeaderboard) AS `nub_leaderboard_size!`,
+           (SELECT COUNT(*) FROM ProLeaderboard) AS `pro_leaderboard_size!`,
+           (SELECT leaderboard_size
+            FROM PointDistributionData
+            WHERE filter_id = r.filter_id
+            AND is_pro_leaderboard = FALSE) AS nub_fitted_on,
+           (SELECT leaderboard_size
+            FROM PointDistributionData
+            WHERE filter_id = r.filter_id
+            AND is_pro_leaderboard = TRUE) AS pro_fitted_on,
+           (SELECT COUNT(*) FROM RankedCourseFilters WHERE id = r.filter_id) AS `is_ranked!`
+         FROM Records AS r
+         JOIN CourseFilters AS cf ON cf.id = r.filter_id
+         LEFT JOIN NubLeaderboard AS nub ON nub.record_id = r.id
+         LEFT JOIN ProLeaderboard AS pro ON pro.record_id = r.id
+         WHERE r.id = ?",
+        record_id,
+        record_id,
+        record_id,
+    )
+    .fetch_optional(cx.database().as_ref())
+    .await?
+    else {
+        return Ok(None);
+    };
+
+    let nub_leaderboard_size = row.nub_leaderboard_size as u32;
+    let pro_leaderboard_size = row.pro_leaderboard_size as u32;
+
+    // points were calculated when the distribution was fitted, so whether the leaderboard was
+    // small is decided by its size back then; without a distribution we can only go by its
+    // current size
+    let nub_fitted_on = row.nub_fitted_on.unwrap_or(nub_leaderboard_size);
+    let pro_fitted_on = row.pro_fitted_on.unwrap_or(pro_leaderboard_size);
+    let is_ranked = row.is_ranked > 0;
+
+    if !is_ranked {
+        return Ok(Some(PointsBreakdown { is_ranked, nub: None, pro: None }));
+    }
+
+    let leaderboard_points = |tier: Tier,
+                              is_pro_leaderboard: bool,
+                              rank: Option<i64>,
+                              dist_points: Option<f64>,
+                              based_on_pro_leaderboard: bool| {
+        let (rank, dist_points) = Option::zip(rank, dist_points)?;
+        let rank = rank as u32;
+
+        // points are taken from whichever leaderboard they were calculated on
+        let fitted_on = if based_on_pro_leaderboard {
+            pro_fitted_on
+        } else {
+            nub_fitted_on
+        };
+
+        Some(LeaderboardPoints {
+            tier,
+            rank,
+            leaderboard_size: if is_pro_leaderboard {
+                pro_leaderboard_size
+            } else {
+                nub_leaderboard_size
+            },
+            dist_points,
+            small_leaderboard: fitted_on as usize <= points::SMALL_LEADERBOARD_THRESHOLD,
+            based_on_pro_leaderboard,
+            breakdown: points::breakdown(
+                tier,
+                is_pro_leaderboard,
+                (rank - 1) as usize,
+                dist_points,
+            ),
+        })
+    };
+
+    Ok(Some(PointsBreakdown {
+        is_ranked,
+        nub: leaderboard_points(row.nub_tier, false, row.nub_rank, row.nub_points, false),
+        pro: leaderboard_points(
+            row.pro_tier,
+            true,
+            row.pro_rank,
+            row.pro_points,
+            row.points_based_on_pro_leaderboard.unwrap_or_default(),
+        ),
+    }))
+}
+
 #[tracing::instrument(skip(cx), err(level = "debug"))]
 pub async fn get_replay(
     cx: &Context,
@@ -1065,6 +1424,41 @@ pub async fn count_by_filter(
         .map_err(GetRecordsError::from)
 }
 
+/// Removes the points of every best record on a filter.
+///
+/// The records themselves stay on the leaderboards; this is used when a filter stops being
+/// ranked.
+#[tracing::instrument(skip(cx), err(level = "debug"))]
+pub async fn clear_points(cx: &Context, filter_id: CourseFilterId) -> Result<(), GetRecordsError> {
+    cx.database_transaction(async move |conn| {
+        sqlx::query!(
+            "UPDATE BestNubRecords
+             SET points = 0,
+                 points_formula_version = ?
+             WHERE filter_id = ?",
+            points::formula::CURRENT.version,
+            filter_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        sqlx::query!(
+            "UPDATE BestProRecords
+             SET points = 0,
+                 points_based_on_pro_leaderboard = TRUE,
+                 points_formula_version = ?
+             WHERE filter_id = ?",
+            points::formula::CURRENT.version,
+            filter_id,
+        )
+        .execute(&mut *conn)
+        .await?;
+
+        Ok(())
+    })
+    .await
+}
+
 #[tracing::instrument(skip(cx, records), err(level = "debug"))]
 pub async fn update_best_records(
     cx: &Context,
@@ -1075,12 +1469,23 @@ pub async fn update_best_records(
     // length limits.
     const MAX_CHUNK_SIZE: usize = 1_000;
 
-    let (nub, pro) = records
-        .into_iter()
-        .partition::<Vec<_>, _>(|record| record.nub_points != 0.0);
+    let records = records.into_iter().collect::<Vec<_>>();
 
-    let mut nub = nub.into_iter();
-    let mut pro = pro.into_iter();
+    // a record can be the player's best on both leaderboards, in which case it has to end up in
+    // both tables
+    let mut nub = records
+        .iter()
+        .filter(|record| record.nub_points != 0.0)
+        .copied()
+        .collect::<Vec<_>>()
+        .into_iter();
+
+    let mut pro = records
+        .iter()
+        .filter(|record| record.pro_points != ProPoints::default())
+        .copied()
+        .collect::<Vec<_>>()
+        .into_iter();
 
     cx.database_transaction(async move |conn| {
         let mut nub_query = QueryBuilder::new(
@@ -1088,7 +1493,8 @@ pub async fn update_best_records(
                filter_id,
                player_id,
                record_id,
-               points
+               points,
+               points_formula_version
              )",
         );
 
@@ -1098,7 +1504,8 @@ pub async fn update_best_records(
                player_id,
                record_id,
                points,
-               points_based_on_pro_leaderboard
+               points_based_on_pro_leaderboard,
+               points_formula_version
              )",
         );
 
@@ -1110,12 +1517,14 @@ pub async fn update_best_records(
                     query.push_bind(record.player_id);
                     query.push_bind(record.id);
                     query.push_bind(record.nub_points);
+                    query.push_bind(points::formula::CURRENT.version);
                 });
 
                 nub_query.push(
                     "ON DUPLICATE KEY
                      UPDATE record_id = VALUES(record_id),
-                            points = VALUES(points)",
+                            points = VALUES(points),
+                            points_formula_version = VALUES(points_formula_version)",
                 );
 
                 nub_query
@@ -1133,13 +1542,15 @@ pub async fn update_best_records(
                     query.push_bind(record.id);
                     query.push_bind(record.pro_points.value);
                     query.push_bind(record.pro_points.based_on_pro_leaderboard);
+                    query.push_bind(points::formula::CURRENT.version);
                 });
 
                 pro_query.push(
                     "ON DUPLICATE KEY
                      UPDATE record_id = VALUES(record_id),
                             points = VALUES(points),
-                            points_based_on_pro_leaderboard = VALUES(points_based_on_pro_leaderboard)",
+                            points_based_on_pro_leaderboard = VALUES(points_based_on_pro_leaderboard),
+                            points_formula_version = VALUES(points_formula_version)",
                 );
 
                 pro_query
@@ -1242,7 +1653,7 @@ mod macros {
                 "WITH NubLeaderboard AS (
                    SELECT
                      r.id AS record_id,
-                     NubRecords.points,
+                     IF(cf.id IN (SELECT id FROM RankedCourseFilters), NubRecords.points, NULL) AS points,
                      RANK() OVER (
                        PARTITION BY r.filter_id
                        ORDER BY
@@ -1261,7 +1672,7 @@ mod macros {
                 + "ProLeaderboard AS (
                    SELECT
                      r.id AS record_id,
-                     ProRecords.points,
+                     IF(cf.id IN (SELECT id FROM RankedCourseFilters), ProRecords.points, NULL) AS points,
                      RANK() OVER (
                        PARTITION BY r.filter_id
                        ORDER BY
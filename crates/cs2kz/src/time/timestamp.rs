@@ -69,3 +69,11 @@ impl ops::Add<Timestamp> for time::Duration {
         timestamp + self
     }
 }
+
+impl ops::Sub<Timestamp> for Timestamp {
+    type Output = time::Duration;
+
+    fn sub(self, other: Timestamp) -> Self::Output {
+        self.0 - other.0
+    }
+}
@@ -23,10 +23,6 @@
         overlays = [ (import inputs.rust-overlay) ];
         pkgs = import nixpkgs { inherit system overlays; };
 
-        python = pkgs.python3.withPackages (p: with p; [
-          scipy
-        ]);
-
         rust-toolchain =
           pkgs.rust-bin.fromRustupToolchainFile ./rust-toolchain.toml;
 
@@ -54,9 +50,7 @@
         commonArgs = {
           inherit src;
           strictDeps = true;
-          buildInputs = [ python ];
           env = {
-            PYO3_PYTHON = "${python}/bin/python";
             SQLX_OFFLINE = true;
           };
         };
@@ -117,7 +111,7 @@
         };
 
         devShells.default = pkgs.mkShell {
-          nativeBuildInputs = [ rust-toolchain python ] ++ (with pkgs; [
+          nativeBuildInputs = [ rust-toolchain ] ++ (with pkgs; [
             docker-client
             lazydocker
             mycli
@@ -126,8 +120,6 @@
             depotdownloader
             oha
           ]);
-
-          PYO3_PYTHON = "${python}/bin/python";
         };
       });
 }